@@ -0,0 +1,66 @@
+// Composites an offscreen layer over a copy of the backdrop for blend modes
+// that fixed-function blending can't express. Both inputs are premultiplied.
+
+@group(0) @binding(0) var backdrop: texture_2d<f32>;
+@group(0) @binding(1) var layer: texture_2d<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    // A single triangle that covers the whole target.
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+fn unpremultiply(color: vec4<f32>) -> vec3<f32> {
+    if (color.a <= 0.0) {
+        return vec3<f32>(0.0);
+    }
+    return color.rgb / color.a;
+}
+
+// Separable blend: `mixed` is B(cb, cs) computed on unpremultiplied colors.
+fn composite(dst: vec4<f32>, src: vec4<f32>, mixed: vec3<f32>) -> vec4<f32> {
+    let rgb = src.rgb * (1.0 - dst.a) + dst.rgb * (1.0 - src.a) + src.a * dst.a * mixed;
+    let a = src.a + dst.a * (1.0 - src.a);
+    return vec4<f32>(rgb, a);
+}
+
+fn load_inputs(position: vec4<f32>) -> array<vec4<f32>, 2> {
+    let coords = vec2<i32>(position.xy);
+    return array<vec4<f32>, 2>(
+        textureLoad(backdrop, coords, 0),
+        textureLoad(layer, coords, 0),
+    );
+}
+
+@fragment
+fn fs_multiply(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let inputs = load_inputs(position);
+    let mixed = unpremultiply(inputs[0]) * unpremultiply(inputs[1]);
+    return composite(inputs[0], inputs[1], mixed);
+}
+
+@fragment
+fn fs_overlay(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let inputs = load_inputs(position);
+    let cb = unpremultiply(inputs[0]);
+    let cs = unpremultiply(inputs[1]);
+    let multiply = cs * (2.0 * cb);
+    let screen = 1.0 - (1.0 - cs) * (1.0 - (2.0 * cb - 1.0));
+    let mixed = select(screen, multiply, cb <= vec3<f32>(0.5));
+    return composite(inputs[0], inputs[1], mixed);
+}
+
+@fragment
+fn fs_darken(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let inputs = load_inputs(position);
+    let mixed = min(unpremultiply(inputs[0]), unpremultiply(inputs[1]));
+    return composite(inputs[0], inputs[1], mixed);
+}
+
+@fragment
+fn fs_lighten(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let inputs = load_inputs(position);
+    let mixed = max(unpremultiply(inputs[0]), unpremultiply(inputs[1]));
+    return composite(inputs[0], inputs[1], mixed);
+}
@@ -0,0 +1,97 @@
+// Filter effects over a group's resolved offscreen texture. Every pass writes
+// the whole target with a fullscreen triangle. Colors are premultiplied, and
+// reads past the edge clamp to the nearest pixel, like `FilterImage::get`.
+
+struct FilterParams {
+    color_matrix: array<vec4<f32>, 4>,
+    color_offset: vec4<f32>,
+    color: vec4<f32>,
+    direction: vec2<i32>,
+    offset: vec2<i32>,
+    sigma: f32,
+    radius: i32,
+    _pad: vec2<f32>,
+};
+
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var auxiliary: texture_2d<f32>;
+@group(0) @binding(2) var<uniform> params: FilterParams;
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+fn load_source(coords: vec2<i32>) -> vec4<f32> {
+    let last = vec2<i32>(textureDimensions(source)) - vec2<i32>(1, 1);
+    return textureLoad(source, clamp(coords, vec2<i32>(0, 0), last), 0);
+}
+
+fn weight(i: i32) -> f32 {
+    let x = f32(i);
+    return exp(-(x * x) / (2.0 * params.sigma * params.sigma));
+}
+
+@fragment
+fn fs_blur(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let coords = vec2<i32>(position.xy);
+    if (params.sigma <= 0.0) {
+        return load_source(coords);
+    }
+    var sum = vec4<f32>(0.0);
+    var total = 0.0;
+    for (var i: i32 = -params.radius; i <= params.radius; i = i + 1) {
+        let w = weight(i);
+        sum = sum + load_source(coords + params.direction * i) * w;
+        total = total + w;
+    }
+    return sum / total;
+}
+
+// First half of a drop shadow: the source alpha, shifted and tinted, blurred
+// along `direction`.
+@fragment
+fn fs_shadow_blur(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let coords = vec2<i32>(position.xy) - params.offset;
+    if (params.sigma <= 0.0) {
+        return params.color * load_source(coords).a;
+    }
+    var sum = vec4<f32>(0.0);
+    var total = 0.0;
+    for (var i: i32 = -params.radius; i <= params.radius; i = i + 1) {
+        let w = weight(i);
+        sum = sum + params.color * load_source(coords + params.direction * i).a * w;
+        total = total + w;
+    }
+    return sum / total;
+}
+
+// Draws the source over the finished shadow in `auxiliary`.
+@fragment
+fn fs_shadow_merge(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let coords = vec2<i32>(position.xy);
+    let src = load_source(coords);
+    let shadow = textureLoad(auxiliary, coords, 0);
+    return src + shadow * (1.0 - src.a);
+}
+
+@fragment
+fn fs_color_matrix(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = load_source(vec2<i32>(position.xy));
+    var color = vec4<f32>(0.0);
+    if (pixel.a > 0.0) {
+        color = vec4<f32>(pixel.rgb / pixel.a, pixel.a);
+    }
+    let out = clamp(
+        vec4<f32>(
+            dot(params.color_matrix[0], color),
+            dot(params.color_matrix[1], color),
+            dot(params.color_matrix[2], color),
+            dot(params.color_matrix[3], color),
+        ) + params.color_offset,
+        vec4<f32>(0.0),
+        vec4<f32>(1.0),
+    );
+    return vec4<f32>(out.rgb * out.a, out.a);
+}
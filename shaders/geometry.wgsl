@@ -0,0 +1,40 @@
+// Draws the tessellated geometry of a canvas layer. Vertices are in picture
+// space, placed in the view by the camera's view matrix. Primitive colors are
+// linear and premultiplied, and are passed through as is.
+
+struct Globals {
+    picture_resolution: vec2<f32>,
+    view_matrix: mat4x4<f32>,
+};
+
+struct Primitive {
+    color: vec4<f32>,
+};
+
+struct Primitives {
+    primitives: array<Primitive>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> globals: Globals;
+@group(0) @binding(1) var<storage, read> primitives: Primitives;
+
+@vertex
+fn vs_main(
+    @location(0) position: vec2<f32>,
+    @location(1) prim_id: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = globals.view_matrix * vec4<f32>(position, 0.0, 1.0);
+    out.color = primitives.primitives[prim_id].color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
@@ -0,0 +1,29 @@
+// Draws a group's offscreen texture onto its parent target, scaled by the
+// group opacity. The texture holds premultiplied colors.
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) opacity: f32,
+};
+
+@group(0) @binding(0) var group_texture: texture_2d<f32>;
+@group(0) @binding(1) var group_sampler: sampler;
+
+@vertex
+fn vs_main(
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) opacity: f32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4<f32>(position, 0.0, 1.0);
+    out.uv = uv;
+    out.opacity = opacity;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(group_texture, group_sampler, in.uv) * in.opacity;
+}
@@ -0,0 +1,45 @@
+// Draws a symbol's mesh once per instance. Each instance places the mesh
+// with its own affine transform, and multiplies the primitive colors by its
+// own color. Both are linear and premultiplied, and so is their product.
+
+struct Globals {
+    picture_resolution: vec2<f32>,
+    view_matrix: mat4x4<f32>,
+};
+
+struct Primitive {
+    color: vec4<f32>,
+};
+
+struct Primitives {
+    primitives: array<Primitive>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> globals: Globals;
+@group(0) @binding(1) var<storage, read> primitives: Primitives;
+
+@vertex
+fn vs_main(
+    @location(0) position: vec2<f32>,
+    @location(1) prim_id: u32,
+    @location(2) transform_x: vec2<f32>,
+    @location(3) transform_y: vec2<f32>,
+    @location(4) translation: vec2<f32>,
+    @location(5) instance_color: vec4<f32>,
+) -> VertexOutput {
+    let placed = transform_x * position.x + transform_y * position.y + translation;
+    var out: VertexOutput;
+    out.position = globals.view_matrix * vec4<f32>(placed, 0.0, 1.0);
+    out.color = primitives.primitives[prim_id].color * instance_color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
@@ -0,0 +1,47 @@
+// Writes `prim_id + 1` of the topmost primitive into an integer target, so
+// zero means nothing was drawn. Uses the same vertex buffer and bind group as
+// the geometry pipelines, and the same instance buffer as the instanced
+// pipelines. Instances write the `prim_id` of the op that placed them.
+
+struct Globals {
+    picture_resolution: vec2<f32>,
+    view_matrix: mat4x4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) @interpolate(flat) prim_id: u32,
+};
+
+@group(0) @binding(0) var<uniform> globals: Globals;
+
+@vertex
+fn vs_main(
+    @location(0) position: vec2<f32>,
+    @location(1) prim_id: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = globals.view_matrix * vec4<f32>(position, 0.0, 1.0);
+    out.prim_id = prim_id;
+    return out;
+}
+
+@vertex
+fn vs_instanced(
+    @location(0) position: vec2<f32>,
+    @location(2) transform_x: vec2<f32>,
+    @location(3) transform_y: vec2<f32>,
+    @location(4) translation: vec2<f32>,
+    @location(6) instance_prim_id: u32,
+) -> VertexOutput {
+    let placed = transform_x * position.x + transform_y * position.y + translation;
+    var out: VertexOutput;
+    out.position = globals.view_matrix * vec4<f32>(placed, 0.0, 1.0);
+    out.prim_id = instance_prim_id;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) u32 {
+    return in.prim_id + 1u;
+}
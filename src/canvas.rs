@@ -1,5 +1,6 @@
 pub mod wgpu_backend;
 pub mod cg_backend;
+pub mod worker;
 
 use std::marker::PhantomData;
 
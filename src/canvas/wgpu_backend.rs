@@ -3,6 +3,11 @@ pub mod update;
 pub mod draw;
 pub mod helpers;
 pub mod gpu_target;
+pub mod pipeline;
+pub mod composite;
+pub mod group;
+pub mod filter;
+pub mod picking;
 
 use std::marker::PhantomData;
 
@@ -11,6 +16,9 @@ use crate::data::{Content, TessellatedContent};
 use crate::data::draw_cmds::{DrawOp, FillOp, FillStrokeOp, StrokeOp};
 use crate::frontend::DrawableObject;
 use crate::frontend::SceneTessellator;
+use crate::data::camera::Camera;
+use crate::data::optimize::IndexChunk;
+use crate::data::culling::CullIndex;
 
 pub struct WgpuBackend<const N: usize> {
     pub gpu_handle: gpu_target::GpuHandle,
@@ -19,16 +27,33 @@ pub struct WgpuBackend<const N: usize> {
 
 pub struct CanvasLayer  {
     pub scene_tessellator: SceneTessellator,
+    /// Places the picture in the view. Takes effect on the next `draw`.
+    pub camera: Camera,
     pub ibo: wgpu::Buffer,
     pub vbo: wgpu::Buffer,
+    /// Per-instance data of instanced symbol batches, bound to slot 1.
+    pub instance_buffer: wgpu::Buffer,
+    /// Uploads an `OptimizedMesh` instead of the tessellator's mesh: welded
+    /// vertices and mostly 16-bit indices, at the cost of re-optimizing and
+    /// re-uploading the whole mesh on every change. Best for layers that
+    /// rarely change.
+    pub optimize_mesh: bool,
+    /// How `ibo` is laid out when `optimize_mesh` is set. Empty means plain
+    /// 32-bit indices.
+    pub index_chunks: Vec<IndexChunk>,
+    /// Bounds of what the tessellator's top-level batches draw, so `draw`
+    /// only issues what the camera can see.
+    pub cull_index: CullIndex,
     pub prim_buffer_byte_size: u64,
     pub prims_ssbo: wgpu::Buffer,
     pub globals_buffer_byte_size: u64,
     pub globals_ubo: wgpu::Buffer,
     pub bind_group_layout: wgpu::BindGroupLayout,
     pub bind_group: wgpu::BindGroup,
-    pub render_pipeline: wgpu::RenderPipeline,
-    pub wireframe_render_pipeline: wgpu::RenderPipeline,
+    pub pipelines: pipeline::PipelineCache,
+    pub composite: composite::CompositePass,
+    pub groups: group::GroupPass,
+    pub picking: picking::PickingPass,
     pub msaa_texture: Option<wgpu::TextureView>,
     pub msaa_samples: u32,
     /// We need to write GPU buffers at least once.
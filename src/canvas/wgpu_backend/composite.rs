@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+use wgpu::include_wgsl;
+use super::gpu_target::GpuHandle;
+use crate::data::ViewResolution;
+use crate::data::draw_cmds::BlendMode;
+use crate::data::picture::DrawNode;
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// COMPOSITE PASS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// Read-back compositing for blend modes where
+/// [`BlendMode::requires_composite_pass_over`] holds.
+///
+/// The batch is drawn source-over into a transparent offscreen `layer`, the
+/// frame rendered so far is copied into `backdrop`, and a fullscreen pass
+/// writes the blended result back into the main target.
+pub struct CompositePass {
+    pub module: wgpu::ShaderModule,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub pipeline_layout: wgpu::PipelineLayout,
+    pub pipelines: HashMap<BlendMode, wgpu::RenderPipeline>,
+    pub targets: Option<CompositeTargets>,
+    pub msaa_samples: u32,
+}
+
+pub struct CompositeTargets {
+    pub view_resolution: ViewResolution,
+    pub backdrop: wgpu::Texture,
+    pub layer_view: wgpu::TextureView,
+    pub layer_msaa: Option<wgpu::TextureView>,
+    pub bind_group: wgpu::BindGroup,
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// INIT COMPOSITE PASS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+impl CompositePass {
+    pub fn new(handle: &GpuHandle, msaa_samples: u32) -> Self {
+        let module = handle.device.create_shader_module(include_wgsl!("./../../../shaders/blend_composite.wgsl"));
+        let texture_entry = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = handle.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Composite bind group layout"),
+            entries: &[texture_entry(0), texture_entry(1)],
+        });
+        let pipeline_layout = handle.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+            label: Some("Composite pipeline layout"),
+        });
+        CompositePass {
+            module,
+            bind_group_layout,
+            pipeline_layout,
+            pipelines: HashMap::new(),
+            targets: None,
+            msaa_samples,
+        }
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// UPDATE COMPOSITE PASS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+impl CompositePass {
+    /// Creates the pipelines and offscreen textures the given nodes need.
+    /// Nothing is allocated for scenes that only use fixed-function modes.
+    /// `opaque` tells whether the frame is cleared to an opaque color.
+    pub fn prepare(&mut self, handle: &GpuHandle, view_resolution: ViewResolution, nodes: &[DrawNode], opaque: bool) {
+        let mut modes = Vec::new();
+        composite_modes(nodes, opaque, &mut modes);
+        let mut any_composite = false;
+        for blend_mode in modes {
+            any_composite = true;
+            if !self.pipelines.contains_key(&blend_mode) {
+                let pipeline = self.create_pipeline(handle, blend_mode);
+                self.pipelines.insert(blend_mode, pipeline);
+            }
+        }
+        if !any_composite {
+            return
+        }
+        let targets_changed = self.targets
+            .as_ref()
+            .map(|targets| targets.view_resolution.resolution_changed(&view_resolution))
+            .unwrap_or(true);
+        if targets_changed {
+            self.targets = Some(self.create_targets(handle, view_resolution));
+        }
+    }
+    fn create_pipeline(&self, handle: &GpuHandle, blend_mode: BlendMode) -> wgpu::RenderPipeline {
+        let entry_point = match blend_mode {
+            BlendMode::Multiply => "fs_multiply",
+            BlendMode::Overlay => "fs_overlay",
+            BlendMode::Darken => "fs_darken",
+            BlendMode::Lighten => "fs_lighten",
+            _ => unreachable!("[CompositePass] {:?} doesn't need a composite pass", blend_mode),
+        };
+        handle.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Composite pipeline"),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.module,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.module,
+                entry_point,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: GpuHandle::TEXTURE_FORMAT,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: self.msaa_samples,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
+    }
+    fn create_targets(&self, handle: &GpuHandle, view_resolution: ViewResolution) -> CompositeTargets {
+        let size = wgpu::Extent3d {
+            width: view_resolution.width(),
+            height: view_resolution.height(),
+            depth_or_array_layers: 1,
+        };
+        let create_texture = |label: &str, sample_count: u32, usage: wgpu::TextureUsages| {
+            handle.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format: GpuHandle::TEXTURE_FORMAT,
+                usage,
+            })
+        };
+        let backdrop = create_texture(
+            "Composite backdrop",
+            1,
+            wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+        );
+        let layer = create_texture(
+            "Composite layer",
+            1,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        );
+        let layer_msaa = if self.msaa_samples > 1 {
+            let texture = create_texture(
+                "Composite layer multisampled",
+                self.msaa_samples,
+                wgpu::TextureUsages::RENDER_ATTACHMENT,
+            );
+            Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+        } else {
+            None
+        };
+        let backdrop_view = backdrop.create_view(&wgpu::TextureViewDescriptor::default());
+        let layer_view = layer.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = handle.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Composite bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&backdrop_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&layer_view),
+                },
+            ],
+        });
+        CompositeTargets { view_resolution, backdrop, layer_view, layer_msaa, bind_group }
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// ENCODE COMPOSITE PASS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+impl CompositePass {
+    /// Snapshots the resolved target so the composite shader can read it
+    /// while writing to the target. `size` is the used region of the target,
+    /// which is never larger than the view.
+    pub fn copy_backdrop(&self, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture, size: [u32; 2]) {
+        let targets = self.targets.as_ref().expect("[CompositePass] targets were never prepared");
+        encoder.copy_texture_to_texture(
+            texture.as_image_copy(),
+            targets.backdrop.as_image_copy(),
+            wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+    pub fn composite(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        blend_mode: BlendMode,
+        color_view: &wgpu::TextureView,
+        resolve_target: Option<&wgpu::TextureView>,
+        size: [u32; 2],
+    ) {
+        let targets = self.targets.as_ref().expect("[CompositePass] targets were never prepared");
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Composite pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color_view,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+                resolve_target,
+            })],
+            depth_stencil_attachment: None,
+        });
+        pass.set_scissor_rect(0, 0, size[0], size[1]);
+        pass.set_pipeline(&self.pipelines[&blend_mode]);
+        pass.set_bind_group(0, &targets.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// HELPERS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// The blend modes of `nodes` that are drawn through a composite pass, onto a
+/// backdrop that's `opaque` or not. Groups are cleared to transparent.
+fn composite_modes(nodes: &[DrawNode], opaque: bool, modes: &mut Vec<BlendMode>) {
+    for node in nodes {
+        let blend_mode = match node {
+            DrawNode::Batch(batch) => batch.blend_mode,
+            DrawNode::Group(group) => group.blend_mode,
+        };
+        if blend_mode.requires_composite_pass_over(opaque) {
+            modes.push(blend_mode);
+        }
+        if let DrawNode::Group(group) = node {
+            composite_modes(&group.children, false, modes);
+        }
+    }
+}
@@ -1,9 +1,13 @@
 use crate::canvas::{CanvasRenderer, UpdateStatus};
-use super::CanvasLayer;
+use super::{helpers, CanvasLayer};
 use super::gpu_target::{GpuBackend, GpuHandle};
 use crate::data::{Content, TessellatedContent};
 use crate::data::gpu_types;
 use crate::data::collections::CowCollection;
+use crate::data::ViewInfo;
+use crate::data::picture::{DrawBatch, DrawNode};
+use crate::data::culling::VisibleRanges;
+use crate::data::draw_cmds::BlendMode;
 use wgpu::util::DeviceExt;
 
 
@@ -15,6 +19,277 @@ impl<const N: usize> CanvasRenderer<N> {
 }
 
 
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// DRAW CANVAS-LAYER
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+impl CanvasLayer {
+    /// Fails when the surface has no frame to draw into, e.g. when it's
+    /// outdated or lost and needs to be reconfigured.
+    pub fn draw(&mut self, handle: &mut GpuHandle, view_info: ViewInfo) -> Result<(), wgpu::SurfaceError> {
+        let _ = handle.update(view_info, self.msaa_samples, &mut self.msaa_texture);
+        let view_resolution = view_info.view_resolution();
+        let frame = handle.surface.get_current_texture()?;
+        let frame_view_descriptor = wgpu::TextureViewDescriptor{
+            format: Some(GpuHandle::TEXTURE_FORMAT),
+            ..wgpu::TextureViewDescriptor::default()
+        };
+        let frame_view = frame.texture.create_view(&frame_view_descriptor);
+        let mut encoder = handle.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Encoder"),
+        });
+        self.write_globals(handle, view_info);
+        self.execute_render_pass(handle, view_info, &frame.texture, &frame_view, &mut encoder);
+        if self.picking.prepare(handle, view_resolution) {
+            let nodes = &self.scene_tessellator.nodes;
+            self.picking.encode_ids(&mut encoder, &self.vbo, &self.ibo, &self.index_chunks, &self.instance_buffer, &self.bind_group, nodes);
+            self.picking.encode_readbacks(handle, &mut encoder, view_resolution, &self.scene_tessellator.op_primitives);
+        }
+        handle.queue.submit(Some(encoder.finish()));
+        self.picking.request_mapping();
+        frame.present();
+        Ok(())
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// RENDER PASS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// Where a list of nodes is rendered to: either the frame, or the offscreen
+/// texture of a group.
+#[derive(Clone, Copy)]
+struct RenderTarget<'a> {
+    color_view: &'a wgpu::TextureView,
+    resolve_target: Option<&'a wgpu::TextureView>,
+    /// The resolved texture, read back by composite passes.
+    texture: &'a wgpu::Texture,
+    /// Used region of `texture`, starting at its top left corner.
+    size: [u32; 2],
+    /// Full size of `texture`.
+    extent: [u32; 2],
+    /// Maps picture space onto the target: the whole view, shifted so that the
+    /// target's origin lands at its top left corner.
+    viewport: [f32; 4],
+    /// What the camera can see, for culling batches. `None` draws them whole.
+    visible: Option<&'a VisibleRanges>,
+    /// Whether the target is cleared to an opaque color, which fixed-function
+    /// multiply relies on.
+    opaque: bool,
+}
+
+impl CanvasLayer {
+    /// Draws the nodes in order. Runs of fixed-function batches share one
+    /// render pass; every batch or group that needs a composite pass ends the
+    /// current pass so its backdrop can be read back.
+    ///
+    /// Top-level batches only draw the ops and instances the camera can see.
+    pub fn execute_render_pass(
+        &mut self,
+        handle: &GpuHandle,
+        view_info: ViewInfo,
+        frame_texture: &wgpu::Texture,
+        frame_view: &wgpu::TextureView,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        let wireframe = handle.wireframe;
+        let view_resolution = view_info.view_resolution();
+        let nodes = &self.scene_tessellator.nodes;
+        self.pipelines.prepare(handle, nodes, wireframe);
+        let [r, g, b, a] = self.scene_tessellator.color_scheme.palette().background.to_premultiplied().map(f64::from);
+        let clear_color = wgpu::Color { r, g, b, a };
+        let opaque = a >= 1.0;
+        self.composite.prepare(handle, view_resolution, nodes, opaque);
+        self.groups.prepare(handle, view_info, self.scene_tessellator.picture_resolution, &self.camera, nodes);
+        let view_size = [view_resolution.width(), view_resolution.height()];
+        let visible = self.camera
+            .visible_bounds(view_info, self.scene_tessellator.picture_resolution)
+            .map(|region| self.cull_index.visible(&region));
+        let frame_target = RenderTarget {
+            color_view: self.msaa_texture.as_ref().unwrap_or(frame_view),
+            resolve_target: self.msaa_texture.as_ref().map(|_| frame_view),
+            texture: frame_texture,
+            size: view_size,
+            extent: view_size,
+            viewport: [0.0, 0.0, view_size[0] as f32, view_size[1] as f32],
+            visible: visible.as_ref(),
+            opaque,
+        };
+        let mut group_ix = 0;
+        self.encode_nodes(encoder, frame_target, nodes, clear_color, &mut group_ix, wireframe);
+    }
+    fn encode_nodes(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: RenderTarget,
+        nodes: &[DrawNode],
+        clear_color: wgpu::Color,
+        group_ix: &mut usize,
+        wireframe: bool,
+    ) {
+        let mut load = wgpu::LoadOp::Clear(clear_color);
+        let mut pending: Vec<DrawBatch> = Vec::new();
+        let flush = |encoder: &mut wgpu::CommandEncoder, load: &mut wgpu::LoadOp<wgpu::Color>, pending: &mut Vec<DrawBatch>| {
+            // Always run the first pass, even when empty, so the target gets cleared.
+            if !pending.is_empty() || *load != wgpu::LoadOp::Load {
+                self.encode_geometry_pass(encoder, target.color_view, target.resolve_target, *load, target.viewport, pending, wireframe);
+                *load = wgpu::LoadOp::Load;
+                pending.clear();
+            }
+        };
+        for node in nodes {
+            match node {
+                DrawNode::Batch(batch) if !batch.blend_mode.requires_composite_pass_over(target.opaque) => {
+                    pending.extend(clip(batch, target.visible));
+                }
+                DrawNode::Batch(batch) => {
+                    let pieces = clip(batch, target.visible)
+                        .map(|piece| DrawBatch { blend_mode: BlendMode::SourceOver, ..piece })
+                        .collect::<Vec<_>>();
+                    if pieces.is_empty() {
+                        continue
+                    }
+                    flush(encoder, &mut load, &mut pending);
+                    let layer = self.composite.targets.as_ref().unwrap();
+                    self.composite.copy_backdrop(encoder, target.texture, target.size);
+                    self.encode_geometry_pass(
+                        encoder,
+                        layer.layer_msaa.as_ref().unwrap_or(&layer.layer_view),
+                        layer.layer_msaa.as_ref().map(|_| &layer.layer_view),
+                        wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        target.viewport,
+                        &pieces,
+                        wireframe,
+                    );
+                    self.composite.composite(encoder, batch.blend_mode, target.color_view, target.resolve_target, target.size);
+                }
+                DrawNode::Group(group) => {
+                    flush(encoder, &mut load, &mut pending);
+                    let ix = *group_ix;
+                    *group_ix += 1;
+                    let group_target = match self.groups.frame_targets[ix].as_ref() {
+                        Some(group_target) => group_target,
+                        None => {
+                            // Skip the targets of nested groups as well.
+                            node.visit(&mut |node| {
+                                if let DrawNode::Group(_) = node {
+                                    *group_ix += 1;
+                                }
+                            });
+                            *group_ix -= 1;
+                            continue
+                        }
+                    };
+                    let texture = &group_target.texture;
+                    let offscreen = RenderTarget {
+                        color_view: texture.msaa_view.as_ref().unwrap_or(&texture.view),
+                        resolve_target: texture.msaa_view.as_ref().map(|_| &texture.view),
+                        texture: &texture.texture,
+                        size: group_target.size,
+                        extent: texture.size,
+                        viewport: [
+                            -(group_target.origin[0] as f32),
+                            -(group_target.origin[1] as f32),
+                            target.viewport[2],
+                            target.viewport[3],
+                        ],
+                        visible: None,
+                        opaque: false,
+                    };
+                    self.encode_nodes(encoder, offscreen, &group.children, wgpu::Color::TRANSPARENT, group_ix, wireframe);
+                    self.groups.apply_filters(encoder, ix);
+                    let full_target = [0.0, 0.0, target.extent[0] as f32, target.extent[1] as f32];
+                    if group.blend_mode.requires_composite_pass_over(target.opaque) {
+                        let layer = self.composite.targets.as_ref().unwrap();
+                        self.composite.copy_backdrop(encoder, target.texture, target.size);
+                        self.groups.composite(
+                            encoder,
+                            ix,
+                            BlendMode::SourceOver,
+                            layer.layer_msaa.as_ref().unwrap_or(&layer.layer_view),
+                            layer.layer_msaa.as_ref().map(|_| &layer.layer_view),
+                            wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            full_target,
+                        );
+                        self.composite.composite(encoder, group.blend_mode, target.color_view, target.resolve_target, target.size);
+                    } else {
+                        self.groups.composite(
+                            encoder,
+                            ix,
+                            group.blend_mode,
+                            target.color_view,
+                            target.resolve_target,
+                            wgpu::LoadOp::Load,
+                            full_target,
+                        );
+                    }
+                }
+            }
+        }
+        flush(encoder, &mut load, &mut pending);
+    }
+    #[allow(clippy::too_many_arguments)]
+    fn encode_geometry_pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        color_view: &wgpu::TextureView,
+        resolve_target: Option<&wgpu::TextureView>,
+        load: wgpu::LoadOp<wgpu::Color>,
+        viewport: [f32; 4],
+        batches: &[DrawBatch],
+        wireframe: bool,
+    ) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color_view,
+                ops: wgpu::Operations {
+                    load,
+                    store: true,
+                },
+                resolve_target,
+            })],
+            depth_stencil_attachment: None,
+        });
+        let [x, y, width, height] = viewport;
+        pass.set_viewport(x, y, width, height, 0.0, 1.0);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        helpers::set_index_buffer(&mut pass, &self.ibo, &self.index_chunks);
+        pass.set_vertex_buffer(0, self.vbo.slice(..));
+        if self.instance_buffer.size() > 0 {
+            pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        }
+        pass.set_blend_constant(wgpu::Color::TRANSPARENT);
+        for batch in batches {
+            let instanced = batch.instances.is_some();
+            pass.set_pipeline(self.pipelines.get(batch.blend_mode, wireframe, instanced));
+            let instances = batch.instances.clone().unwrap_or(0..1);
+            helpers::draw_indexed(&mut pass, &self.ibo, &self.index_chunks, batch.indices.clone(), instances);
+        }
+    }
+}
+
+/// The visible pieces of a batch, in order: its ops' index ranges, or its
+/// instance ranges for a symbol.
+fn clip<'a>(batch: &'a DrawBatch, visible: Option<&'a VisibleRanges>) -> Box<dyn Iterator<Item = DrawBatch> + 'a> {
+    let visible = match visible {
+        Some(visible) => visible,
+        None => return Box::new(std::iter::once(batch.clone())),
+    };
+    match batch.instances.clone() {
+        Some(instances) => Box::new(visible.clip_instances(instances).map(|instances| DrawBatch {
+            instances: Some(instances),
+            ..batch.clone()
+        })),
+        None => Box::new(visible.clip_indices(batch.indices.clone()).map(|indices| DrawBatch {
+            indices,
+            ..batch.clone()
+        })),
+    }
+}
+
+
 // impl<Item> Layer<Item> {
 //     pub fn draw(&mut self, handle: &GpuHandle, content: Content<'_, Item>) where Item: ItemConstraints {
 //         let update_status = self.update(handle, content);
@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use wgpu::include_wgsl;
+use super::gpu_target::GpuHandle;
+use crate::data::gpu_types::{GpuFilterParams, GpuPrimitive};
+use crate::data::filters::{Filter, kernel_extent};
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// FILTER STEPS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FilterStage {
+    Blur,
+    ShadowBlur,
+    ShadowMerge,
+    ColorMatrix,
+}
+
+/// The textures a filter chain works with: the group's own layer, and two
+/// scratch textures of the same size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterSlot {
+    Layer,
+    ScratchA,
+    ScratchB,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum FilterStep {
+    Render {
+        stage: FilterStage,
+        source: FilterSlot,
+        auxiliary: FilterSlot,
+        target: FilterSlot,
+        params: GpuFilterParams,
+    },
+    Copy {
+        source: FilterSlot,
+        target: FilterSlot,
+    },
+}
+
+/// A step with its GPU resources, ready to encode.
+pub struct PreparedFilterStep {
+    pub step: FilterStep,
+    pub params_offset: u32,
+    pub bind_group: Option<wgpu::BindGroup>,
+}
+
+impl FilterStep {
+    /// Separable passes for one filter. The result always ends up back in
+    /// `FilterSlot::Layer`, so filters chain and the group's composite quad
+    /// doesn't need to know which ran. `scale` converts picture units to
+    /// pixels along each axis.
+    pub fn plan(filter: &Filter, scale: [f32; 2]) -> Vec<FilterStep> {
+        use FilterSlot::*;
+        let blur = |sigma: f32, axis: usize| {
+            let sigma = sigma * scale[axis];
+            let mut params = empty_params();
+            params.direction = if axis == 0 { [1, 0] } else { [0, 1] };
+            params.sigma = sigma;
+            params.radius = kernel_extent(sigma) as i32;
+            params
+        };
+        match filter {
+            Filter::GaussianBlur { radius } => vec![
+                FilterStep::Render { stage: FilterStage::Blur, source: Layer, auxiliary: Layer, target: ScratchA, params: blur(*radius, 0) },
+                FilterStep::Render { stage: FilterStage::Blur, source: ScratchA, auxiliary: ScratchA, target: Layer, params: blur(*radius, 1) },
+            ],
+            Filter::DropShadow { offset, color, blur_radius } => {
+                let mut shadow = blur(*blur_radius, 0);
+                shadow.color = GpuPrimitive::from_u8_rgba(*color).color;
+                shadow.offset = [(offset.x * scale[0]).round() as i32, (offset.y * scale[1]).round() as i32];
+                vec![
+                    FilterStep::Render { stage: FilterStage::ShadowBlur, source: Layer, auxiliary: Layer, target: ScratchA, params: shadow },
+                    FilterStep::Render { stage: FilterStage::Blur, source: ScratchA, auxiliary: ScratchA, target: ScratchB, params: blur(*blur_radius, 1) },
+                    FilterStep::Render { stage: FilterStage::ShadowMerge, source: Layer, auxiliary: ScratchB, target: ScratchA, params: empty_params() },
+                    FilterStep::Copy { source: ScratchA, target: Layer },
+                ]
+            }
+            Filter::ColorMatrix(matrix) => {
+                let mut params = empty_params();
+                for row in 0..4 {
+                    params.color_matrix[row].copy_from_slice(&matrix[row * 5..row * 5 + 4]);
+                    params.color_offset[row] = matrix[row * 5 + 4];
+                }
+                vec![
+                    FilterStep::Render { stage: FilterStage::ColorMatrix, source: Layer, auxiliary: Layer, target: ScratchA, params },
+                    FilterStep::Copy { source: ScratchA, target: Layer },
+                ]
+            }
+        }
+    }
+}
+
+fn empty_params() -> GpuFilterParams {
+    bytemuck::Zeroable::zeroed()
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// FILTER PIPELINES
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+pub struct FilterPipelines {
+    pub module: wgpu::ShaderModule,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub pipeline_layout: wgpu::PipelineLayout,
+    pub pipelines: HashMap<FilterStage, wgpu::RenderPipeline>,
+}
+
+impl FilterPipelines {
+    /// Each pass's params live in one shared buffer, at this stride.
+    pub const PARAMS_STRIDE: u64 = 256;
+
+    pub fn new(handle: &GpuHandle) -> Self {
+        let module = handle.device.create_shader_module(include_wgsl!("./../../../shaders/filters.wgsl"));
+        let texture_entry = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = handle.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Filter bind group layout"),
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<GpuFilterParams>() as u64),
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = handle.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+            label: Some("Filter pipeline layout"),
+        });
+        FilterPipelines { module, bind_group_layout, pipeline_layout, pipelines: HashMap::new() }
+    }
+    pub fn prepare(&mut self, handle: &GpuHandle, stage: FilterStage) {
+        if self.pipelines.contains_key(&stage) {
+            return
+        }
+        let entry_point = match stage {
+            FilterStage::Blur => "fs_blur",
+            FilterStage::ShadowBlur => "fs_shadow_blur",
+            FilterStage::ShadowMerge => "fs_shadow_merge",
+            FilterStage::ColorMatrix => "fs_color_matrix",
+        };
+        let pipeline = handle.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Filter pipeline"),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.module,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.module,
+                entry_point,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: GpuHandle::TEXTURE_FORMAT,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        self.pipelines.insert(stage, pipeline);
+    }
+    pub fn create_bind_group(
+        &self,
+        handle: &GpuHandle,
+        source: &wgpu::TextureView,
+        auxiliary: &wgpu::TextureView,
+        params: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        handle.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Filter bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(source),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(auxiliary),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: params,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(std::mem::size_of::<GpuFilterParams>() as u64),
+                    }),
+                },
+            ],
+        })
+    }
+    /// `slots` resolves each `FilterSlot` to its texture and view.
+    pub fn encode<'a>(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        steps: &[PreparedFilterStep],
+        slots: impl Fn(FilterSlot) -> (&'a wgpu::Texture, &'a wgpu::TextureView),
+        size: [u32; 2],
+    ) {
+        for prepared in steps {
+            match prepared.step {
+                FilterStep::Render { stage, target, .. } => {
+                    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("Filter pass"),
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view: slots(target).1,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                                store: true,
+                            },
+                            resolve_target: None,
+                        })],
+                        depth_stencil_attachment: None,
+                    });
+                    pass.set_pipeline(&self.pipelines[&stage]);
+                    pass.set_bind_group(0, prepared.bind_group.as_ref().unwrap(), &[prepared.params_offset]);
+                    pass.draw(0..3, 0..1);
+                }
+                FilterStep::Copy { source, target } => {
+                    encoder.copy_texture_to_texture(
+                        slots(source).0.as_image_copy(),
+                        slots(target).0.as_image_copy(),
+                        wgpu::Extent3d {
+                            width: size[0],
+                            height: size[1],
+                            depth_or_array_layers: 1,
+                        },
+                    );
+                }
+            }
+        }
+    }
+}
@@ -8,7 +8,7 @@ use futures::executor::block_on;
 use wgpu::util::DeviceExt;
 use std::f64::NAN;
 
-pub use crate::data::{ViewResolution, PictureResolution};
+pub use crate::data::{ViewInfo, ViewResolution, PictureResolution};
 
 
 
@@ -63,6 +63,7 @@ pub struct GpuHandle {
 pub struct GpuViewInfo {
     pub surface_desc: wgpu::SurfaceConfiguration,
     pub view_resolution: ViewResolution,
+    pub scale_factor: f32,
 }
 
 impl GpuHandle {
@@ -131,6 +132,9 @@ impl GpuHandle {
 
 pub enum GpuHandleUpdateInfo {
     UpdatedViewResolution,
+    /// The pixel ratio changed without changing the physical size, so the
+    /// surface was kept.
+    UpdatedScaleFactor,
     NoOp,
 }
 
@@ -146,21 +150,29 @@ impl GpuHandleUpdateInfo {
 impl GpuHandle {
     pub(crate) fn update(
         &mut self,
-        latest_resolution: ViewResolution,
+        view_info: ViewInfo,
         msaa_samples: u32,
         msaa_texture: &mut Option<wgpu::TextureView>,
     ) -> GpuHandleUpdateInfo {
+        let latest_resolution = view_info.view_resolution();
         let view_resolution_changed = self.gpu_view_info
             .as_ref()
             .map(|gpu_view_info| {
                 gpu_view_info.view_resolution.resolution_changed(&latest_resolution)
             })
             .unwrap_or(true);
-        let surface_needs_configure = view_resolution_changed || self.gpu_view_info.is_none();
         if view_resolution_changed {
             self.set_surface_config(latest_resolution, msaa_samples, msaa_texture);
+        }
+        let gpu_view_info = self.gpu_view_info.as_mut().unwrap();
+        let scale_factor_changed = gpu_view_info.scale_factor != view_info.scale_factor;
+        gpu_view_info.scale_factor = view_info.scale_factor;
+        if view_resolution_changed {
             return GpuHandleUpdateInfo::UpdatedViewResolution;
         }
+        if scale_factor_changed {
+            return GpuHandleUpdateInfo::UpdatedScaleFactor;
+        }
         GpuHandleUpdateInfo::NoOp
     }
     fn set_surface_config(
@@ -175,7 +187,8 @@ impl GpuHandle {
             .take()
             .unwrap_or_else(|| {
                 let surface_desc = wgpu::SurfaceConfiguration {
-                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    // COPY_SRC lets blend modes that need a composite pass read back the frame.
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
                     format: GpuHandle::TEXTURE_FORMAT,
                     width: resolution.width(),
                     height: resolution.height(),
@@ -186,8 +199,10 @@ impl GpuHandle {
                 GpuViewInfo {
                     surface_desc,
                     view_resolution: resolution,
+                    scale_factor: 1.0,
                 }
             });
+        gpu_view_info.view_resolution = resolution;
         gpu_view_info.surface_desc.format = GpuHandle::TEXTURE_FORMAT;
         gpu_view_info.surface_desc.width = resolution.width();
         gpu_view_info.surface_desc.height = resolution.height();
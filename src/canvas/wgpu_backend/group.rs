@@ -0,0 +1,497 @@
+use std::collections::HashMap;
+use wgpu::include_wgsl;
+use wgpu::util::DeviceExt;
+use super::gpu_target::GpuHandle;
+use super::pipeline::blend_state;
+use super::filter::{FilterPipelines, FilterSlot, FilterStep, PreparedFilterStep};
+use crate::data::{ViewInfo, ViewResolution, PictureResolution};
+use crate::data::gpu_types::{GpuGroupVertex, GpuFilterParams};
+use crate::data::draw_cmds::BlendMode;
+use crate::data::picture::{DrawNode, GroupNode};
+use crate::data::camera::Camera;
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// GROUP PASS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// Renders each `GroupNode` into a pooled offscreen texture covering the
+/// group's bounds, and composites it onto the parent target with a quad.
+pub struct GroupPass {
+    pub module: wgpu::ShaderModule,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub pipeline_layout: wgpu::PipelineLayout,
+    pub sampler: wgpu::Sampler,
+    pub pipelines: HashMap<BlendMode, wgpu::RenderPipeline>,
+    pub pool: TexturePool,
+    /// One entry per group of the current frame, in `DrawNode::visit` order.
+    /// Groups that fall outside the view get `None`.
+    pub frame_targets: Vec<Option<GroupTarget>>,
+    pub quads: Option<wgpu::Buffer>,
+    pub filters: FilterPipelines,
+    pub filter_params: Option<wgpu::Buffer>,
+    pub msaa_samples: u32,
+}
+
+/// The region of a pooled texture that a group is rendered into.
+pub struct GroupTarget {
+    /// Physical pixel offset of the group's bounds within the view.
+    pub origin: [u32; 2],
+    /// Size of the used region, which may be smaller than the texture.
+    pub size: [u32; 2],
+    pub texture: PooledTexture,
+    /// Two textures the size of `texture`, only for groups with filters.
+    pub scratch: Vec<PooledTexture>,
+    pub filter_steps: Vec<PreparedFilterStep>,
+}
+
+impl GroupTarget {
+    fn slot(&self, slot: FilterSlot) -> &PooledTexture {
+        match slot {
+            FilterSlot::Layer => &self.texture,
+            FilterSlot::ScratchA => &self.scratch[0],
+            FilterSlot::ScratchB => &self.scratch[1],
+        }
+    }
+}
+
+pub struct PooledTexture {
+    pub size: [u32; 2],
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub msaa_view: Option<wgpu::TextureView>,
+    pub bind_group: wgpu::BindGroup,
+}
+
+/// Offscreen textures are recycled between frames. Sizes are rounded up so a
+/// group that changes size slightly can still reuse last frame's texture.
+#[derive(Default)]
+pub struct TexturePool {
+    pub free: Vec<PooledTexture>,
+}
+
+impl TexturePool {
+    pub const SIZE_GRANULARITY: u32 = 128;
+
+    pub fn acquire(&mut self, size: [u32; 2], create: impl FnOnce([u32; 2]) -> PooledTexture) -> PooledTexture {
+        let round_up = |x: u32| x.div_ceil(Self::SIZE_GRANULARITY) * Self::SIZE_GRANULARITY;
+        let size = [round_up(size[0]), round_up(size[1])];
+        match self.free.iter().position(|texture| texture.size == size) {
+            Some(ix) => self.free.swap_remove(ix),
+            None => create(size),
+        }
+    }
+    pub fn release(&mut self, texture: PooledTexture) {
+        self.free.push(texture);
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// INIT GROUP PASS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+impl GroupPass {
+    pub fn new(handle: &GpuHandle, msaa_samples: u32) -> Self {
+        let module = handle.device.create_shader_module(include_wgsl!("./../../../shaders/group_composite.wgsl"));
+        let bind_group_layout = handle.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Group bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = handle.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+            label: Some("Group pipeline layout"),
+        });
+        let sampler = handle.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Group sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..wgpu::SamplerDescriptor::default()
+        });
+        GroupPass {
+            module,
+            bind_group_layout,
+            pipeline_layout,
+            sampler,
+            pipelines: HashMap::new(),
+            pool: TexturePool::default(),
+            frame_targets: Vec::new(),
+            quads: None,
+            filters: FilterPipelines::new(handle),
+            filter_params: None,
+            msaa_samples,
+        }
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// UPDATE GROUP PASS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+impl GroupPass {
+    /// Assigns a pooled texture to every group of this frame and uploads the
+    /// quads used to composite them. Last frame's textures go back to the pool
+    /// first; wgpu keeps them alive until the work that used them is done.
+    pub fn prepare(
+        &mut self,
+        handle: &GpuHandle,
+        view_info: ViewInfo,
+        picture_resolution: PictureResolution,
+        camera: &Camera,
+        nodes: &[DrawNode],
+    ) {
+        for target in self.frame_targets.drain(..).flatten() {
+            self.pool.release(target.texture);
+            for scratch in target.scratch {
+                self.pool.release(scratch);
+            }
+        }
+        let mut quads: Vec<GpuGroupVertex> = Vec::new();
+        let mut filter_params: Vec<GpuFilterParams> = Vec::new();
+        let view_resolution = view_info.view_resolution();
+        let frame = ([0, 0], [view_resolution.width(), view_resolution.height()]);
+        let placement = Placement {
+            view_resolution,
+            picture_to_view: camera.picture_to_view_transform(view_info, picture_resolution),
+            scale: camera.scale(view_info, picture_resolution),
+        };
+        self.prepare_nodes(handle, &placement, nodes, frame, &mut quads, &mut filter_params);
+        self.prepare_filters(handle, &filter_params);
+        self.quads = if quads.is_empty() {
+            None
+        } else {
+            Some(handle.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Group quads"),
+                contents: bytemuck::cast_slice(&quads),
+                usage: wgpu::BufferUsages::VERTEX,
+            }))
+        };
+    }
+    /// `parent` is the origin and full texture size of the target the groups
+    /// in `nodes` are composited onto.
+    fn prepare_nodes(
+        &mut self,
+        handle: &GpuHandle,
+        placement: &Placement,
+        nodes: &[DrawNode],
+        parent: ([u32; 2], [u32; 2]),
+        quads: &mut Vec<GpuGroupVertex>,
+        filter_params: &mut Vec<GpuFilterParams>,
+    ) {
+        for node in nodes {
+            let group = match node {
+                DrawNode::Group(group) => group,
+                DrawNode::Batch(_) => continue,
+            };
+            // Groups drawn through a composite pass go source-over into its layer.
+            for blend_mode in [group.blend_mode, BlendMode::SourceOver] {
+                if !self.pipelines.contains_key(&blend_mode) {
+                    let pipeline = self.create_pipeline(handle, blend_mode);
+                    self.pipelines.insert(blend_mode, pipeline);
+                }
+            }
+            let (origin, size) = match pixel_bounds(group, placement) {
+                Some(x) => x,
+                None => {
+                    node.visit(&mut |node| {
+                        if let DrawNode::Group(_) = node {
+                            self.frame_targets.push(None);
+                        }
+                    });
+                    continue
+                }
+            };
+            let mut pool = std::mem::take(&mut self.pool);
+            let texture = pool.acquire(size, |size| self.create_texture(handle, size));
+            let scratch = if group.filters.is_empty() {
+                Vec::new()
+            } else {
+                (0..2).map(|_| pool.acquire(size, |size| self.create_texture(handle, size))).collect()
+            };
+            self.pool = pool;
+            let filter_steps = group.filters
+                .iter()
+                .flat_map(|filter| FilterStep::plan(filter, placement.scale))
+                .map(|step| {
+                    let params_offset = (filter_params.len() as u64 * FilterPipelines::PARAMS_STRIDE) as u32;
+                    if let FilterStep::Render { stage, params, .. } = step {
+                        self.filters.prepare(handle, stage);
+                        filter_params.push(params);
+                    }
+                    PreparedFilterStep { step, params_offset, bind_group: None }
+                })
+                .collect::<Vec<_>>();
+            let texture_size = texture.size;
+            quads.extend(group_quad(group.opacity, origin, size, texture_size, parent));
+            self.frame_targets.push(Some(GroupTarget { origin, size, texture, scratch, filter_steps }));
+            self.prepare_nodes(
+                handle,
+                placement,
+                &group.children,
+                (origin, texture_size),
+                quads,
+                filter_params,
+            );
+        }
+    }
+    /// Uploads the params of every filter pass of this frame, and binds each
+    /// pass to its textures.
+    fn prepare_filters(&mut self, handle: &GpuHandle, filter_params: &[GpuFilterParams]) {
+        if filter_params.is_empty() {
+            self.filter_params = None;
+            return
+        }
+        let stride = FilterPipelines::PARAMS_STRIDE as usize;
+        let mut contents = vec![0u8; filter_params.len() * stride];
+        for (ix, params) in filter_params.iter().enumerate() {
+            let bytes = bytemuck::bytes_of(params);
+            contents[ix * stride..ix * stride + bytes.len()].copy_from_slice(bytes);
+        }
+        let buffer = handle.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Filter params"),
+            contents: &contents,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let filters = &self.filters;
+        for target in self.frame_targets.iter_mut().flatten() {
+            let mut filter_steps = std::mem::take(&mut target.filter_steps);
+            for prepared in filter_steps.iter_mut() {
+                if let FilterStep::Render { source, auxiliary, .. } = prepared.step {
+                    prepared.bind_group = Some(filters.create_bind_group(
+                        handle,
+                        &target.slot(source).view,
+                        &target.slot(auxiliary).view,
+                        &buffer,
+                    ));
+                }
+            }
+            target.filter_steps = filter_steps;
+        }
+        self.filter_params = Some(buffer);
+    }
+    fn create_pipeline(&self, handle: &GpuHandle, blend_mode: BlendMode) -> wgpu::RenderPipeline {
+        handle.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Group pipeline"),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.module,
+                entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<GpuGroupVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x2,
+                        1 => Float32x2,
+                        2 => Float32,
+                    ],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.module,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: GpuHandle::TEXTURE_FORMAT,
+                    blend: Some(blend_state(blend_mode)),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: self.msaa_samples,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
+    }
+    fn create_texture(&self, handle: &GpuHandle, size: [u32; 2]) -> PooledTexture {
+        let descriptor = |label, sample_count, usage| wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: GpuHandle::TEXTURE_FORMAT,
+            usage,
+        };
+        let texture = handle.device.create_texture(&descriptor(
+            "Group texture",
+            1,
+            wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
+        ));
+        let msaa_view = if self.msaa_samples > 1 {
+            let texture = handle.device.create_texture(&descriptor(
+                "Group texture multisampled",
+                self.msaa_samples,
+                wgpu::TextureUsages::RENDER_ATTACHMENT,
+            ));
+            Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+        } else {
+            None
+        };
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = handle.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Group bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+        PooledTexture { size, texture, view, msaa_view, bind_group }
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// ENCODE GROUP PASS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+impl GroupPass {
+    /// Runs the filter chain of the group at `group_ix` over its rendered
+    /// layer, leaving the result in the layer.
+    pub fn apply_filters(&self, encoder: &mut wgpu::CommandEncoder, group_ix: usize) {
+        let target = match self.frame_targets[group_ix].as_ref() {
+            Some(target) => target,
+            None => return,
+        };
+        let slots = |slot| {
+            let texture = target.slot(slot);
+            (&texture.texture, &texture.view)
+        };
+        self.filters.encode(encoder, &target.filter_steps, slots, target.texture.size);
+    }
+    /// Draws the quad of the group at `group_ix` (in `DrawNode::visit` order)
+    /// into the given target.
+    #[allow(clippy::too_many_arguments)]
+    pub fn composite(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        group_ix: usize,
+        blend_mode: BlendMode,
+        color_view: &wgpu::TextureView,
+        resolve_target: Option<&wgpu::TextureView>,
+        load: wgpu::LoadOp<wgpu::Color>,
+        viewport: [f32; 4],
+    ) {
+        let target = self.frame_targets[group_ix]
+            .as_ref()
+            .expect("[GroupPass] group has no target");
+        let quad_ix = self.frame_targets[..group_ix]
+            .iter()
+            .filter(|target| target.is_some())
+            .count() as u32;
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Group composite pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color_view,
+                ops: wgpu::Operations {
+                    load,
+                    store: true,
+                },
+                resolve_target,
+            })],
+            depth_stencil_attachment: None,
+        });
+        let [x, y, width, height] = viewport;
+        pass.set_viewport(x, y, width, height, 0.0, 1.0);
+        pass.set_pipeline(&self.pipelines[&blend_mode]);
+        pass.set_bind_group(0, &target.texture.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.quads.as_ref().unwrap().slice(..));
+        pass.draw(quad_ix * 6..quad_ix * 6 + 6, 0..1);
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// HELPERS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// Where the camera puts the picture this frame.
+struct Placement {
+    view_resolution: ViewResolution,
+    picture_to_view: lyon::math::Transform,
+    /// View pixels per picture unit, for filter radii and offsets. These
+    /// aren't rotated with the camera.
+    scale: [f32; 2],
+}
+
+/// The group's bounds in physical pixels, clamped to the view. `None` when
+/// nothing of the group is visible.
+fn pixel_bounds(group: &GroupNode, placement: &Placement) -> Option<([u32; 2], [u32; 2])> {
+    let view_width = placement.view_resolution.width() as f32;
+    let view_height = placement.view_resolution.height() as f32;
+    let bounds = placement.picture_to_view.outer_transformed_box(&group.bounds);
+    let min_x = bounds.min.x.floor().clamp(0.0, view_width);
+    let min_y = bounds.min.y.floor().clamp(0.0, view_height);
+    let max_x = bounds.max.x.ceil().clamp(0.0, view_width);
+    let max_y = bounds.max.y.ceil().clamp(0.0, view_height);
+    if max_x <= min_x || max_y <= min_y {
+        return None
+    }
+    let origin = [min_x as u32, min_y as u32];
+    let size = [(max_x - min_x) as u32, (max_y - min_y) as u32];
+    Some((origin, size))
+}
+
+/// Two triangles covering the group's region of its parent target, in the
+/// parent's NDC.
+fn group_quad(
+    opacity: f32,
+    origin: [u32; 2],
+    size: [u32; 2],
+    texture_size: [u32; 2],
+    (parent_origin, parent_size): ([u32; 2], [u32; 2]),
+) -> [GpuGroupVertex; 6] {
+    let to_ndc = |x: u32, y: u32| {
+        // Signed, in case the group sticks out past the parent's region.
+        let x = (x as f32 - parent_origin[0] as f32) / parent_size[0] as f32;
+        let y = (y as f32 - parent_origin[1] as f32) / parent_size[1] as f32;
+        [x * 2.0 - 1.0, 1.0 - y * 2.0]
+    };
+    let min = to_ndc(origin[0], origin[1]);
+    let max = to_ndc(origin[0] + size[0], origin[1] + size[1]);
+    let max_u = size[0] as f32 / texture_size[0] as f32;
+    let max_v = size[1] as f32 / texture_size[1] as f32;
+    let vertex = |position: [f32; 2], uv: [f32; 2]| GpuGroupVertex { position, uv, opacity };
+    let top_left = vertex(min, [0.0, 0.0]);
+    let top_right = vertex([max[0], min[1]], [max_u, 0.0]);
+    let bottom_left = vertex([min[0], max[1]], [0.0, max_v]);
+    let bottom_right = vertex(max, [max_u, max_v]);
+    [top_left, bottom_left, top_right, top_right, bottom_left, bottom_right]
+}
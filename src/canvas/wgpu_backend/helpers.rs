@@ -5,6 +5,7 @@ use crate::data::{Content, TessellatedContent};
 use crate::data::gpu_types;
 use crate::data::collections::CowCollection;
 use wgpu::util::DeviceExt;
+use crate::data::optimize::{self, IndexChunk, IndexFormat};
 
 // impl Layer {
 //     pub fn content_ref(&self) -> Content {
@@ -15,3 +16,44 @@ use wgpu::util::DeviceExt;
 //         }
 //     }
 // }
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// INDEXED DRAWS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+impl From<IndexFormat> for wgpu::IndexFormat {
+    fn from(format: IndexFormat) -> Self {
+        match format {
+            IndexFormat::Uint16 => wgpu::IndexFormat::Uint16,
+            IndexFormat::Uint32 => wgpu::IndexFormat::Uint32,
+        }
+    }
+}
+
+/// Binds a layer's index buffer. With `chunks`, each draw binds its own.
+pub(crate) fn set_index_buffer<'a>(pass: &mut wgpu::RenderPass<'a>, ibo: &'a wgpu::Buffer, chunks: &[IndexChunk]) {
+    if chunks.is_empty() {
+        pass.set_index_buffer(ibo.slice(..), wgpu::IndexFormat::Uint32);
+    }
+}
+
+/// Draws a range of the tessellator's indices. With `chunks`, the range is
+/// split into a draw per chunk, each with the chunk's index format and base
+/// vertex.
+pub(crate) fn draw_indexed<'a>(
+    pass: &mut wgpu::RenderPass<'a>,
+    ibo: &'a wgpu::Buffer,
+    chunks: &'a [IndexChunk],
+    indices: std::ops::Range<u32>,
+    instances: std::ops::Range<u32>,
+) {
+    if chunks.is_empty() {
+        pass.draw_indexed(indices, 0, instances);
+        return
+    }
+    for (chunk, indices) in optimize::split(chunks, indices) {
+        pass.set_index_buffer(ibo.slice(chunk.bytes.clone()), chunk.format.into());
+        pass.draw_indexed(indices, chunk.base_vertex, instances.clone());
+    }
+}
@@ -1,10 +1,16 @@
 use crate::canvas::{CanvasRenderer, UpdateStatus};
 use super::CanvasLayer;
 use super::gpu_target::{GpuBackend, GpuHandle};
+use super::pipeline::PipelineCache;
+use super::composite::CompositePass;
+use super::group::GroupPass;
+use super::picking::PickingPass;
 use crate::data::{Content, TessellatedContent};
 use crate::data::gpu_types;
 use crate::data::collections::CowCollection;
 use crate::frontend::SceneTessellator;
+use crate::data::camera::Camera;
+use crate::data::culling::CullIndex;
 use wgpu::util::DeviceExt;
 
 
@@ -46,12 +52,17 @@ impl CanvasLayer {
         let vbo = handle.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: None,
             contents: bytemuck::cast_slice(&scene_tessellator.mesh.vertices),
-            usage: wgpu::BufferUsages::VERTEX,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
         });
         let ibo = handle.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: None,
             contents: bytemuck::cast_slice(&scene_tessellator.mesh.indices),
-            usage: wgpu::BufferUsages::INDEX,
+            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+        });
+        let instance_buffer = handle.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Instance buffer"),
+            contents: bytemuck::cast_slice(&scene_tessellator.instances),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
         });
         let prim_buffer_byte_size = (scene_tessellator.primitives.len() * std::mem::size_of::<gpu_types::GpuPrimitive>()) as u64;
         let prims_ssbo = handle.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -65,9 +76,11 @@ impl CanvasLayer {
         let globals_buffer_byte_size = std::mem::size_of::<gpu_types::GpuGlobals>() as u64;
         let globals_ubo = handle.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Globals ubo"),
+            // The view matrix is written with each frame, by `write_globals`.
             contents: bytemuck::cast_slice(&[gpu_types::GpuGlobals {
                 picture_resolution: [picture_viewport.width(), picture_viewport.height()],
-                _pad: 0.0,
+                _pad: [0.0; 2],
+                view_matrix: [[0.0; 4]; 4],
             }]),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
@@ -121,81 +134,37 @@ impl CanvasLayer {
             push_constant_ranges: &[],
             label: None,
         });
-        let fragment_targets = &[Some(wgpu::ColorTargetState {
-            format: GpuHandle::TEXTURE_FORMAT,
-            blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
-            write_mask: wgpu::ColorWrites::ALL,
-        })];
-        let mut render_pipeline_descriptor = wgpu::RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &handle.vs_module,
-                entry_point: "main",
-                buffers: &[wgpu::VertexBufferLayout {
-                    array_stride: std::mem::size_of::<gpu_types::GpuVertex>() as wgpu::BufferAddress,
-                    step_mode: wgpu::VertexStepMode::Vertex,
-                    attributes: &[
-                        wgpu::VertexAttribute {
-                            offset: 0,
-                            format: wgpu::VertexFormat::Float32x2,
-                            shader_location: 0,
-                        },
-                        wgpu::VertexAttribute {
-                            offset: 8,
-                            format: wgpu::VertexFormat::Uint32,
-                            shader_location: 1,
-                        },
-                    ],
-                }],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &handle.fs_module,
-                entry_point: "main",
-                targets: fragment_targets,
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                front_face: wgpu::FrontFace::Ccw,
-                strip_index_format: None,
-                cull_mode: None,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: msaa_samples,
-                mask: !0,
-                alpha_to_coverage_enabled: true,
-            },
-            multiview: None,
-        };
-        let render_pipeline = handle.device.create_render_pipeline(&render_pipeline_descriptor);
-        // TODO: this isn't what we want: we'd need the equivalent of VK_POLYGON_MODE_LINE,
-        // but it doesn't seem to be exposed by wgpu?
-        render_pipeline_descriptor.primitive.polygon_mode = wgpu::PolygonMode::Line;
-        let wireframe_render_pipeline = handle.device.create_render_pipeline(&render_pipeline_descriptor);
+        let picking = PickingPass::new(handle, &pipeline_layout);
+        let pipelines = PipelineCache::new(handle, pipeline_layout, msaa_samples);
+        let composite = CompositePass::new(handle, msaa_samples);
+        let groups = GroupPass::new(handle, msaa_samples);
         //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
         // DONE
         //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
         let context: CanvasLayer = CanvasLayer {
             scene_tessellator,
+            camera: Camera::default(),
             ibo, 
             vbo, 
+            instance_buffer,
+            optimize_mesh: false,
+            index_chunks: Vec::new(),
+            cull_index: CullIndex::new(),
             prim_buffer_byte_size, 
             prims_ssbo, 
             globals_buffer_byte_size, 
             globals_ubo, 
             bind_group_layout, 
             bind_group, 
-            render_pipeline, 
-            wireframe_render_pipeline,
+            pipelines,
+            composite,
+            groups,
+            picking,
             msaa_samples,
             msaa_texture: None,
             provisioned: false,
         };
-        unimplemented!()
+        context
     }
 }
 
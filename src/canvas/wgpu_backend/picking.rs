@@ -0,0 +1,366 @@
+use std::sync::{Arc, Mutex};
+use wgpu::include_wgsl;
+use super::{helpers, CanvasLayer};
+use super::gpu_target::GpuHandle;
+use super::pipeline::{instance_buffer_layout, vertex_buffer_layout};
+use crate::data::ViewResolution;
+use crate::data::picture::{DrawNode, op_for_primitive};
+use crate::data::optimize::IndexChunk;
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// PICKING PASS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// GPU object picking. Every batch, including those inside groups, is drawn
+/// into a single-sampled `R32Uint` target holding `prim_id + 1`, so the last
+/// primitive drawn over a pixel wins and zero means the background.
+///
+/// Integer targets can't be resolved or blended, so rather than adding an
+/// attachment to the (multisampled, blended) color passes, the IDs get their
+/// own pass over the same buffers. It only runs on frames with queued
+/// queries, and results are read back asynchronously: `CanvasLayer::pick`
+/// queues a query, and `CanvasLayer::poll_picks` returns whatever has
+/// finished, without waiting on the GPU.
+pub struct PickingPass {
+    pub module: wgpu::ShaderModule,
+    pub pipeline: wgpu::RenderPipeline,
+    pub instanced_pipeline: wgpu::RenderPipeline,
+    pub target: Option<PickingTarget>,
+    pub queued: Vec<(PickTicket, PickQuery)>,
+    pub in_flight: Vec<PickReadback>,
+    pub next_ticket: u64,
+}
+
+pub struct PickingTarget {
+    pub view_resolution: ViewResolution,
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+}
+
+/// Identifies a queued query in the results of `CanvasLayer::poll_picks`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PickTicket(pub u64);
+
+/// Queries are in physical pixels of the view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickQuery {
+    /// The topmost op at a pixel.
+    Pixel { x: u32, y: u32 },
+    /// Every op visible within `min..max`.
+    Rect { min: [u32; 2], max: [u32; 2] },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PickResult {
+    pub ticket: PickTicket,
+    pub query: PickQuery,
+    /// Indices of the ops in the order they were appended to the scene,
+    /// topmost first. Empty when nothing was hit, or when the readback
+    /// failed.
+    pub ops: Vec<usize>,
+}
+
+/// A copy of a query's region on its way back from the GPU.
+pub struct PickReadback {
+    pub ticket: PickTicket,
+    pub query: PickQuery,
+    pub size: [u32; 2],
+    pub padded_bytes_per_row: u32,
+    pub buffer: wgpu::Buffer,
+    /// The scene's primitive ranges when the IDs were rendered, since the
+    /// scene may change before the readback completes.
+    pub op_primitives: Vec<std::ops::Range<u32>>,
+    /// Set by the `map_async` callback; `None` until mapping was requested
+    /// and finished.
+    pub mapped: Arc<Mutex<Option<bool>>>,
+    pub mapping_requested: bool,
+}
+
+impl PickQuery {
+    /// The query's region as `(origin, size)`, clipped to the view. `None`
+    /// when it lies entirely outside.
+    fn region(&self, view_size: [u32; 2]) -> Option<([u32; 2], [u32; 2])> {
+        let (min, max) = match *self {
+            PickQuery::Pixel { x, y } => ([x, y], [x.saturating_add(1), y.saturating_add(1)]),
+            PickQuery::Rect { min, max } => (min, max),
+        };
+        let max = [max[0].min(view_size[0]), max[1].min(view_size[1])];
+        if min[0] >= max[0] || min[1] >= max[1] {
+            return None
+        }
+        Some((min, [max[0] - min[0], max[1] - min[1]]))
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// INIT PICKING PASS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+impl PickingPass {
+    pub const TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+
+    /// `pipeline_layout` is the geometry pipeline layout.
+    pub fn new(handle: &GpuHandle, pipeline_layout: &wgpu::PipelineLayout) -> Self {
+        let module = handle.device.create_shader_module(include_wgsl!("./../../../shaders/picking.wgsl"));
+        let vertex_buffers = [vertex_buffer_layout(), instance_buffer_layout()];
+        let create_pipeline = |label: &str, entry_point: &str, buffers: &[wgpu::VertexBufferLayout]| {
+            handle.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point,
+                    buffers,
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: Self::TEXTURE_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+        let pipeline = create_pipeline("Picking pipeline", "vs_main", &vertex_buffers[..1]);
+        let instanced_pipeline = create_pipeline("Instanced picking pipeline", "vs_instanced", &vertex_buffers);
+        PickingPass {
+            module,
+            pipeline,
+            instanced_pipeline,
+            target: None,
+            queued: Vec::new(),
+            in_flight: Vec::new(),
+            next_ticket: 0,
+        }
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// ENCODE PICKING PASS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+impl PickingPass {
+    pub fn queue(&mut self, query: PickQuery) -> PickTicket {
+        let ticket = PickTicket(self.next_ticket);
+        self.next_ticket += 1;
+        self.queued.push((ticket, query));
+        ticket
+    }
+    /// Creates the ID target if any queries are queued, and returns whether
+    /// the pass needs to run this frame.
+    pub fn prepare(&mut self, handle: &GpuHandle, view_resolution: ViewResolution) -> bool {
+        if self.queued.is_empty() {
+            return false
+        }
+        let target_changed = self.target
+            .as_ref()
+            .map(|target| target.view_resolution.resolution_changed(&view_resolution))
+            .unwrap_or(true);
+        if target_changed {
+            self.target = Some(Self::create_target(handle, view_resolution));
+        }
+        true
+    }
+    /// Draws every batch with the layer's vertex buffer, index buffer,
+    /// instance buffer and geometry bind group.
+    #[allow(clippy::too_many_arguments)]
+    pub fn encode_ids(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        vbo: &wgpu::Buffer,
+        ibo: &wgpu::Buffer,
+        index_chunks: &[IndexChunk],
+        instance_buffer: &wgpu::Buffer,
+        bind_group: &wgpu::BindGroup,
+        nodes: &[DrawNode],
+    ) {
+        let target = self.target.as_ref().expect("[PickingPass] target was never prepared");
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Picking pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &target.view,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: true,
+                },
+                resolve_target: None,
+            })],
+            depth_stencil_attachment: None,
+        });
+        pass.set_bind_group(0, bind_group, &[]);
+        helpers::set_index_buffer(&mut pass, ibo, index_chunks);
+        pass.set_vertex_buffer(0, vbo.slice(..));
+        if instance_buffer.size() > 0 {
+            pass.set_vertex_buffer(1, instance_buffer.slice(..));
+        }
+        for node in nodes {
+            node.visit(&mut |node| {
+                if let DrawNode::Batch(batch) = node {
+                    match batch.instances.clone() {
+                        Some(instances) => {
+                            pass.set_pipeline(&self.instanced_pipeline);
+                            helpers::draw_indexed(&mut pass, ibo, index_chunks, batch.indices.clone(), instances);
+                        }
+                        None => {
+                            pass.set_pipeline(&self.pipeline);
+                            helpers::draw_indexed(&mut pass, ibo, index_chunks, batch.indices.clone(), 0..1);
+                        }
+                    }
+                }
+            });
+        }
+    }
+    /// Copies each queued query's region into its own readback buffer.
+    pub fn encode_readbacks(
+        &mut self,
+        handle: &GpuHandle,
+        encoder: &mut wgpu::CommandEncoder,
+        view_resolution: ViewResolution,
+        op_primitives: &[std::ops::Range<u32>],
+    ) {
+        let target = self.target.as_ref().expect("[PickingPass] target was never prepared");
+        let view_size = [view_resolution.width(), view_resolution.height()];
+        for (ticket, query) in std::mem::take(&mut self.queued) {
+            let (origin, size) = query.region(view_size).unwrap_or(([0, 0], [0, 0]));
+            let bytes_per_pixel = std::mem::size_of::<u32>() as u32;
+            let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+            let padded_bytes_per_row = (size[0] * bytes_per_pixel).div_ceil(alignment).max(1) * alignment;
+            let buffer = handle.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Picking readback"),
+                size: (padded_bytes_per_row * size[1].max(1)) as u64,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+            if size[0] > 0 && size[1] > 0 {
+                encoder.copy_texture_to_buffer(
+                    wgpu::ImageCopyTexture {
+                        texture: &target.texture,
+                        mip_level: 0,
+                        origin: wgpu::Origin3d { x: origin[0], y: origin[1], z: 0 },
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    wgpu::ImageCopyBuffer {
+                        buffer: &buffer,
+                        layout: wgpu::ImageDataLayout {
+                            offset: 0,
+                            bytes_per_row: std::num::NonZeroU32::new(padded_bytes_per_row),
+                            rows_per_image: None,
+                        },
+                    },
+                    wgpu::Extent3d {
+                        width: size[0],
+                        height: size[1],
+                        depth_or_array_layers: 1,
+                    },
+                );
+            }
+            self.in_flight.push(PickReadback {
+                ticket,
+                query,
+                size,
+                padded_bytes_per_row,
+                buffer,
+                op_primitives: op_primitives.to_vec(),
+                mapped: Arc::new(Mutex::new(None)),
+                mapping_requested: false,
+            });
+        }
+    }
+    /// Call once the encoder holding the copies has been submitted.
+    pub fn request_mapping(&mut self) {
+        for readback in self.in_flight.iter_mut().filter(|readback| !readback.mapping_requested) {
+            let mapped = readback.mapped.clone();
+            readback.buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                *mapped.lock().unwrap() = Some(result.is_ok());
+            });
+            readback.mapping_requested = true;
+        }
+    }
+    /// Returns the results of every readback that has completed.
+    pub fn poll(&mut self, handle: &GpuHandle) -> Vec<PickResult> {
+        if self.in_flight.is_empty() {
+            return Vec::new()
+        }
+        handle.device.poll(wgpu::Maintain::Poll);
+        let mut results = Vec::new();
+        let mut ix = 0;
+        while ix < self.in_flight.len() {
+            let mapped = *self.in_flight[ix].mapped.lock().unwrap();
+            match mapped {
+                Some(ok) => {
+                    let readback = self.in_flight.remove(ix);
+                    results.push(readback.finish(ok));
+                }
+                None => ix += 1,
+            }
+        }
+        results
+    }
+    fn create_target(handle: &GpuHandle, view_resolution: ViewResolution) -> PickingTarget {
+        let texture = handle.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Picking target"),
+            size: wgpu::Extent3d {
+                width: view_resolution.width(),
+                height: view_resolution.height(),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::TEXTURE_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        PickingTarget { view_resolution, texture, view }
+    }
+}
+
+impl PickReadback {
+    fn finish(self, mapped: bool) -> PickResult {
+        let mut result = PickResult { ticket: self.ticket, query: self.query, ops: Vec::new() };
+        if !mapped || self.size[0] == 0 || self.size[1] == 0 {
+            return result
+        }
+        {
+            let data = self.buffer.slice(..).get_mapped_range();
+            for row in data.chunks(self.padded_bytes_per_row as usize).take(self.size[1] as usize) {
+                let row: &[u32] = bytemuck::cast_slice(&row[..self.size[0] as usize * 4]);
+                for id in row.iter().copied().filter(|id| *id != 0) {
+                    if let Some(op) = op_for_primitive(&self.op_primitives, id - 1) {
+                        result.ops.push(op);
+                    }
+                }
+            }
+        }
+        self.buffer.unmap();
+        result.ops.sort_unstable_by(|a, b| b.cmp(a));
+        result.ops.dedup();
+        result
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// CANVAS-LAYER PICKING API
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+impl CanvasLayer {
+    /// Queues a query, which is rendered with the next frame. Its result
+    /// shows up in `poll_picks` a frame or two later.
+    pub fn pick(&mut self, query: PickQuery) -> PickTicket {
+        self.picking.queue(query)
+    }
+    /// Results of finished queries. Never blocks, so call it every frame.
+    pub fn poll_picks(&mut self, handle: &GpuHandle) -> Vec<PickResult> {
+        self.picking.poll(handle)
+    }
+}
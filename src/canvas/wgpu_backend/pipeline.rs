@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use wgpu::include_wgsl;
+use super::gpu_target::GpuHandle;
+use crate::data::gpu_types;
+use crate::data::draw_cmds::BlendMode;
+use crate::data::picture::DrawNode;
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// PIPELINE CACHE
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PipelineKey {
+    pub blend_mode: BlendMode,
+    pub wireframe: bool,
+    /// Draws a symbol per instance, from the instance buffer in slot 1.
+    pub instanced: bool,
+}
+
+/// Geometry pipelines only differ in their blend state (and polygon mode), so
+/// they are built lazily the first time a batch asks for one and kept around
+/// for the lifetime of the layer.
+pub struct PipelineCache {
+    pub pipeline_layout: wgpu::PipelineLayout,
+    pub geometry_module: wgpu::ShaderModule,
+    pub instanced_module: wgpu::ShaderModule,
+    pub msaa_samples: u32,
+    pub pipelines: HashMap<PipelineKey, wgpu::RenderPipeline>,
+}
+
+impl PipelineCache {
+    pub fn new(handle: &GpuHandle, pipeline_layout: wgpu::PipelineLayout, msaa_samples: u32) -> Self {
+        let geometry_module = handle.device.create_shader_module(include_wgsl!("./../../../shaders/geometry.wgsl"));
+        let instanced_module = handle.device.create_shader_module(include_wgsl!("./../../../shaders/instanced.wgsl"));
+        PipelineCache { pipeline_layout, geometry_module, instanced_module, msaa_samples, pipelines: HashMap::new() }
+    }
+    /// Builds any pipeline the given batches need that doesn't exist yet.
+    /// Call this before starting a render pass, since the pass holds shared
+    /// borrows of the cached pipelines.
+    pub fn prepare(&mut self, handle: &GpuHandle, nodes: &[DrawNode], wireframe: bool) {
+        let mut keys = Vec::new();
+        for node in nodes {
+            node.visit(&mut |node| {
+                if let DrawNode::Batch(batch) = node {
+                    let instanced = batch.instances.is_some();
+                    keys.push(PipelineKey { blend_mode: batch.blend_mode, wireframe, instanced });
+                    // Batches drawn through a composite pass go source-over into its layer.
+                    if batch.blend_mode.requires_composite_pass_over(false) {
+                        keys.push(PipelineKey { blend_mode: BlendMode::SourceOver, wireframe, instanced });
+                    }
+                }
+            });
+        }
+        for key in keys {
+            if !self.pipelines.contains_key(&key) {
+                let pipeline = self.create_pipeline(handle, key);
+                self.pipelines.insert(key, pipeline);
+            }
+        }
+    }
+    pub fn get(&self, blend_mode: BlendMode, wireframe: bool, instanced: bool) -> &wgpu::RenderPipeline {
+        self.pipelines
+            .get(&PipelineKey { blend_mode, wireframe, instanced })
+            .expect("[PipelineCache.get] pipeline was never prepared")
+    }
+    fn create_pipeline(&self, handle: &GpuHandle, key: PipelineKey) -> wgpu::RenderPipeline {
+        let fragment_targets = &[Some(wgpu::ColorTargetState {
+            format: GpuHandle::TEXTURE_FORMAT,
+            blend: Some(blend_state(key.blend_mode)),
+            write_mask: wgpu::ColorWrites::ALL,
+        })];
+        let polygon_mode = if key.wireframe {
+            // TODO: this isn't what we want: we'd need the equivalent of VK_POLYGON_MODE_LINE,
+            // but it doesn't seem to be exposed by wgpu?
+            wgpu::PolygonMode::Line
+        } else {
+            wgpu::PolygonMode::Fill
+        };
+        let vertex_buffers = [vertex_buffer_layout(), instance_buffer_layout()];
+        let (vertex, fragment) = if key.instanced {
+            let vertex = wgpu::VertexState {
+                module: &self.instanced_module,
+                entry_point: "vs_main",
+                buffers: &vertex_buffers,
+            };
+            let fragment = wgpu::FragmentState {
+                module: &self.instanced_module,
+                entry_point: "fs_main",
+                targets: fragment_targets,
+            };
+            (vertex, fragment)
+        } else {
+            let vertex = wgpu::VertexState {
+                module: &self.geometry_module,
+                entry_point: "vs_main",
+                buffers: &vertex_buffers[..1],
+            };
+            let fragment = wgpu::FragmentState {
+                module: &self.geometry_module,
+                entry_point: "fs_main",
+                targets: fragment_targets,
+            };
+            (vertex, fragment)
+        };
+        handle.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&self.pipeline_layout),
+            vertex,
+            fragment: Some(fragment),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                polygon_mode,
+                front_face: wgpu::FrontFace::Ccw,
+                strip_index_format: None,
+                cull_mode: None,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: self.msaa_samples,
+                mask: !0,
+                alpha_to_coverage_enabled: true,
+            },
+            multiview: None,
+        })
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// VERTEX LAYOUTS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![
+    0 => Float32x2,
+    1 => Uint32,
+];
+
+const INSTANCE_ATTRIBUTES: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+    2 => Float32x2,
+    3 => Float32x2,
+    4 => Float32x2,
+    5 => Float32x4,
+    6 => Uint32,
+];
+
+/// `GpuVertex`, in slot 0.
+pub fn vertex_buffer_layout() -> wgpu::VertexBufferLayout<'static> {
+    wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<gpu_types::GpuVertex>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &VERTEX_ATTRIBUTES,
+    }
+}
+
+/// `GpuInstance`, in slot 1: the transform's three columns, the color and
+/// the picking `prim_id`.
+pub fn instance_buffer_layout() -> wgpu::VertexBufferLayout<'static> {
+    wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<gpu_types::GpuInstance>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &INSTANCE_ATTRIBUTES,
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// BLEND STATES
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// Fixed-function equivalents of each blend mode, assuming premultiplied
+/// source colors. Multiply drops the `src * (1 - dst_alpha)` term, which is
+/// only exact over an opaque backdrop; over a group's target it goes through
+/// the composite pass instead, see [`BlendMode::requires_composite_pass_over`].
+///
+/// Modes that need a composite pass are first drawn source-over into a
+/// transparent offscreen target, so they map to the default here.
+pub fn blend_state(blend_mode: BlendMode) -> wgpu::BlendState {
+    use wgpu::{BlendComponent, BlendFactor, BlendOperation};
+    let color = match blend_mode {
+        BlendMode::SourceOver | BlendMode::Overlay | BlendMode::Darken | BlendMode::Lighten => {
+            return wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING
+        }
+        BlendMode::Multiply => BlendComponent {
+            src_factor: BlendFactor::Dst,
+            dst_factor: BlendFactor::OneMinusSrcAlpha,
+            operation: BlendOperation::Add,
+        },
+        BlendMode::Screen => BlendComponent {
+            src_factor: BlendFactor::One,
+            dst_factor: BlendFactor::OneMinusSrc,
+            operation: BlendOperation::Add,
+        },
+        BlendMode::Additive => BlendComponent {
+            src_factor: BlendFactor::One,
+            dst_factor: BlendFactor::One,
+            operation: BlendOperation::Add,
+        },
+    };
+    let alpha = BlendComponent {
+        src_factor: BlendFactor::One,
+        dst_factor: BlendFactor::OneMinusSrcAlpha,
+        operation: BlendOperation::Add,
+    };
+    wgpu::BlendState { color, alpha }
+}
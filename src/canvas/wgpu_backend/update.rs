@@ -6,6 +6,10 @@ use crate::frontend::DrawableObject;
 use crate::data::{Content, TessellatedContent};
 use crate::data::gpu_types;
 use crate::data::collections::CowCollection;
+use crate::frontend::{ColorScheme, LiveStroke, Scene, SceneChanges};
+use crate::data::{PictureResolution, TesselationSettings, ViewInfo};
+use crate::data::optimize::OptimizedMesh;
+use crate::canvas::worker::TessellationWorker;
 use wgpu::util::DeviceExt;
 
 
@@ -33,23 +37,181 @@ impl<const N: usize> CanvasRenderer<N> {
 // }
 
 impl CanvasLayer {
+    /// Syncs a keyed scene into this layer's tessellator and uploads only the
+    /// parts of the buffers that changed. Buffers that are too small are
+    /// replaced with larger ones, and then written in full.
+    pub fn update_scene(&mut self, handle: &GpuHandle, scene: &mut Scene) -> UpdateStatus {
+        match scene.sync(&mut self.scene_tessellator) {
+            Some(changes) => {
+                self.write_scene_changes(handle, &changes);
+                UpdateStatus::Changed
+            }
+            None => UpdateStatus::Unchanged,
+        }
+    }
+    /// Appends a sample to a stroke being drawn into this layer, and uploads
+    /// just the geometry it touched.
+    pub fn push_live_point(&mut self, handle: &GpuHandle, stroke: &mut LiveStroke, point: impl Into<lyon::math::Point>) -> UpdateStatus {
+        match stroke.push(&mut self.scene_tessellator, point) {
+            Some(changes) => {
+                self.write_scene_changes(handle, &changes);
+                UpdateStatus::Changed
+            }
+            None => UpdateStatus::Unchanged,
+        }
+    }
+    /// Switches the scheme that color tokens and the clear color follow.
+    /// Only the primitives are rewritten.
+    pub fn set_color_scheme(&mut self, handle: &GpuHandle, color_scheme: ColorScheme) -> UpdateStatus {
+        if color_scheme == self.scene_tessellator.color_scheme {
+            return UpdateStatus::Unchanged
+        }
+        if let Some(changes) = self.scene_tessellator.set_color_scheme(color_scheme) {
+            self.write_scene_changes(handle, &changes);
+        }
+        UpdateStatus::Changed
+    }
+    /// Writes the picture resolution and the camera's view matrix. Runs
+    /// every frame, so moving the camera is just a field update.
+    pub fn write_globals(&self, handle: &GpuHandle, view_info: ViewInfo) {
+        let picture_resolution = self.scene_tessellator.picture_resolution;
+        handle.queue.write_buffer(
+            &self.globals_ubo,
+            0,
+            bytemuck::cast_slice(&[gpu_types::GpuGlobals {
+                picture_resolution: [picture_resolution.width(), picture_resolution.height()],
+                _pad: [0.0; 2],
+                view_matrix: self.camera.view_matrix(view_info, picture_resolution),
+            }]),
+        );
+    }
+    pub fn write_scene_changes(&mut self, handle: &GpuHandle, changes: &SceneChanges) {
+        let tessellator = &self.scene_tessellator;
+        let optimized = self.optimize_mesh.then(|| OptimizedMesh::new(&tessellator.mesh, &tessellator.nodes));
+        let write = |buffer: &mut wgpu::Buffer, label: &str, usage: wgpu::BufferUsages, data: &[u8], changed_from: usize| -> bool {
+            if data.len() as u64 > buffer.size() {
+                let size = (data.len() as u64).next_power_of_two();
+                *buffer = handle.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(label),
+                    size,
+                    usage: usage | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                handle.queue.write_buffer(buffer, 0, data);
+                return true
+            }
+            if changed_from < data.len() {
+                handle.queue.write_buffer(buffer, changed_from as u64, &data[changed_from..]);
+            }
+            false
+        };
+        match optimized.as_ref() {
+            Some(optimized) => {
+                write(&mut self.vbo, "Vertex buffer", wgpu::BufferUsages::VERTEX, bytemuck::cast_slice(&optimized.vertices), 0);
+                write(&mut self.ibo, "Index buffer", wgpu::BufferUsages::INDEX, &optimized.index_bytes, 0);
+            }
+            None => {
+                // Anything before the changes may have been optimized.
+                let rewrite = !self.index_chunks.is_empty();
+                write(
+                    &mut self.vbo,
+                    "Vertex buffer",
+                    wgpu::BufferUsages::VERTEX,
+                    bytemuck::cast_slice(&tessellator.mesh.vertices),
+                    if rewrite { 0 } else { changes.vertices.start as usize * std::mem::size_of::<gpu_types::GpuVertex>() },
+                );
+                write(
+                    &mut self.ibo,
+                    "Index buffer",
+                    wgpu::BufferUsages::INDEX,
+                    bytemuck::cast_slice(&tessellator.mesh.indices),
+                    if rewrite { 0 } else { changes.indices.start as usize * std::mem::size_of::<u32>() },
+                );
+            }
+        }
+        self.index_chunks = optimized.map(|optimized| optimized.chunks).unwrap_or_default();
+        self.cull_index.update(
+            &tessellator.mesh,
+            &tessellator.instances,
+            &tessellator.nodes,
+            &tessellator.op_primitives,
+            changes.indices.start,
+            changes.instances.start,
+        );
+        write(
+            &mut self.instance_buffer,
+            "Instance buffer",
+            wgpu::BufferUsages::VERTEX,
+            bytemuck::cast_slice(&tessellator.instances),
+            changes.instances.start as usize * std::mem::size_of::<gpu_types::GpuInstance>(),
+        );
+        let prims_replaced = write(
+            &mut self.prims_ssbo,
+            "Prims ssbo",
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE,
+            bytemuck::cast_slice(&tessellator.primitives),
+            changes.primitives.start as usize * std::mem::size_of::<gpu_types::GpuPrimitive>(),
+        );
+        self.prim_buffer_byte_size = self.prims_ssbo.size();
+        if prims_replaced {
+            self.bind_group = handle.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Bind group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::Buffer(self.globals_ubo.as_entire_buffer_binding()),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Buffer(self.prims_ssbo.as_entire_buffer_binding()),
+                    },
+                ],
+            });
+        }
+    }
+    /// Replaces everything in this layer with `content` and uploads it.
+    /// All buffers are written in one go before the next frame is
+    /// submitted, so a frame never mixes old and new geometry.
     pub fn update(
         &mut self,
         handle: &GpuHandle,
-        scene: TessellatedContent,
+        content: TessellatedContent,
     ) -> UpdateStatus {
-        // self.tessellated_content = content.to_owned_content().tessellate();
-        // self.vbo = handle.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        //     label: None,
-        //     contents: bytemuck::cast_slice(&self.tessellated_content.mesh.vertices),
-        //     usage: wgpu::BufferUsages::VERTEX,
-        // });
-        // self.ibo = handle.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        //     label: None,
-        //     contents: bytemuck::cast_slice(&self.tessellated_content.mesh.indices),
-        //     usage: wgpu::BufferUsages::INDEX,
-        // });
-        unimplemented!()
+        let tessellator = &mut self.scene_tessellator;
+        tessellator.mesh = content.mesh;
+        tessellator.primitives = content.primitives;
+        tessellator.primitive_colors = content.primitive_colors;
+        tessellator.instances = content.instances;
+        tessellator.nodes = content.nodes;
+        tessellator.op_primitives = content.op_primitives;
+        tessellator.picture_resolution = content.picture_resolution;
+        tessellator.symbols.clear();
+        // Content is always tessellated against the default scheme.
+        let color_scheme = std::mem::take(&mut tessellator.color_scheme);
+        let changes = SceneChanges {
+            vertices: 0..tessellator.mesh.vertices.len() as u32,
+            indices: 0..tessellator.mesh.indices.len() as u32,
+            primitives: 0..tessellator.primitives.len() as u32,
+            instances: 0..tessellator.instances.len() as u32,
+        };
+        let _ = tessellator.set_color_scheme(color_scheme);
+        self.write_scene_changes(handle, &changes);
+        UpdateStatus::Changed
+    }
+    /// Flattens curves to a fraction of a pixel under this layer's camera.
+    /// Pass it to `TessellationWorker::submit_with`, and submit again once
+    /// the zoom has changed enough to matter.
+    pub fn tessellation_settings(&self, view_info: ViewInfo, picture_resolution: PictureResolution) -> TesselationSettings {
+        TesselationSettings::for_camera(&self.camera, view_info, picture_resolution)
+    }
+    /// Swaps in the worker's newest finished job, if there is one. Until
+    /// then the layer keeps drawing what it has.
+    pub fn update_from_worker(&mut self, handle: &GpuHandle, worker: &mut TessellationWorker) -> UpdateStatus {
+        match worker.poll() {
+            Some(finished) => self.update(handle, finished.content),
+            None => UpdateStatus::Unchanged,
+        }
     }
     // pub fn update(
     //     &mut self,
@@ -0,0 +1,134 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::JoinHandle;
+
+use crate::data::{View, ViewInfo, TessellatedContent, TesselationSettings};
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// TESSELLATION WORKER
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// Tessellates submitted views on a thread of its own, so large edits don't
+/// block the frame. Keep drawing the current buffers, and hand each finished
+/// job to `CanvasLayer::update`, which swaps them all at once.
+///
+/// Only the newest view matters: submitting one cancels every older job,
+/// whether it's still queued or already tessellating, and their results are
+/// never returned.
+#[derive(Debug)]
+pub struct TessellationWorker {
+    jobs: Option<mpsc::Sender<TessellationJob>>,
+    results: mpsc::Receiver<FinishedTessellation>,
+    /// The generation of the newest submitted job.
+    latest: Arc<AtomicU64>,
+    /// The generation of the last job handed out.
+    returned: u64,
+    thread: Option<JoinHandle<()>>,
+}
+
+#[derive(Debug)]
+struct TessellationJob {
+    generation: u64,
+    view: View,
+    settings: Option<TesselationSettings>,
+}
+
+#[derive(Debug)]
+pub struct FinishedTessellation {
+    /// As returned by `TessellationWorker::submit`.
+    pub generation: u64,
+    pub view_info: ViewInfo,
+    pub content: TessellatedContent,
+}
+
+impl TessellationWorker {
+    pub fn spawn() -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<TessellationJob>();
+        let (result_sender, results) = mpsc::channel();
+        let latest = Arc::new(AtomicU64::new(0));
+        let worker_latest = latest.clone();
+        let thread = std::thread::Builder::new()
+            .name("tessellation".to_owned())
+            .spawn(move || {
+                while let Ok(mut job) = job_receiver.recv() {
+                    // Skip straight to the newest queued job.
+                    while let Ok(newer) = job_receiver.try_recv() {
+                        job = newer;
+                    }
+                    let stale = || worker_latest.load(Ordering::Acquire) != job.generation;
+                    if stale() {
+                        continue
+                    }
+                    let content = match job.view.picture.tessellate_until(job.settings, stale) {
+                        Some(content) => content,
+                        None => continue,
+                    };
+                    let finished = FinishedTessellation {
+                        generation: job.generation,
+                        view_info: job.view.view_info,
+                        content,
+                    };
+                    if result_sender.send(finished).is_err() {
+                        return
+                    }
+                }
+            })
+            .expect("Failed to spawn the tessellation thread");
+        TessellationWorker { jobs: Some(jobs), results, latest, returned: 0, thread: Some(thread) }
+    }
+    /// Queues a view, cancelling older jobs, and returns its generation.
+    /// Each op keeps its own tolerance.
+    pub fn submit(&mut self, view: View) -> u64 {
+        self.queue(view, None)
+    }
+    /// As `submit`, with the tolerance of `settings` for every op, e.g. from
+    /// `CanvasLayer::tessellation_settings`.
+    pub fn submit_with(&mut self, view: View, settings: TesselationSettings) -> u64 {
+        self.queue(view, Some(settings))
+    }
+    fn queue(&mut self, view: View, settings: Option<TesselationSettings>) -> u64 {
+        let generation = self.latest.load(Ordering::Acquire) + 1;
+        self.latest.store(generation, Ordering::Release);
+        let job = TessellationJob { generation, view, settings };
+        if let Some(jobs) = self.jobs.as_ref() {
+            let _ = jobs.send(job);
+        }
+        generation
+    }
+    /// The newest finished job, if one is ready. Never blocks.
+    pub fn poll(&mut self) -> Option<FinishedTessellation> {
+        let latest = self.latest.load(Ordering::Acquire);
+        let finished = self.results
+            .try_iter()
+            .filter(|finished| finished.generation == latest)
+            .last()?;
+        self.returned = finished.generation;
+        Some(finished)
+    }
+    /// Blocks until the newest job is done, e.g. for the first frame. `None`
+    /// when there's nothing left to wait for.
+    pub fn wait(&mut self) -> Option<FinishedTessellation> {
+        let latest = self.latest.load(Ordering::Acquire);
+        if latest == self.returned {
+            return None
+        }
+        let finished = self.results.iter().find(|finished| finished.generation == latest)?;
+        self.returned = finished.generation;
+        Some(finished)
+    }
+    /// Whether a submitted job hasn't been handed out yet.
+    pub fn is_pending(&self) -> bool {
+        self.latest.load(Ordering::Acquire) != self.returned
+    }
+}
+
+impl Drop for TessellationWorker {
+    fn drop(&mut self) {
+        // Cancels the running job, and ends the thread's loop.
+        self.latest.fetch_add(1, Ordering::AcqRel);
+        self.jobs = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
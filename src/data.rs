@@ -5,6 +5,20 @@ pub mod collections;
 pub mod picture;
 pub mod draw_cmds;
 pub mod drawable;
+pub mod filters;
+pub mod dash;
+pub mod spatial;
+pub mod hit_test;
+pub mod symbols;
+pub mod shapes;
+pub mod ink;
+pub mod color;
+pub mod camera;
+pub mod lod;
+pub mod optimize;
+pub mod culling;
+pub mod boolean;
+pub mod offset;
 
 use std::hash::Hash;
 
@@ -31,6 +45,7 @@ pub struct SceneGlobals {
 //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
 
 
+/// A size in whole pixels, or in picture units for `PictureResolution`.
 #[derive(Debug, Clone, Copy, Default, Hash, PartialEq)]
 pub struct Resolution<T> {
     width: T,
@@ -41,24 +56,77 @@ impl<T> Resolution<T> {
     pub fn new(width: T, height: T) -> Resolution<T> { Resolution { width, height } }
 }
 
-impl Resolution<u32> {
-    pub fn width(&self) -> u32 {self.width * 2}
-    pub fn height(&self) -> u32 {self.height * 2}
+impl<T: Copy> Resolution<T> {
+    pub fn width(&self) -> T {self.width}
+    pub fn height(&self) -> T {self.height}
 }
-impl Resolution<f32> {
-    pub fn width(&self) -> f32 {self.width}
-    pub fn height(&self) -> f32 {self.height}
+
+/// A view's size in logical points, as windowing systems report it before
+/// the device pixel ratio is applied.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LogicalSize {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl LogicalSize {
+    pub fn new(width: f32, height: f32) -> LogicalSize { LogicalSize { width, height } }
+    /// Rounded to whole pixels, and never smaller than one pixel so the
+    /// surface can always be configured.
+    pub fn to_physical(&self, scale_factor: f32) -> ViewResolution {
+        let to_pixels = |points: f32| ((points * scale_factor).round() as u32).max(1);
+        Resolution::new(to_pixels(self.width), to_pixels(self.height))
+    }
+}
+
+impl ViewResolution {
+    pub fn to_logical(&self, scale_factor: f32) -> LogicalSize {
+        LogicalSize::new(self.width as f32 / scale_factor, self.height as f32 / scale_factor)
+    }
 }
 
-#[derive(Debug, Clone, Default)]
+/// The view's size and device pixel ratio. The surface, MSAA textures and
+/// camera all work in the physical pixels these two give.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct ViewInfo {
-    pub view_resolution: Resolution<u32>
+    pub logical_size: LogicalSize,
+    /// Physical pixels per logical point: 1 on most desktop monitors, 2 or 3
+    /// on high density displays.
+    pub scale_factor: f32,
+}
+
+impl Default for ViewInfo {
+    fn default() -> Self {
+        ViewInfo { logical_size: LogicalSize::default(), scale_factor: 1.0 }
+    }
+}
+
+impl ViewInfo {
+    pub fn new(logical_size: LogicalSize, scale_factor: f32) -> ViewInfo {
+        ViewInfo { logical_size, scale_factor }
+    }
+    /// For windowing systems and headless targets that report pixels.
+    pub fn from_physical(view_resolution: ViewResolution, scale_factor: f32) -> ViewInfo {
+        ViewInfo { logical_size: view_resolution.to_logical(scale_factor), scale_factor }
+    }
+    pub fn view_resolution(&self) -> ViewResolution {
+        self.logical_size.to_physical(self.scale_factor)
+    }
+    /// For when the window moves to a monitor with another pixel ratio; the
+    /// logical size stays and the physical size follows.
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        self.scale_factor = scale_factor;
+    }
+    /// Physical pixels spanned by `points` logical points.
+    pub fn to_pixels(&self, points: f32) -> f32 {
+        points * self.scale_factor
+    }
 }
 
 
 #[derive(Debug, Clone)]
 pub struct View {
-    pub view_resolution: Resolution<u32>,
+    pub view_info: ViewInfo,
     pub picture: Picture,
 }
 
@@ -77,12 +145,18 @@ impl<T: Hash> Resolution<T> {
 }
 
 impl View {
+    pub fn view_resolution(&self) -> ViewResolution {
+        self.view_info.view_resolution()
+    }
+    /// True when the physical size or the pixel ratio differ.
     pub fn resolution_changed(&self, other: &View) -> bool {
-        self.view_resolution.resolution_changed(&other.view_resolution)
+        self.view_resolution().resolution_changed(&other.view_resolution())
+            || self.view_info.scale_factor != other.view_info.scale_factor
     }
 }
 
 
+/// In physical pixels.
 pub type ViewResolution = Resolution<u32>;
 pub type PictureResolution = Resolution<f32>;
 
@@ -116,7 +190,10 @@ impl LinearScale {
 //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
 
 
-#[derive(Debug, Clone, Copy)]
+/// With `u8` channels, an sRGB encoded color with straight alpha, as in CSS.
+/// See `color` for conversions to linear light and other color spaces, and
+/// for parsing.
+#[derive(Debug, Clone, Copy, PartialEq)]
 #[repr(C)]
 pub struct RGBA<T> {
     pub red: T,
@@ -127,6 +204,8 @@ pub struct RGBA<T> {
 
 
 impl RGBA<f32> {
+    /// Rescales the channels to `0.0..=1.0`, leaving them sRGB encoded. Use
+    /// `RGBA::to_linear` for colors that are blended or uploaded.
     pub fn from_u8(RGBA { red, green, blue, alpha }: RGBA<u8>) -> RGBA<f32> {
         let scale = LinearScale {
             domain: (0.0, u8::MAX as f32),
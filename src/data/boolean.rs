@@ -0,0 +1,678 @@
+use std::collections::{HashMap, HashSet};
+use lyon::geom::LineSegment;
+use lyon::math::{point, Box2D, Point};
+use lyon::path::{Event, FillRule, Path};
+use crate::data::draw_cmds::FillOp;
+use crate::data::ink;
+use crate::data::spatial::{self, RTree};
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// PATH BOOLEANS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// How the regions filled by two paths combine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BooleanOp {
+    /// Filled by either path, e.g. merging shapes.
+    Union,
+    /// Filled by both, e.g. highlighting an overlap.
+    Intersection,
+    /// Filled by the first path and not the second, e.g. erasing.
+    Difference,
+    /// Filled by exactly one of them.
+    Xor,
+}
+
+/// Curves are flattened within `tolerance` before combining, so the result
+/// is made of lines unless `refit_tolerance` is set, in which case what's
+/// left of each input's curves is fitted with cubic Béziers again. Straight
+/// input edges always stay lines.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BooleanOptions {
+    pub tolerance: f32,
+    /// How the first and the second path are filled.
+    pub fill_rules: [FillRule; 2],
+    pub refit_tolerance: Option<f32>,
+}
+
+impl Default for BooleanOptions {
+    fn default() -> Self {
+        BooleanOptions {
+            tolerance: lyon::tessellation::FillOptions::DEFAULT_TOLERANCE,
+            fill_rules: [FillRule::NonZero; 2],
+            refit_tolerance: None,
+        }
+    }
+}
+
+impl BooleanOptions {
+    /// Turns sharper than this within a curve end a refitted run, so the
+    /// curve's corners stay sharp.
+    pub const REFIT_CORNER_ANGLE: f32 = 40.0 * std::f32::consts::PI / 180.0;
+
+    pub fn with_tolerance(mut self, tolerance: f32) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+    pub fn with_fill_rules(mut self, first: FillRule, second: FillRule) -> Self {
+        self.fill_rules = [first, second];
+        self
+    }
+    pub fn with_refit(mut self, tolerance: f32) -> Self {
+        self.refit_tolerance = Some(tolerance);
+        self
+    }
+}
+
+impl BooleanOp {
+    /// Whether a point filled by `a`, `b` or both is filled by the result.
+    pub fn contains(&self, a: bool, b: bool) -> bool {
+        match self {
+            BooleanOp::Union => a || b,
+            BooleanOp::Intersection => a && b,
+            BooleanOp::Difference => a && !b,
+            BooleanOp::Xor => a != b,
+        }
+    }
+    /// The outline of the combined region, as closed subpaths. Outer
+    /// boundaries wind one way and holes the other, so the result fills the
+    /// same with either fill rule. Self-intersections and overlapping
+    /// subpaths in either input are resolved by its fill rule.
+    pub fn apply(&self, a: &Path, b: &Path, options: &BooleanOptions) -> Path {
+        let mut edges = Vec::new();
+        flatten(a, options.tolerance, 0, &mut edges);
+        flatten(b, options.tolerance, 1, &mut edges);
+        let (edges, crossings) = split_edges(&edges);
+        let boundary = classify(&edges, *self, options.fill_rules);
+        let loops = link(boundary);
+        let mut builder = Path::builder();
+        for points in loops {
+            match options.refit_tolerance {
+                Some(refit_tolerance) => refit_loop(&points, &crossings, refit_tolerance, &mut builder),
+                None => {
+                    builder.begin(points[0].0);
+                    for (point, _) in points[1..].iter() {
+                        builder.line_to(*point);
+                    }
+                    builder.end(true);
+                }
+            }
+        }
+        builder.build()
+    }
+}
+
+impl FillOp {
+    /// Combines this fill with `other` by their own fill rules, keeping this
+    /// op's color, fill settings and blend mode.
+    pub fn boolean(&self, other: &FillOp, op: BooleanOp, options: &BooleanOptions) -> FillOp {
+        let options = options.with_fill_rules(self.fill_settings.fill_rule, other.fill_settings.fill_rule);
+        FillOp {
+            path: op.apply(&self.path, &other.path, &options),
+            ..self.clone()
+        }
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// EDGES
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+#[derive(Debug, Clone, Copy)]
+struct Edge {
+    from: Point,
+    to: Point,
+    /// Which input the edge is from.
+    operand: usize,
+    /// Whether the edge is part of a flattened curve.
+    curved: bool,
+}
+
+impl Edge {
+    fn bounds(&self) -> Box2D {
+        Box2D::new(self.from.min(self.to), self.from.max(self.to))
+    }
+}
+
+/// Pushes the flattened edges of every subpath, closing each one as fills
+/// do. Non-finite points and zero length edges are dropped.
+fn flatten(path: &Path, tolerance: f32, operand: usize, edges: &mut Vec<Edge>) {
+    let mut push = |from: Point, to: Point, curved: bool| {
+        if from != to && from.x.is_finite() && from.y.is_finite() && to.x.is_finite() && to.y.is_finite() {
+            edges.push(Edge { from: key_point(from), to: key_point(to), operand, curved });
+        }
+    };
+    for event in path.iter() {
+        match event {
+            Event::Line { from, to } => push(from, to, false),
+            Event::Quadratic { from, ctrl, to } => {
+                let curve = lyon::geom::QuadraticBezierSegment { from, ctrl, to };
+                curve.for_each_flattened(tolerance, &mut |line: &LineSegment<f32>| push(line.from, line.to, true));
+            }
+            Event::Cubic { from, ctrl1, ctrl2, to } => {
+                let curve = lyon::geom::CubicBezierSegment { from, ctrl1, ctrl2, to };
+                curve.for_each_flattened(tolerance, &mut |line: &LineSegment<f32>| push(line.from, line.to, true));
+            }
+            Event::End { last, first, .. } => push(last, first, false),
+            Event::Begin { .. } => {}
+        }
+    }
+}
+
+/// Turns -0.0 into 0.0, so equal points have equal bits.
+fn key_point(point: Point) -> Point {
+    lyon::math::point(point.x + 0.0, point.y + 0.0)
+}
+
+fn key(point: Point) -> (u32, u32) {
+    (point.x.to_bits(), point.y.to_bits())
+}
+
+/// Splits every edge wherever another one crosses or touches it, or overlaps
+/// it along the same line, so edges only meet at their ends. Both edges are
+/// split at the very same point. Also returns the points split at.
+fn split_edges(edges: &[Edge]) -> (Vec<Edge>, HashSet<(u32, u32)>) {
+    let tree = RTree::new(edges.iter().enumerate().map(|(ix, edge)| (edge.bounds(), ix)));
+    let mut cuts: Vec<Vec<(f64, Point)>> = vec![Vec::new(); edges.len()];
+    for (ix, edge) in edges.iter().enumerate() {
+        tree.query(&edge.bounds(), |_, other_ix| {
+            if *other_ix <= ix {
+                return
+            }
+            for (on_first, t, point) in intersections(edge, &edges[*other_ix]) {
+                let target = if on_first { ix } else { *other_ix };
+                cuts[target].push((t, point));
+            }
+        });
+    }
+    let crossings = cuts.iter().flatten().map(|(_, point)| key(*point)).collect();
+    let mut split = Vec::with_capacity(edges.len());
+    for (edge, mut cuts) in edges.iter().zip(cuts) {
+        cuts.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut from = edge.from;
+        for (_, point) in cuts.into_iter().chain([(1.0, edge.to)]) {
+            if point != from {
+                split.push(Edge { from, to: point, ..*edge });
+                from = point;
+            }
+        }
+    }
+    (split, crossings)
+}
+
+/// Where the edges must be cut: `(on_first, t, point)` with `t` along the
+/// edge being cut. Ends of one edge that lie on the other are used exactly.
+fn intersections(first: &Edge, second: &Edge) -> Vec<(bool, f64, Point)> {
+    let (p, r) = (to_f64(first.from), sub(to_f64(first.to), to_f64(first.from)));
+    let (q, s) = (to_f64(second.from), sub(to_f64(second.to), to_f64(second.from)));
+    let denominator = cross(r, s);
+    let offset = sub(q, p);
+    let mut cuts = Vec::new();
+    let interior = |t: f64| t > 0.0 && t < 1.0;
+    if denominator != 0.0 {
+        let t = cross(offset, s) / denominator;
+        let u = cross(offset, r) / denominator;
+        if !(0.0..=1.0).contains(&t) || !(0.0..=1.0).contains(&u) {
+            return cuts
+        }
+        let point = if u == 0.0 {
+            second.from
+        } else if u == 1.0 {
+            second.to
+        } else if t == 0.0 {
+            first.from
+        } else if t == 1.0 {
+            first.to
+        } else {
+            point((p.0 + r.0 * t) as f32, (p.1 + r.1 * t) as f32)
+        };
+        if interior(t) {
+            cuts.push((true, t, point));
+        }
+        if interior(u) {
+            cuts.push((false, u, point));
+        }
+        return cuts
+    }
+    if cross(offset, r) != 0.0 {
+        // Parallel, on different lines.
+        return cuts
+    }
+    // On one line: cut each edge at the other's ends that lie within it.
+    let along = |origin: (f64, f64), direction: (f64, f64), point: Point| {
+        dot(sub(to_f64(point), origin), direction) / dot(direction, direction)
+    };
+    for end in [second.from, second.to] {
+        let t = along(p, r, end);
+        if interior(t) {
+            cuts.push((true, t, end));
+        }
+    }
+    for end in [first.from, first.to] {
+        let u = along(q, s, end);
+        if interior(u) {
+            cuts.push((false, u, end));
+        }
+    }
+    cuts
+}
+
+fn to_f64(point: Point) -> (f64, f64) {
+    (point.x as f64, point.y as f64)
+}
+fn sub(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    (a.0 - b.0, a.1 - b.1)
+}
+fn cross(a: (f64, f64), b: (f64, f64)) -> f64 {
+    a.0 * b.1 - a.1 * b.0
+}
+fn dot(a: (f64, f64), b: (f64, f64)) -> f64 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// CLASSIFICATION
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// Split edges with the same ends, merged. `windings` is how much each input
+/// winds from `from` to `to`, counting edges the other way negatively.
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    from: Point,
+    to: Point,
+    windings: [i32; 2],
+    /// Unless an edge that isn't curved lies along it too.
+    curved: bool,
+}
+
+impl Span {
+    fn bounds(&self) -> Box2D {
+        Box2D::new(self.from.min(self.to), self.from.max(self.to))
+    }
+    /// Closer to horizontal than vertical.
+    fn is_flat(&self) -> bool {
+        (self.to.x - self.from.x).abs() > (self.to.y - self.from.y).abs()
+    }
+    /// How the span changes the winding numbers along a ray crossing it,
+    /// along +x or, when `vertical`, along +y. Counter-clockwise loops, with
+    /// y pointing up, wind positively.
+    fn crossing(&self, vertical: bool) -> i32 {
+        match vertical {
+            false if self.to.y > self.from.y => 1,
+            false => -1,
+            true if self.to.x < self.from.x => 1,
+            true => -1,
+        }
+    }
+}
+
+/// The directed edges of the result's boundary, with the region on their
+/// left (y pointing up), and whether each is curved.
+fn classify(edges: &[Edge], op: BooleanOp, fill_rules: [FillRule; 2]) -> Vec<(Point, Point, bool)> {
+    let mut spans = HashMap::new();
+    for edge in edges {
+        let forward = (key(edge.from), key(edge.to)) < (key(edge.to), key(edge.from));
+        let (from, to) = if forward { (edge.from, edge.to) } else { (edge.to, edge.from) };
+        let span = spans.entry((key(from), key(to))).or_insert(Span { from, to, windings: [0; 2], curved: true });
+        span.windings[edge.operand] += if forward { 1 } else { -1 };
+        span.curved &= edge.curved;
+    }
+    // Sorted, so the same inputs always give the same loops.
+    let mut spans = spans
+        .into_iter()
+        .filter(|(_, span)| span.windings != [0; 2])
+        .collect::<Vec<_>>();
+    spans.sort_unstable_by_key(|(key, _)| *key);
+    let spans = spans.into_iter().map(|(_, span)| span).collect::<Vec<_>>();
+    let bounds = match spans.iter().map(Span::bounds).reduce(|a, b| spatial::union(&a, &b)) {
+        Some(bounds) => bounds,
+        None => return Vec::new(),
+    };
+    let tree = RTree::new(spans.iter().enumerate().map(|(ix, span)| (span.bounds(), ix)));
+    let filled = |windings: [i32; 2]| {
+        let [a, b] = [0, 1].map(|operand| match fill_rules[operand] {
+            FillRule::EvenOdd => windings[operand] % 2 != 0,
+            FillRule::NonZero => windings[operand] != 0,
+        });
+        op.contains(a, b)
+    };
+    let mut boundary = Vec::new();
+    for (ix, span) in spans.iter().enumerate() {
+        // Cast a ray from the span's middle, away from the span, and add up
+        // what it crosses. That's the winding on the ray's side; the other
+        // side also has the span's own. The ray runs across the span, so a
+        // middle rounded off the span doesn't land on its other side.
+        let middle = span.from.lerp(span.to, 0.5);
+        let flat = span.is_flat();
+        let ray = match flat {
+            false => Box2D::new(middle, point(bounds.max.x, middle.y)),
+            true => Box2D::new(middle, point(middle.x, bounds.max.y)),
+        };
+        let mut ahead = [0; 2];
+        tree.query(&ray, |_, other_ix| {
+            if *other_ix == ix {
+                return
+            }
+            let other = &spans[*other_ix];
+            if crosses_ray(other, middle, flat) {
+                let crossing = other.crossing(flat);
+                ahead[0] += crossing * other.windings[0];
+                ahead[1] += crossing * other.windings[1];
+            }
+        });
+        let crossing = span.crossing(flat);
+        let behind = [0, 1].map(|operand| ahead[operand] + crossing * span.windings[operand]);
+        let (filled_ahead, filled_behind) = (filled(ahead), filled(behind));
+        if filled_ahead == filled_behind {
+            continue
+        }
+        // The ray points along +x, or +y for a flat span. Keep the
+        // filled side on the left.
+        let (low, high) = match flat {
+            false if span.from.y < span.to.y => (span.from, span.to),
+            false => (span.to, span.from),
+            true if span.from.x < span.to.x => (span.from, span.to),
+            true => (span.to, span.from),
+        };
+        let (from, to) = match (flat, filled_ahead) {
+            (false, true) => (high, low),
+            (false, false) => (low, high),
+            (true, true) => (low, high),
+            (true, false) => (high, low),
+        };
+        boundary.push((from, to, span.curved));
+    }
+    boundary
+}
+
+/// Whether a ray from `origin` along +x, or +y when `vertical`, crosses
+/// `span`. Ends count on one side only, so a ray through a vertex is
+/// counted once.
+fn crosses_ray(span: &Span, origin: Point, vertical: bool) -> bool {
+    let (from, to, origin) = match vertical {
+        false => (span.from, span.to, origin),
+        true => (span.from.yx(), span.to.yx(), origin.yx()),
+    };
+    if (from.y > origin.y) == (to.y > origin.y) {
+        return false
+    }
+    let t = (origin.y as f64 - from.y as f64) / (to.y as f64 - from.y as f64);
+    let x = from.x as f64 + (to.x as f64 - from.x as f64) * t;
+    x > origin.x as f64
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// LOOPS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// Chains boundary edges into closed loops of points, each with whether the
+/// edge leaving it is curved. Points in the middle of straight runs are
+/// dropped, and so are loops with no area.
+fn link(edges: Vec<(Point, Point, bool)>) -> Vec<Vec<(Point, bool)>> {
+    let mut outgoing: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+    for (ix, (from, _, _)) in edges.iter().enumerate() {
+        outgoing.entry(key(*from)).or_default().push(ix);
+    }
+    let mut used = vec![false; edges.len()];
+    let mut loops = Vec::new();
+    for first in 0..edges.len() {
+        if used[first] {
+            continue
+        }
+        let start = edges[first].0;
+        let mut points = Vec::new();
+        let mut current = first;
+        loop {
+            used[current] = true;
+            let (from, to, curved) = edges[current];
+            points.push((from, curved));
+            if to == start {
+                break
+            }
+            let next = outgoing
+                .get(&key(to))
+                .and_then(|candidates| candidates.iter().copied().find(|ix| !used[*ix]));
+            match next {
+                Some(next) => current = next,
+                // Only when rounding left the boundary open; close it here.
+                None => {
+                    points.push((to, false));
+                    break
+                }
+            }
+        }
+        let points = simplify(points);
+        if points.len() >= 3 {
+            loops.push(points);
+        }
+    }
+    loops
+}
+
+/// Drops points that continue straight on between edges of the same kind,
+/// in a closed loop.
+fn simplify(mut points: Vec<(Point, bool)>) -> Vec<(Point, bool)> {
+    let mut ix = 0;
+    while ix < points.len() && points.len() >= 3 {
+        let count = points.len();
+        let (previous, previous_curved) = points[(ix + count - 1) % count];
+        let (next, _) = points[(ix + 1) % count];
+        let (point, curved) = points[ix];
+        let a = sub(to_f64(point), to_f64(previous));
+        let b = sub(to_f64(next), to_f64(point));
+        if cross(a, b) == 0.0 && dot(a, b) > 0.0 && curved == previous_curved {
+            points.remove(ix);
+            ix = ix.saturating_sub(1);
+        } else {
+            ix += 1;
+        }
+    }
+    points
+}
+
+/// Fits Béziers to the curved runs of a loop, which end at sharp turns,
+/// straight edges, and points where the inputs crossed.
+fn refit_loop(points: &[(Point, bool)], crossings: &HashSet<(u32, u32)>, tolerance: f32, builder: &mut lyon::path::path::Builder) {
+    let count = points.len();
+    let min_cos = BooleanOptions::REFIT_CORNER_ANGLE.cos();
+    let is_corner = |ix: usize| {
+        let (previous, previous_curved) = points[(ix + count - 1) % count];
+        let (point, curved) = points[ix];
+        let (next, _) = points[(ix + 1) % count];
+        let turn = (point - previous).normalize().dot((next - point).normalize());
+        !curved || !previous_curved || crossings.contains(&key(point)) || turn < min_cos
+    };
+    let corners = (0..count).filter(|ix| is_corner(*ix)).collect::<Vec<_>>();
+    // Without corners the loop is one smooth run, from its first point
+    // back to it.
+    let starts = if corners.is_empty() { vec![0] } else { corners };
+    builder.begin(points[starts[0]].0);
+    for (nth, start) in starts.iter().enumerate() {
+        let end = starts.get(nth + 1).copied().unwrap_or(starts[0] + count);
+        let run = (*start..=end).map(|ix| points[ix % count].0).collect::<Vec<_>>();
+        if run.len() == 2 {
+            builder.line_to(run[1]);
+            continue
+        }
+        for segment in ink::fit_cubics(&run, tolerance) {
+            builder.cubic_bezier_to(segment.ctrl1, segment.ctrl2, segment.to);
+        }
+    }
+    builder.end(true);
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// TESTS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lyon::path::Winding;
+    use lyon::path::iterator::PathIterator;
+
+    fn square(min: (f32, f32), size: f32) -> Path {
+        polygon(&[min, (min.0 + size, min.1), (min.0 + size, min.1 + size), (min.0, min.1 + size)])
+    }
+
+    fn polygon(points: &[(f32, f32)]) -> Path {
+        let mut builder = Path::builder();
+        builder.begin(point(points[0].0, points[0].1));
+        for (x, y) in points[1..].iter() {
+            builder.line_to(point(*x, *y));
+        }
+        builder.end(true);
+        builder.build()
+    }
+
+    fn circle(center: (f32, f32), radius: f32) -> Path {
+        let mut builder = Path::builder();
+        builder.add_circle(point(center.0, center.1), radius, Winding::Positive);
+        builder.build()
+    }
+
+    /// Signed, so holes wound the other way are taken off.
+    fn area(path: &Path) -> f32 {
+        let mut area = 0.0;
+        for event in path.iter().flattened(0.001) {
+            if let Event::Line { from, to } | Event::End { last: from, first: to, close: true } = event {
+                area += from.x * to.y - to.x * from.y;
+            }
+        }
+        area / 2.0
+    }
+
+    fn subpaths(path: &Path) -> usize {
+        path.iter().filter(|event| matches!(event, Event::Begin { .. })).count()
+    }
+
+    fn assert_area(path: &Path, expected: f32) {
+        assert!((area(path) - expected).abs() < 1e-3, "area {} instead of {}", area(path), expected);
+    }
+
+    #[test]
+    fn combines_overlapping_squares() {
+        let (a, b) = (square((0.0, 0.0), 2.0), square((1.0, 1.0), 2.0));
+        let options = BooleanOptions::default();
+        assert_area(&BooleanOp::Union.apply(&a, &b, &options), 7.0);
+        assert_area(&BooleanOp::Intersection.apply(&a, &b, &options), 1.0);
+        assert_area(&BooleanOp::Difference.apply(&a, &b, &options), 3.0);
+        assert_area(&BooleanOp::Difference.apply(&b, &a, &options), 3.0);
+        let xor = BooleanOp::Xor.apply(&a, &b, &options);
+        assert_area(&xor, 6.0);
+        assert_eq!(subpaths(&xor), 2);
+        // Either input may wind either way.
+        let reversed = polygon(&[(1.0, 1.0), (1.0, 3.0), (3.0, 3.0), (3.0, 1.0)]);
+        assert_area(&BooleanOp::Union.apply(&a, &reversed, &options), 7.0);
+        assert_area(&BooleanOp::Difference.apply(&a, &reversed, &options), 3.0);
+    }
+
+    #[test]
+    fn holes_follow_the_fill_rule() {
+        let mut builder = Path::builder();
+        builder.add_rectangle(&Box2D::new(point(0.0, 0.0), point(4.0, 4.0)), Winding::Positive);
+        builder.add_rectangle(&Box2D::new(point(1.0, 1.0), point(3.0, 3.0)), Winding::Positive);
+        let nested = builder.build();
+        let mut builder = Path::builder();
+        builder.add_rectangle(&Box2D::new(point(0.0, 0.0), point(4.0, 4.0)), Winding::Positive);
+        builder.add_rectangle(&Box2D::new(point(1.0, 1.0), point(3.0, 3.0)), Winding::Negative);
+        let holed = builder.build();
+        let empty = Path::new();
+        let even_odd = BooleanOptions::default().with_fill_rules(FillRule::EvenOdd, FillRule::NonZero);
+        let non_zero = BooleanOptions::default();
+
+        let result = BooleanOp::Union.apply(&nested, &empty, &even_odd);
+        assert_area(&result, 12.0);
+        assert_eq!(subpaths(&result), 2);
+        let result = BooleanOp::Union.apply(&nested, &empty, &non_zero);
+        assert_area(&result, 16.0);
+        assert_eq!(subpaths(&result), 1);
+        assert_area(&BooleanOp::Union.apply(&holed, &empty, &non_zero), 12.0);
+        assert_area(&BooleanOp::Union.apply(&holed, &empty, &even_odd), 12.0);
+        // Filling the hole back in.
+        assert_area(&BooleanOp::Union.apply(&holed, &square((1.0, 1.0), 2.0), &non_zero), 16.0);
+        assert_area(&BooleanOp::Intersection.apply(&holed, &square((0.0, 0.0), 2.0), &non_zero), 3.0);
+    }
+
+    #[test]
+    fn resolves_a_self_intersecting_bow_tie() {
+        let bow_tie = polygon(&[(0.0, 0.0), (2.0, 2.0), (2.0, 0.0), (0.0, 2.0)]);
+        let empty = Path::new();
+        for fill_rule in [FillRule::NonZero, FillRule::EvenOdd] {
+            let options = BooleanOptions::default().with_fill_rules(fill_rule, fill_rule);
+            let result = BooleanOp::Union.apply(&bow_tie, &empty, &options);
+            assert_area(&result, 2.0);
+            assert_eq!(subpaths(&result), 2);
+            assert_area(&BooleanOp::Intersection.apply(&bow_tie, &square((0.0, 0.0), 1.0), &options), 0.5);
+            assert_area(&BooleanOp::Difference.apply(&square((0.0, 0.0), 2.0), &bow_tie, &options), 2.0);
+        }
+    }
+
+    #[test]
+    fn merges_shared_and_collinear_edges() {
+        let options = BooleanOptions::default();
+        let left = square((0.0, 0.0), 1.0);
+        let right = square((1.0, 0.0), 1.0);
+        let union = BooleanOp::Union.apply(&left, &right, &options);
+        assert_area(&union, 2.0);
+        assert_eq!(subpaths(&union), 1);
+        // The shared edge and the points on the straight runs are gone.
+        assert_eq!(union.iter().filter(|event| matches!(event, Event::Line { .. })).count(), 3);
+        assert_eq!(subpaths(&BooleanOp::Intersection.apply(&left, &right, &options)), 0);
+        assert_area(&BooleanOp::Difference.apply(&left, &right, &options), 1.0);
+
+        // Edges overlapping along part of their length.
+        let shifted = square((1.0, 0.5), 1.0);
+        let union = BooleanOp::Union.apply(&left, &shifted, &options);
+        assert_area(&union, 2.0);
+        assert_eq!(subpaths(&union), 1);
+        assert_area(&BooleanOp::Xor.apply(&left, &shifted, &options), 2.0);
+
+        // The very same square.
+        assert_area(&BooleanOp::Union.apply(&left, &left, &options), 1.0);
+        assert_eq!(subpaths(&BooleanOp::Xor.apply(&left, &left, &options)), 0);
+        assert_eq!(subpaths(&BooleanOp::Difference.apply(&left, &left, &options)), 0);
+    }
+
+    #[test]
+    fn refit_round_trips_circles() {
+        let (a, b) = (circle((0.0, 0.0), 10.0), circle((10.0, 0.0), 10.0));
+        let flat = BooleanOptions::default().with_tolerance(0.01);
+        let refit = flat.with_refit(0.02);
+        let lines = BooleanOp::Union.apply(&a, &b, &flat);
+        let curves = BooleanOp::Union.apply(&a, &b, &refit);
+        let cubics = curves.iter().filter(|event| matches!(event, Event::Cubic { .. })).count();
+        assert!(cubics > 0 && cubics < 24, "{} cubics", cubics);
+        assert!(curves.iter().count() * 4 < lines.iter().count());
+        assert!((area(&curves) - area(&lines)).abs() < area(&lines) * 1e-3);
+        // Everything stays on one of the circles, and the two corners where
+        // they cross are kept, to within how far the flattened circles
+        // cross from the true ones.
+        for event in curves.iter().flattened(0.01) {
+            if let Event::Line { to, .. } = event {
+                let off = (to.to_vector().length() - 10.0).abs().min(((to - point(10.0, 0.0)).length() - 10.0).abs());
+                assert!(off < 0.05, "{:?} is {} off", to, off);
+            }
+        }
+        let ends = curves
+            .iter()
+            .filter_map(|event| match event {
+                Event::Cubic { to, .. } | Event::Line { to, .. } => Some(to),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        for corner in [point(5.0, 75f32.sqrt()), point(5.0, -(75f32.sqrt()))] {
+            assert!(ends.iter().any(|end| (*end - corner).length() < flat.tolerance), "lost the corner at {:?}", corner);
+        }
+
+        // A lone circle comes back as a few cubics around the same area.
+        let single = BooleanOp::Union.apply(&a, &Path::new(), &refit);
+        let cubics = single.iter().filter(|event| matches!(event, Event::Cubic { .. })).count();
+        assert!((1..=12).contains(&cubics), "{} cubics", cubics);
+        assert!((area(&single) - std::f32::consts::PI * 100.0).abs() < 0.5);
+    }
+}
@@ -0,0 +1,317 @@
+use lyon::math::{point, vector, Angle, Box2D, Point, Transform, Vector};
+use crate::{PictureResolution, ViewInfo};
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// CAMERA
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// How the picture is scaled into the view before the camera's own zoom,
+/// when their aspect ratios differ.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FitMode {
+    /// The whole picture is visible, with bars along one axis.
+    Contain,
+    /// The picture covers the whole view, cropped along one axis.
+    Cover,
+    /// Each axis is scaled on its own, distorting the picture.
+    Stretch,
+    /// Logical points per picture unit, whatever the view size and pixel
+    /// ratio.
+    FixedScale(f32),
+}
+
+/// Which way picture y points on screen. With `Up`, the picture's origin is
+/// at its bottom left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum YAxis {
+    Down,
+    Up,
+}
+
+/// Maps the picture into the view: the picture's center, moved by `pan`,
+/// lands in the middle of the view, fitted by `fit`, rotated clockwise on
+/// screen by `rotation`, and scaled by `zoom`.
+///
+/// The camera only changes the view matrix in the globals uniform, so
+/// panning and zooming never re-tessellate. Lengths in `pan` are picture
+/// units; view coordinates are physical pixels from the view's top left.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub pan: Vector,
+    pub zoom: f32,
+    pub rotation: Angle,
+    pub fit: FitMode,
+    pub y_axis: YAxis,
+}
+
+impl Default for Camera {
+    /// Stretches the picture over the view, as without a camera.
+    fn default() -> Self {
+        Camera {
+            pan: Vector::zero(),
+            zoom: 1.0,
+            rotation: Angle::zero(),
+            fit: FitMode::Stretch,
+            y_axis: YAxis::Down,
+        }
+    }
+}
+
+impl Camera {
+    pub fn with_pan(mut self, pan: Vector) -> Self {
+        self.pan = pan;
+        self
+    }
+    pub fn with_zoom(mut self, zoom: f32) -> Self {
+        self.zoom = zoom;
+        self
+    }
+    pub fn with_rotation(mut self, rotation: Angle) -> Self {
+        self.rotation = rotation;
+        self
+    }
+    pub fn with_fit(mut self, fit: FitMode) -> Self {
+        self.fit = fit;
+        self
+    }
+    pub fn with_y_axis(mut self, y_axis: YAxis) -> Self {
+        self.y_axis = y_axis;
+        self
+    }
+    /// View pixels per picture unit along each picture axis, zoom included.
+    /// Rotation doesn't change it.
+    pub fn scale(&self, view: ViewInfo, picture: PictureResolution) -> [f32; 2] {
+        let view_resolution = view.view_resolution();
+        let scale_x = view_resolution.width() as f32 / picture.width();
+        let scale_y = view_resolution.height() as f32 / picture.height();
+        let [x, y] = match self.fit {
+            FitMode::Contain => [scale_x.min(scale_y); 2],
+            FitMode::Cover => [scale_x.max(scale_y); 2],
+            FitMode::Stretch => [scale_x, scale_y],
+            FitMode::FixedScale(scale) => [view.to_pixels(scale); 2],
+        };
+        [x * self.zoom, y * self.zoom]
+    }
+    /// Picture units to view pixels.
+    pub fn picture_to_view_transform(&self, view: ViewInfo, picture: PictureResolution) -> Transform {
+        let center = point(picture.width() / 2.0, picture.height() / 2.0) + self.pan;
+        let flip = match self.y_axis {
+            YAxis::Down => 1.0,
+            YAxis::Up => -1.0,
+        };
+        let [scale_x, scale_y] = self.scale(view, picture);
+        let view_resolution = view.view_resolution();
+        Transform::translation(-center.x, -center.y)
+            .then_scale(scale_x, scale_y * flip)
+            .then_rotate(self.rotation)
+            .then_translate(vector(view_resolution.width() as f32 / 2.0, view_resolution.height() as f32 / 2.0))
+    }
+    /// `None` when the camera collapses the picture, e.g. at zero zoom.
+    pub fn view_to_picture_transform(&self, view: ViewInfo, picture: PictureResolution) -> Option<Transform> {
+        self.picture_to_view_transform(view, picture).inverse()
+    }
+    pub fn picture_to_view(&self, position: Point, view: ViewInfo, picture: PictureResolution) -> Point {
+        self.picture_to_view_transform(view, picture).transform_point(position)
+    }
+    pub fn view_to_picture(&self, position: Point, view: ViewInfo, picture: PictureResolution) -> Option<Point> {
+        self.view_to_picture_transform(view, picture).map(|transform| transform.transform_point(position))
+    }
+    /// The view's corners in picture space, bounded.
+    pub fn visible_bounds(&self, view: ViewInfo, picture: PictureResolution) -> Option<Box2D> {
+        let view_resolution = view.view_resolution();
+        let view_box = Box2D::new(
+            point(0.0, 0.0),
+            point(view_resolution.width() as f32, view_resolution.height() as f32),
+        );
+        self.view_to_picture_transform(view, picture)
+            .map(|transform| transform.outer_transformed_box(&view_box))
+    }
+    /// Picture units to clip space, as the columns of a 4x4 matrix.
+    pub fn view_matrix(&self, view: ViewInfo, picture: PictureResolution) -> [[f32; 4]; 4] {
+        let view_resolution = view.view_resolution();
+        let t = self.picture_to_view_transform(view, picture)
+            .then_scale(2.0 / view_resolution.width() as f32, -2.0 / view_resolution.height() as f32)
+            .then_translate(vector(-1.0, 1.0));
+        [
+            [t.m11, t.m12, 0.0, 0.0],
+            [t.m21, t.m22, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [t.m31, t.m32, 0.0, 1.0],
+        ]
+    }
+    /// Picture units spanning `points` logical points on screen, for stroke
+    /// widths that should look the same at any zoom and on any display, e.g.
+    /// hairlines and selection outlines. Under `Stretch` the stroke is never
+    /// wider than `points` along either axis.
+    pub fn picture_length(&self, points: f32, view: ViewInfo, picture: PictureResolution) -> f32 {
+        let [scale_x, scale_y] = self.scale(view, picture);
+        view.to_pixels(points) / scale_x.max(scale_y)
+    }
+    /// Moves the picture by `delta` view pixels, e.g. for a drag.
+    pub fn pan_by(&mut self, delta: Vector, view: ViewInfo, picture: PictureResolution) {
+        if let Some(transform) = self.view_to_picture_transform(view, picture) {
+            self.pan -= transform.transform_vector(delta);
+        }
+    }
+    /// Zooms by `factor`, keeping the picture point under `anchor` (in view
+    /// pixels) in place, e.g. under the cursor.
+    pub fn zoom_about(&mut self, anchor: Point, factor: f32, view: ViewInfo, picture: PictureResolution) {
+        let before = self.view_to_picture(anchor, view, picture);
+        self.zoom *= factor;
+        let after = self.view_to_picture(anchor, view, picture);
+        if let (Some(before), Some(after)) = (before, after) {
+            self.pan += before - after;
+        }
+    }
+}
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// TESTS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::LogicalSize;
+
+    /// 800 by 600 pixels, at twice the logical size.
+    fn view() -> ViewInfo {
+        ViewInfo::new(LogicalSize::new(400.0, 300.0), 2.0)
+    }
+
+    /// Scaled 4 times to fit the view's width, and 6 times its height.
+    fn picture() -> PictureResolution {
+        PictureResolution::new(200.0, 100.0)
+    }
+
+    fn assert_near(actual: Point, expected: Point) {
+        assert!((actual - expected).length() < 1e-3, "{:?} != {:?}", actual, expected);
+    }
+
+    fn cameras() -> Vec<Camera> {
+        let mut cameras = Vec::new();
+        for y_axis in [YAxis::Down, YAxis::Up] {
+            for degrees in [0.0, 30.0, 90.0, 200.0] {
+                cameras.push(
+                    Camera::default()
+                        .with_fit(FitMode::Contain)
+                        .with_zoom(1.5)
+                        .with_pan(vector(10.0, -5.0))
+                        .with_rotation(Angle::degrees(degrees))
+                        .with_y_axis(y_axis),
+                );
+            }
+        }
+        cameras
+    }
+
+    #[test]
+    fn picture_and_view_round_trip() {
+        let points = [point(0.0, 0.0), point(110.0, 45.0), point(200.0, 100.0), point(-30.0, 250.0)];
+        for camera in cameras() {
+            // The panned center lands in the middle of the view.
+            assert_near(camera.picture_to_view(point(110.0, 45.0), view(), picture()), point(400.0, 300.0));
+            for position in points {
+                let there = camera.picture_to_view(position, view(), picture());
+                assert_near(camera.view_to_picture(there, view(), picture()).unwrap(), position);
+            }
+        }
+    }
+
+    #[test]
+    fn rotation_is_clockwise_on_screen_and_up_flips_y() {
+        let camera = Camera::default().with_fit(FitMode::Contain);
+        let center = point(100.0, 50.0);
+        // Picture +y is down on screen, or up with `YAxis::Up`.
+        assert_near(camera.picture_to_view(center + vector(0.0, 10.0), view(), picture()), point(400.0, 340.0));
+        let up = camera.with_y_axis(YAxis::Up);
+        assert_near(up.picture_to_view(center + vector(0.0, 10.0), view(), picture()), point(400.0, 260.0));
+        // A quarter turn takes +x to down on screen.
+        let turned = camera.with_rotation(Angle::frac_pi_2());
+        assert_near(turned.picture_to_view(center + vector(10.0, 0.0), view(), picture()), point(400.0, 340.0));
+    }
+
+    #[test]
+    fn zoom_about_keeps_the_anchor_in_place() {
+        let anchor = point(650.0, 120.0);
+        for mut camera in cameras() {
+            let under = camera.view_to_picture(anchor, view(), picture()).unwrap();
+            camera.zoom_about(anchor, 2.5, view(), picture());
+            assert_eq!(camera.zoom, 1.5 * 2.5);
+            assert_near(camera.picture_to_view(under, view(), picture()), anchor);
+            assert_near(camera.view_to_picture(anchor, view(), picture()).unwrap(), under);
+        }
+    }
+
+    #[test]
+    fn pan_by_drags_the_picture_with_the_pointer() {
+        let grabbed = point(300.0, 400.0);
+        let delta = vector(-35.0, 12.0);
+        for mut camera in cameras() {
+            let under = camera.view_to_picture(grabbed, view(), picture()).unwrap();
+            camera.pan_by(delta, view(), picture());
+            assert_near(camera.picture_to_view(under, view(), picture()), grabbed + delta);
+        }
+        // Nothing to drag when the picture is collapsed.
+        let mut collapsed = Camera::default().with_zoom(0.0);
+        collapsed.pan_by(delta, view(), picture());
+        assert_eq!(collapsed.pan, Vector::zero());
+    }
+
+    #[test]
+    fn each_fit_mode_scales_the_picture() {
+        let scale = |fit: FitMode| Camera::default().with_fit(fit).scale(view(), picture());
+        assert_eq!(scale(FitMode::Contain), [4.0, 4.0]);
+        assert_eq!(scale(FitMode::Cover), [6.0, 6.0]);
+        assert_eq!(scale(FitMode::Stretch), [4.0, 6.0]);
+        // Logical points, so doubled in pixels.
+        assert_eq!(scale(FitMode::FixedScale(3.0)), [6.0, 6.0]);
+        assert_eq!(Camera::default().with_fit(FitMode::Cover).with_zoom(0.5).scale(view(), picture()), [3.0, 3.0]);
+        // Two logical points are four pixels, or one picture unit at a scale
+        // of four, and never more than two points along either axis.
+        assert_eq!(Camera::default().with_fit(FitMode::Contain).picture_length(2.0, view(), picture()), 1.0);
+        assert_eq!(Camera::default().picture_length(3.0, view(), picture()), 1.0);
+
+        let visible = |fit: FitMode| Camera::default().with_fit(fit).visible_bounds(view(), picture()).unwrap();
+        // Bars above and below, cropped at the sides, and exactly the picture.
+        let contain = visible(FitMode::Contain);
+        assert_near(contain.min, point(0.0, -25.0));
+        assert_near(contain.max, point(200.0, 125.0));
+        let cover = visible(FitMode::Cover);
+        assert_near(cover.min, point(100.0 - 800.0 / 12.0, 0.0));
+        assert_near(cover.max, point(100.0 + 800.0 / 12.0, 100.0));
+        let stretch = visible(FitMode::Stretch);
+        assert_near(stretch.min, point(0.0, 0.0));
+        assert_near(stretch.max, point(200.0, 100.0));
+    }
+
+    #[test]
+    fn visible_bounds_turn_with_the_camera() {
+        // A quarter turn shows 600 by 800 pixels of picture, at a scale of 4.
+        let camera = Camera::default().with_fit(FitMode::Contain).with_rotation(Angle::frac_pi_2());
+        let bounds = camera.visible_bounds(view(), picture()).unwrap();
+        assert_near(bounds.min, point(25.0, -50.0));
+        assert_near(bounds.max, point(175.0, 150.0));
+        // An eighth turn bounds the tilted view.
+        let camera = camera.with_rotation(Angle::frac_pi_4());
+        let bounds = camera.visible_bounds(view(), picture()).unwrap();
+        let half = (800.0 + 600.0) / 4.0 * std::f32::consts::FRAC_1_SQRT_2 / 2.0;
+        assert_near(bounds.min, point(100.0 - half, 50.0 - half));
+        assert_near(bounds.max, point(100.0 + half, 50.0 + half));
+        assert!(Camera::default().with_zoom(0.0).visible_bounds(view(), picture()).is_none());
+    }
+
+    #[test]
+    fn view_matrix_maps_the_view_to_clip_space() {
+        for camera in cameras() {
+            let m = camera.view_matrix(view(), picture());
+            let clip = |view_position: Point| {
+                let p = camera.view_to_picture(view_position, view(), picture()).unwrap();
+                point(m[0][0] * p.x + m[1][0] * p.y + m[3][0], m[0][1] * p.x + m[1][1] * p.y + m[3][1])
+            };
+            assert_near(clip(point(0.0, 0.0)), point(-1.0, 1.0));
+            assert_near(clip(point(800.0, 600.0)), point(1.0, -1.0));
+            assert_near(clip(point(400.0, 300.0)), point(0.0, 0.0));
+        }
+    }
+}
@@ -0,0 +1,668 @@
+use std::str::FromStr;
+use crate::data::RGBA;
+use crate::frontend::ColorScheme;
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// TRANSFER FUNCTIONS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// Decodes an sRGB channel, from `0.0` to `1.0`, to linear light.
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes a linear-light channel, from `0.0` to `1.0`, as sRGB.
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn channel_to_u8(value: f32) -> u8 {
+    if value.is_finite() {
+        (value.clamp(0.0, 1.0) * 255.0).round() as u8
+    } else {
+        0
+    }
+}
+
+fn clamp_alpha(alpha: f32) -> f32 {
+    if alpha.is_finite() { alpha.clamp(0.0, 1.0) } else { 1.0 }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// LINEAR LIGHT
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// `RGBA<u8>` holds sRGB encoded channels with straight alpha, as written in
+/// CSS. Everything drawn is blended in linear light, so colors are decoded,
+/// then premultiplied, on their way to the GPU.
+impl RGBA<u8> {
+    /// Linear-light channels from `0.0` to `1.0`, with straight alpha.
+    pub fn to_linear(self) -> RGBA<f32> {
+        let RGBA { red, green, blue, alpha } = self;
+        let decode = |channel: u8| srgb_to_linear(channel as f32 / 255.0);
+        RGBA { red: decode(red), green: decode(green), blue: decode(blue), alpha }
+    }
+    pub fn from_linear(color: RGBA<f32>) -> RGBA<u8> {
+        let RGBA { red, green, blue, alpha } = color;
+        let encode = |channel: f32| channel_to_u8(linear_to_srgb(channel.clamp(0.0, 1.0)));
+        RGBA { red: encode(red), green: encode(green), blue: encode(blue), alpha: clamp_alpha(alpha) }
+    }
+    /// Linear and premultiplied, the way the pipelines blend it.
+    pub fn to_premultiplied(self) -> [f32; 4] {
+        self.to_linear().premultiplied()
+    }
+}
+
+impl RGBA<f32> {
+    pub fn premultiplied(self) -> [f32; 4] {
+        let RGBA { red, green, blue, alpha } = self;
+        let alpha = clamp_alpha(alpha);
+        [red * alpha, green * alpha, blue * alpha, alpha]
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// CYLINDRICAL COLOR SPACES
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// Hues are in degrees, from `0.0` to `360.0`. Other components run from
+/// `0.0` to `1.0`. Grays have a hue of zero.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hsla {
+    pub hue: f32,
+    pub saturation: f32,
+    pub lightness: f32,
+    pub alpha: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hsva {
+    pub hue: f32,
+    pub saturation: f32,
+    pub value: f32,
+    pub alpha: f32,
+}
+
+/// OKLCH, the polar form of Björn Ottosson's perceptual Oklab space.
+/// `lightness` runs from `0.0` to `1.0`, and `chroma` from zero up to about
+/// `0.37` within sRGB.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Oklch {
+    pub lightness: f32,
+    pub chroma: f32,
+    pub hue: f32,
+    pub alpha: f32,
+}
+
+/// The hue, the largest and the smallest channel of an encoded color.
+fn hue_max_min(color: RGBA<u8>) -> (f32, f32, f32) {
+    let [red, green, blue] = [color.red, color.green, color.blue].map(|channel| channel as f32 / 255.0);
+    let max = red.max(green).max(blue);
+    let min = red.min(green).min(blue);
+    let delta = max - min;
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == red {
+        60.0 * ((green - blue) / delta).rem_euclid(6.0)
+    } else if max == green {
+        60.0 * ((blue - red) / delta + 2.0)
+    } else {
+        60.0 * ((red - green) / delta + 4.0)
+    };
+    (hue, max, min)
+}
+
+/// An encoded color from a hue and the chroma-free parts of HSV: the value
+/// and the chroma itself.
+fn from_hue_chroma(hue: f32, chroma: f32, value: f32, alpha: f32) -> RGBA<u8> {
+    let hue = if hue.is_finite() { hue.rem_euclid(360.0) } else { 0.0 };
+    let channel = |n: f32| {
+        let k = (n + hue / 60.0) % 6.0;
+        value - chroma * k.min(4.0 - k).clamp(0.0, 1.0)
+    };
+    RGBA {
+        red: channel_to_u8(channel(5.0)),
+        green: channel_to_u8(channel(3.0)),
+        blue: channel_to_u8(channel(1.0)),
+        alpha: clamp_alpha(alpha),
+    }
+}
+
+impl From<RGBA<u8>> for Hsla {
+    fn from(color: RGBA<u8>) -> Self {
+        let (hue, max, min) = hue_max_min(color);
+        let lightness = (max + min) / 2.0;
+        let saturation = if max == min {
+            0.0
+        } else {
+            (max - min) / (1.0 - (2.0 * lightness - 1.0).abs())
+        };
+        Hsla { hue, saturation, lightness, alpha: color.alpha }
+    }
+}
+
+impl From<Hsla> for RGBA<u8> {
+    fn from(Hsla { hue, saturation, lightness, alpha }: Hsla) -> Self {
+        let (saturation, lightness) = (saturation.clamp(0.0, 1.0), lightness.clamp(0.0, 1.0));
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+        from_hue_chroma(hue, chroma, lightness + chroma / 2.0, alpha)
+    }
+}
+
+impl From<RGBA<u8>> for Hsva {
+    fn from(color: RGBA<u8>) -> Self {
+        let (hue, max, min) = hue_max_min(color);
+        let saturation = if max == 0.0 { 0.0 } else { (max - min) / max };
+        Hsva { hue, saturation, value: max, alpha: color.alpha }
+    }
+}
+
+impl From<Hsva> for RGBA<u8> {
+    fn from(Hsva { hue, saturation, value, alpha }: Hsva) -> Self {
+        let (saturation, value) = (saturation.clamp(0.0, 1.0), value.clamp(0.0, 1.0));
+        from_hue_chroma(hue, value * saturation, value, alpha)
+    }
+}
+
+impl From<RGBA<u8>> for Oklch {
+    // Ottosson's published matrices, kept digit for digit.
+    #[allow(clippy::excessive_precision)]
+    fn from(color: RGBA<u8>) -> Self {
+        let RGBA { red, green, blue, alpha } = color.to_linear();
+        let l = (0.4122214708 * red + 0.5363325363 * green + 0.0514459929 * blue).cbrt();
+        let m = (0.2119034982 * red + 0.6806995451 * green + 0.1073969566 * blue).cbrt();
+        let s = (0.0883024619 * red + 0.2817188376 * green + 0.6299787005 * blue).cbrt();
+        let lightness = 0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s;
+        let a = 1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s;
+        let b = 0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s;
+        let chroma = a.hypot(b);
+        // Grays come out with a tiny chroma and a meaningless hue.
+        let hue = if chroma < 1e-4 { 0.0 } else { b.atan2(a).to_degrees().rem_euclid(360.0) };
+        Oklch { lightness, chroma, hue, alpha }
+    }
+}
+
+impl From<Oklch> for RGBA<u8> {
+    /// Colors outside sRGB keep their lightness and hue, and lose chroma
+    /// until they fit.
+    #[allow(clippy::excessive_precision)]
+    fn from(color: Oklch) -> Self {
+        let to_linear = |chroma: f32| {
+            let hue = color.hue.to_radians();
+            let (a, b) = (chroma * hue.cos(), chroma * hue.sin());
+            let lightness = color.lightness.clamp(0.0, 1.0);
+            let l = (lightness + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+            let m = (lightness - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+            let s = (lightness - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+            RGBA {
+                red: 4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+                green: -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+                blue: -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+                alpha: color.alpha,
+            }
+        };
+        let in_gamut = |color: &RGBA<f32>| {
+            [color.red, color.green, color.blue].iter().all(|channel| (-1e-4..=1.0 + 1e-4).contains(channel))
+        };
+        let chroma = if color.chroma.is_finite() { color.chroma.max(0.0) } else { 0.0 };
+        let mut linear = to_linear(chroma);
+        if !in_gamut(&linear) {
+            let (mut low, mut high) = (0.0, chroma);
+            for _ in 0..16 {
+                let middle = (low + high) / 2.0;
+                if in_gamut(&to_linear(middle)) { low = middle } else { high = middle }
+            }
+            linear = to_linear(low);
+        }
+        RGBA::from_linear(linear)
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// PARSING
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColorParseError {
+    pub input: String,
+}
+
+impl std::fmt::Display for ColorParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid CSS color: {:?}", self.input)
+    }
+}
+
+impl std::error::Error for ColorParseError {}
+
+/// Parses CSS colors: `#rgb`, `#rgba`, `#rrggbb` and `#rrggbbaa`, named
+/// colors, and `rgb()`, `rgba()`, `hsl()` and `hsla()` in both the comma and
+/// the space separated syntax.
+impl FromStr for RGBA<u8> {
+    type Err = ColorParseError;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let error = || ColorParseError { input: input.to_owned() };
+        let text = input.trim().to_ascii_lowercase();
+        if let Some(hex) = text.strip_prefix('#') {
+            return parse_hex(hex).ok_or_else(error)
+        }
+        if let Some((name, rest)) = text.split_once('(') {
+            let arguments = rest.strip_suffix(')').ok_or_else(error)?;
+            return parse_function(name.trim(), arguments).ok_or_else(error)
+        }
+        named_color(&text).ok_or_else(error)
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<RGBA<u8>> {
+    if !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None
+    }
+    let digit = |ix: usize| u8::from_str_radix(&hex[ix..ix + 1], 16).ok().map(|value| value * 17);
+    let pair = |ix: usize| u8::from_str_radix(&hex[ix..ix + 2], 16).ok();
+    let (red, green, blue, alpha) = match hex.len() {
+        3 => (digit(0)?, digit(1)?, digit(2)?, 255),
+        4 => (digit(0)?, digit(1)?, digit(2)?, digit(3)?),
+        6 => (pair(0)?, pair(2)?, pair(4)?, 255),
+        8 => (pair(0)?, pair(2)?, pair(4)?, pair(6)?),
+        _ => return None,
+    };
+    Some(RGBA { red, green, blue, alpha: alpha as f32 / 255.0 })
+}
+
+/// `arguments` is what's between the parentheses.
+fn parse_function(name: &str, arguments: &str) -> Option<RGBA<u8>> {
+    let (channels, alpha) = match arguments.split_once('/') {
+        Some((channels, alpha)) => (channels, Some(alpha.trim())),
+        None => (arguments, None),
+    };
+    let mut components = if channels.contains(',') {
+        channels.split(',').map(str::trim).collect::<Vec<_>>()
+    } else {
+        channels.split_whitespace().collect::<Vec<_>>()
+    };
+    // The legacy syntax passes alpha as a fourth argument.
+    let alpha = match (alpha, components.len()) {
+        (Some(alpha), 3) => alpha,
+        (None, 4) => components.pop()?,
+        (None, 3) => "1",
+        _ => return None,
+    };
+    let alpha = parse_alpha(alpha)?;
+    match name {
+        "rgb" | "rgba" => {
+            let channel = |text: &str| match text.strip_suffix('%') {
+                Some(percent) => percent.parse::<f32>().ok().map(|value| value / 100.0),
+                None => text.parse::<f32>().ok().map(|value| value / 255.0),
+            };
+            Some(RGBA {
+                red: channel_to_u8(channel(components[0])?),
+                green: channel_to_u8(channel(components[1])?),
+                blue: channel_to_u8(channel(components[2])?),
+                alpha,
+            })
+        }
+        "hsl" | "hsla" => {
+            let percent = |text: &str| {
+                let text = text.strip_suffix('%').unwrap_or(text);
+                text.parse::<f32>().ok().map(|value| value / 100.0)
+            };
+            Some(Hsla {
+                hue: parse_hue(components[0])?,
+                saturation: percent(components[1])?,
+                lightness: percent(components[2])?,
+                alpha,
+            }.into())
+        }
+        _ => None,
+    }
+}
+
+fn parse_alpha(text: &str) -> Option<f32> {
+    let alpha = match text.strip_suffix('%') {
+        Some(percent) => percent.parse::<f32>().ok()? / 100.0,
+        None => text.parse::<f32>().ok()?,
+    };
+    Some(clamp_alpha(alpha))
+}
+
+/// In degrees, from a bare number or an angle with a CSS unit.
+fn parse_hue(text: &str) -> Option<f32> {
+    let units = [("deg", 1.0), ("grad", 0.9), ("rad", 180.0 / std::f32::consts::PI), ("turn", 360.0)];
+    for (unit, degrees) in units {
+        if let Some(value) = text.strip_suffix(unit) {
+            return value.parse::<f32>().ok().map(|value| value * degrees)
+        }
+    }
+    text.parse::<f32>().ok()
+}
+
+/// The CSS named colors, and `transparent`.
+pub fn named_color(name: &str) -> Option<RGBA<u8>> {
+    if name == "transparent" {
+        return Some(RGBA::new(0, 0, 0, 0.0))
+    }
+    NAMED_COLORS
+        .binary_search_by(|(entry, _)| entry.cmp(&name))
+        .ok()
+        .map(|ix| {
+            let value = NAMED_COLORS[ix].1;
+            RGBA::new_((value >> 16) as u8, (value >> 8) as u8, value as u8)
+        })
+}
+
+/// Sorted by name, for binary search.
+const NAMED_COLORS: [(&str, u32); 148] = [
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// THEME COLORS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// Semantic colors, given by the active `ColorScheme`'s palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorToken {
+    Foreground,
+    Background,
+    Accent,
+    Grid,
+}
+
+/// The color of an op: either fixed, or a token that's resolved when the op
+/// is tessellated, and again whenever the scheme changes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThemeColor {
+    Fixed(RGBA<u8>),
+    Token(ColorToken),
+}
+
+impl ThemeColor {
+    pub fn resolve(&self, color_scheme: ColorScheme) -> RGBA<u8> {
+        match self {
+            ThemeColor::Fixed(color) => *color,
+            ThemeColor::Token(token) => color_scheme.palette().get(*token),
+        }
+    }
+    pub fn is_token(&self) -> bool {
+        matches!(self, ThemeColor::Token(_))
+    }
+}
+
+impl From<RGBA<u8>> for ThemeColor {
+    fn from(color: RGBA<u8>) -> Self { ThemeColor::Fixed(color) }
+}
+
+impl From<ColorToken> for ThemeColor {
+    fn from(token: ColorToken) -> Self { ThemeColor::Token(token) }
+}
+
+/// What each token resolves to under one scheme. The background is also the
+/// clear color, and should be opaque.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub foreground: RGBA<u8>,
+    pub background: RGBA<u8>,
+    pub accent: RGBA<u8>,
+    pub grid: RGBA<u8>,
+}
+
+impl Palette {
+    pub const LIGHT: Palette = Palette {
+        foreground: RGBA::new_(0x1c, 0x1c, 0x1e),
+        background: RGBA::WHITE,
+        accent: RGBA::new_(0x00, 0x7a, 0xff),
+        grid: RGBA::new_(0xd1, 0xd1, 0xd6),
+    };
+    pub const DARK: Palette = Palette {
+        foreground: RGBA::new_(0xf2, 0xf2, 0xf7),
+        background: RGBA::new_(0x1c, 0x1c, 0x1e),
+        accent: RGBA::new_(0x0a, 0x84, 0xff),
+        grid: RGBA::new_(0x3a, 0x3a, 0x3c),
+    };
+    pub fn get(&self, token: ColorToken) -> RGBA<u8> {
+        match token {
+            ColorToken::Foreground => self.foreground,
+            ColorToken::Background => self.background,
+            ColorToken::Accent => self.accent,
+            ColorToken::Grid => self.grid,
+        }
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// TESTS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> RGBA<u8> {
+        input.parse().unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    fn assert_color(input: &str, (red, green, blue): (u8, u8, u8), alpha: f32) {
+        let color = parse(input);
+        assert_eq!((color.red, color.green, color.blue), (red, green, blue), "{}", input);
+        assert!((color.alpha - alpha).abs() < 1e-3, "{}: alpha {}", input, color.alpha);
+    }
+
+    #[test]
+    fn parses_hex_of_each_length() {
+        assert_color("#f80", (0xff, 0x88, 0x00), 1.0);
+        assert_color("#f808", (0xff, 0x88, 0x00), 0x88 as f32 / 255.0);
+        assert_color("#1a2B3c", (0x1a, 0x2b, 0x3c), 1.0);
+        assert_color("  #1a2b3c80 ", (0x1a, 0x2b, 0x3c), 0x80 as f32 / 255.0);
+    }
+
+    #[test]
+    fn parses_named_colors() {
+        assert_color("rebeccapurple", (0x66, 0x33, 0x99), 1.0);
+        assert_color("AliceBlue", (0xf0, 0xf8, 0xff), 1.0);
+        assert_color("yellowgreen", (0x9a, 0xcd, 0x32), 1.0);
+        assert_color("transparent", (0, 0, 0), 0.0);
+        assert!(NAMED_COLORS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn parses_legacy_and_space_rgb() {
+        assert_color("rgb(255, 128, 0)", (255, 128, 0), 1.0);
+        assert_color("rgba(255, 128, 0, 0.25)", (255, 128, 0), 0.25);
+        assert_color("rgb(100%, 50%, 0%)", (255, 128, 0), 1.0);
+        assert_color("rgb(255 128 0)", (255, 128, 0), 1.0);
+        assert_color("rgb(255 128 0 / 40%)", (255, 128, 0), 0.4);
+        assert_color("rgba(255 128 0 / 0.4)", (255, 128, 0), 0.4);
+    }
+
+    #[test]
+    fn parses_legacy_and_space_hsl() {
+        assert_color("hsl(120, 100%, 25%)", (0, 128, 0), 1.0);
+        assert_color("hsla(240, 100%, 50%, 0.5)", (0, 0, 255), 0.5);
+        assert_color("hsl(0.5turn 100% 50%)", (0, 255, 255), 1.0);
+        assert_color("hsl(0deg 0% 100% / 10%)", (255, 255, 255), 0.1);
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        let invalid = [
+            "", "#", "#12", "#12345", "#1234567", "#ggg", "#ééé",
+            "notacolor", "rgb(1, 2)", "rgb(1, 2, 3, 4, 5)", "rgb(1 2 3 / 4 / 5)",
+            "rgb(1, 2, 3", "rgb(a, b, c)", "hsl(red, 10%, 10%)", "cmyk(1, 2, 3)",
+        ];
+        for input in invalid {
+            let error = input.parse::<RGBA<u8>>().expect_err(input);
+            assert_eq!(error.input, input);
+        }
+    }
+
+    #[test]
+    fn oklch_round_trips_srgb() {
+        for color in [RGBA::RED, RGBA::GREEN, RGBA::BLUE, RGBA::PINK, RGBA::GREY] {
+            let back = RGBA::<u8>::from(Oklch::from(color));
+            let channels = |color: RGBA<u8>| [color.red as i32, color.green as i32, color.blue as i32];
+            let deltas = channels(back).iter().zip(channels(color)).map(|(a, b)| (a - b).abs()).max();
+            assert!(deltas.unwrap() <= 1, "{:?} came back as {:?}", color, back);
+        }
+    }
+}
@@ -0,0 +1,466 @@
+use std::ops::Range;
+
+use lyon::math::{Box2D, Point, Transform};
+
+use crate::data::gpu_types::GpuInstance;
+use crate::data::picture::{self, DrawNode, MeshBuffer};
+use crate::data::spatial::{self, RTree};
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// CULL INDEX
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// Picture space bounds of what the top-level batches draw, for drawing only
+/// what the camera can see: one span per op within each plain batch, and one
+/// per instance of each instanced batch.
+///
+/// Groups aren't split up; the group pass already skips groups that are off
+/// screen, and their filters may pull in content from outside the view.
+///
+/// Spans before a change are kept, so appending, e.g. a live stroke, only
+/// measures the new geometry. Spans past the last rebuild of the R-tree are
+/// scanned one by one until there are enough of them to rebuild it.
+#[derive(Debug, Clone)]
+pub struct CullIndex {
+    spans: Vec<(Box2D, CullSpan)>,
+    /// Indexes `spans[..indexed]`.
+    tree: RTree<usize>,
+    indexed: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CullSpan {
+    Indices(Range<u32>),
+    Instances(Range<u32>),
+}
+
+/// What to draw this frame: sorted, disjoint ranges of the index and
+/// instance buffers, with adjacent ranges merged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VisibleRanges {
+    pub indices: Vec<Range<u32>>,
+    pub instances: Vec<Range<u32>>,
+}
+
+impl Default for CullIndex {
+    fn default() -> Self {
+        CullIndex { spans: Vec::new(), tree: RTree::new([]), indexed: 0 }
+    }
+}
+
+impl CullIndex {
+    /// Spans left out of the R-tree before it's rebuilt, or a quarter of the
+    /// indexed ones if that's more.
+    pub const MAX_UNINDEXED: usize = 256;
+
+    pub fn new() -> Self {
+        CullIndex::default()
+    }
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+    /// Re-measures everything drawn from index `indices_from` and instance
+    /// `instances_from` on. Everything before them must be unchanged.
+    pub fn update(
+        &mut self,
+        mesh: &MeshBuffer,
+        instances: &[GpuInstance],
+        nodes: &[DrawNode],
+        op_primitives: &[Range<u32>],
+        indices_from: u32,
+        instances_from: u32,
+    ) {
+        let kept = self.spans
+            .iter()
+            .position(|(_, span)| match span {
+                CullSpan::Indices(range) => range.end > indices_from,
+                CullSpan::Instances(range) => range.end > instances_from,
+            })
+            .unwrap_or(self.spans.len());
+        self.spans.truncate(kept);
+        let resume_index = self.spans
+            .iter()
+            .rev()
+            .find_map(|(_, span)| match span {
+                CullSpan::Indices(range) => Some(range.end),
+                CullSpan::Instances(_) => None,
+            })
+            .unwrap_or(0);
+        let resume_instance = self.spans
+            .iter()
+            .rev()
+            .find_map(|(_, span)| match span {
+                CullSpan::Instances(range) => Some(range.end),
+                CullSpan::Indices(_) => None,
+            })
+            .unwrap_or(0);
+        for node in nodes {
+            let batch = match node {
+                DrawNode::Batch(batch) => batch,
+                DrawNode::Group(_) => continue,
+            };
+            match batch.instances.as_ref() {
+                None => {
+                    let start = batch.indices.start.max(resume_index);
+                    if start < batch.indices.end {
+                        self.push_op_spans(mesh, op_primitives, start..batch.indices.end);
+                    }
+                }
+                Some(batch_instances) => {
+                    let start = batch_instances.start.max(resume_instance);
+                    if start >= batch_instances.end {
+                        continue
+                    }
+                    let symbol_bounds = match mesh_bounds(mesh, batch.indices.clone()) {
+                        Some(bounds) => bounds,
+                        None => continue,
+                    };
+                    for ix in start..batch_instances.end {
+                        let [x, y, offset] = instances[ix as usize].transform;
+                        let bounds = Transform::new(x[0], x[1], y[0], y[1], offset[0], offset[1])
+                            .outer_transformed_box(&symbol_bounds);
+                        self.spans.push((bounds, CullSpan::Instances(ix..ix + 1)));
+                    }
+                }
+            }
+        }
+        let stale = kept < self.indexed;
+        if stale || self.spans.len() - self.indexed > CullIndex::MAX_UNINDEXED.max(self.indexed / 4) {
+            self.tree = RTree::new(self.spans.iter().enumerate().map(|(ix, (bounds, _))| (*bounds, ix)));
+            self.indexed = self.spans.len();
+        }
+    }
+    /// Everything whose bounds overlap `region`, e.g. `Camera::visible_bounds`.
+    pub fn visible(&self, region: &Box2D) -> VisibleRanges {
+        let mut indices = Vec::new();
+        let mut instances = Vec::new();
+        let mut collect = |span: &CullSpan| match span {
+            CullSpan::Indices(range) => indices.push(range.clone()),
+            CullSpan::Instances(range) => instances.push(range.clone()),
+        };
+        self.tree.query(region, |_, ix| collect(&self.spans[*ix].1));
+        for (bounds, span) in self.spans[self.indexed..].iter() {
+            if spatial::overlaps(bounds, region) {
+                collect(span);
+            }
+        }
+        VisibleRanges { indices: merge(indices), instances: merge(instances) }
+    }
+    /// Splits a plain batch's indices wherever the op changes, and pushes a
+    /// span for each op.
+    fn push_op_spans(&mut self, mesh: &MeshBuffer, op_primitives: &[Range<u32>], indices: Range<u32>) {
+        let prim_id = |ix: u32| mesh.vertices[mesh.indices[ix as usize] as usize].prim_id;
+        // The primitives of the op being measured. Primitives of no op count
+        // as ops of their own.
+        let op_range = |prim_id: u32| match picture::op_for_primitive(op_primitives, prim_id) {
+            Some(op) => op_primitives[op].clone(),
+            None => prim_id..prim_id + 1,
+        };
+        let mut start = indices.start;
+        let mut op = op_range(prim_id(start));
+        for triangle in indices.clone().step_by(3) {
+            let prim_id = prim_id(triangle);
+            if op.contains(&prim_id) {
+                continue
+            }
+            if let Some(bounds) = mesh_bounds(mesh, start..triangle) {
+                self.spans.push((bounds, CullSpan::Indices(start..triangle)));
+            }
+            start = triangle;
+            op = op_range(prim_id);
+        }
+        if let Some(bounds) = mesh_bounds(mesh, start..indices.end) {
+            self.spans.push((bounds, CullSpan::Indices(start..indices.end)));
+        }
+    }
+}
+
+impl VisibleRanges {
+    /// The visible parts of a batch's index range.
+    pub fn clip_indices(&self, range: Range<u32>) -> impl Iterator<Item = Range<u32>> + '_ {
+        clip(&self.indices, range)
+    }
+    /// The visible parts of an instanced batch's instance range.
+    pub fn clip_instances(&self, range: Range<u32>) -> impl Iterator<Item = Range<u32>> + '_ {
+        clip(&self.instances, range)
+    }
+}
+
+fn mesh_bounds(mesh: &MeshBuffer, indices: Range<u32>) -> Option<Box2D> {
+    mesh.indices[indices.start as usize..indices.end as usize]
+        .iter()
+        .map(|ix| Point::from(mesh.vertices[*ix as usize].position))
+        .map(|point| Box2D::new(point, point))
+        .reduce(|a, b| spatial::union(&a, &b))
+}
+
+/// Sorts the ranges and joins the ones that touch or overlap.
+fn merge(mut ranges: Vec<Range<u32>>) -> Vec<Range<u32>> {
+    ranges.sort_unstable_by_key(|range| range.start);
+    let mut merged: Vec<Range<u32>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+fn clip(ranges: &[Range<u32>], range: Range<u32>) -> impl Iterator<Item = Range<u32>> + '_ {
+    let first = ranges.partition_point(|visible| visible.end <= range.start);
+    ranges[first..]
+        .iter()
+        .take_while(move |visible| visible.start < range.end)
+        .map(move |visible| visible.start.max(range.start)..visible.end.min(range.end))
+}
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// TESTS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+#[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)] // Lists of one range are meant.
+mod tests {
+    use super::*;
+    use lyon::math::{point, vector, Angle};
+    use crate::data::gpu_types::GpuVertex;
+    use crate::data::picture::DrawBatch;
+    use crate::data::draw_cmds::BlendMode;
+
+    /// Two triangles covering a square.
+    fn quad(mesh: &mut MeshBuffer, min: (f32, f32), size: f32, prim_id: u32) {
+        let first = mesh.vertices.len() as u32;
+        for (x, y) in [(0.0, 0.0), (size, 0.0), (size, size), (0.0, size)] {
+            mesh.vertices.push(GpuVertex { position: [min.0 + x, min.1 + y], prim_id });
+        }
+        mesh.indices.extend([0, 1, 2, 0, 2, 3].map(|ix| first + ix));
+    }
+
+    fn instance(transform: Transform) -> GpuInstance {
+        GpuInstance {
+            transform: [[transform.m11, transform.m12], [transform.m21, transform.m22], [transform.m31, transform.m32]],
+            color: [0.0; 4],
+            prim_id: 0,
+        }
+    }
+
+    fn batch(indices: Range<u32>, instances: Option<Range<u32>>) -> DrawNode {
+        DrawNode::Batch(DrawBatch { blend_mode: BlendMode::SourceOver, indices, instances })
+    }
+
+    fn region(min: (f32, f32), max: (f32, f32)) -> Box2D {
+        Box2D::new(point(min.0, min.1), point(max.0, max.1))
+    }
+
+    /// A plain batch of two ops, the first drawing two primitives, then two
+    /// primitives of no op, and a symbol drawn three times.
+    struct Drawing {
+        mesh: MeshBuffer,
+        instances: Vec<GpuInstance>,
+        nodes: Vec<DrawNode>,
+        op_primitives: Vec<Range<u32>>,
+    }
+
+    impl Drawing {
+        fn new() -> Self {
+            let mut mesh = MeshBuffer::new();
+            quad(&mut mesh, (0.0, 0.0), 10.0, 0);
+            quad(&mut mesh, (10.0, 0.0), 10.0, 1);
+            quad(&mut mesh, (100.0, 0.0), 10.0, 2);
+            quad(&mut mesh, (200.0, 0.0), 10.0, 3);
+            quad(&mut mesh, (300.0, 0.0), 10.0, 4);
+            quad(&mut mesh, (-1.0, -1.0), 2.0, 5);
+            let instances = vec![
+                instance(Transform::translation(50.0, 50.0)),
+                instance(Transform::translation(300.0, 300.0)),
+                // A diamond reaching 10√2 either side of (500, 0).
+                instance(Transform::scale(10.0, 10.0).then_rotate(Angle::degrees(45.0)).then_translate(vector(500.0, 0.0))),
+            ];
+            Drawing {
+                mesh,
+                instances,
+                nodes: vec![batch(0..30, None), batch(30..36, Some(0..3))],
+                op_primitives: vec![0..2, 2..3],
+            }
+        }
+        fn update(&self, index: &mut CullIndex, indices_from: u32, instances_from: u32) {
+            index.update(&self.mesh, &self.instances, &self.nodes, &self.op_primitives, indices_from, instances_from);
+        }
+        fn index(&self) -> CullIndex {
+            let mut index = CullIndex::new();
+            self.update(&mut index, 0, 0);
+            index
+        }
+    }
+
+    fn span_ranges(index: &CullIndex) -> Vec<CullSpan> {
+        index.spans.iter().map(|(_, span)| span.clone()).collect()
+    }
+
+    #[test]
+    fn plain_batches_split_where_the_op_changes() {
+        let index = Drawing::new().index();
+        assert_eq!(span_ranges(&index), vec![
+            CullSpan::Indices(0..12),
+            CullSpan::Indices(12..18),
+            CullSpan::Indices(18..24),
+            CullSpan::Indices(24..30),
+            CullSpan::Instances(0..1),
+            CullSpan::Instances(1..2),
+            CullSpan::Instances(2..3),
+        ]);
+        // Both primitives of the first op are in its span.
+        assert_eq!(index.spans[0].0, region((0.0, 0.0), (20.0, 10.0)));
+        // Groups aren't measured.
+        let mut drawing = Drawing::new();
+        drawing.nodes.push(DrawNode::Group(crate::data::picture::GroupNode {
+            opacity: 0.5,
+            blend_mode: BlendMode::SourceOver,
+            bounds: region((0.0, 0.0), (10.0, 10.0)),
+            filters: Vec::new(),
+            children: vec![batch(0..6, None)],
+        }));
+        assert_eq!(drawing.index().len(), 7);
+    }
+
+    #[test]
+    fn visible_ranges_are_merged() {
+        let index = Drawing::new().index();
+        let visible = index.visible(&region((-1000.0, -1000.0), (1000.0, 1000.0)));
+        assert_eq!(visible, VisibleRanges { indices: vec![0..30], instances: vec![0..3] });
+        // Neighbouring ops join up, and the gap between them splits.
+        let visible = index.visible(&region((5.0, 2.0), (105.0, 8.0)));
+        assert_eq!(visible.indices, vec![0..18]);
+        let visible = index.visible(&region((5.0, 2.0), (205.0, 8.0)));
+        assert_eq!(visible.indices, vec![0..24]);
+        let visible = index.visible(&region((205.0, 2.0), (305.0, 8.0)));
+        assert_eq!(visible.indices, vec![18..30]);
+        let visible = index.visible(&region((150.0, 2.0), (250.0, 8.0)));
+        assert_eq!(visible, VisibleRanges { indices: vec![18..24], instances: Vec::new() });
+        let visible = index.visible(&region((49.5, 49.5), (50.5, 50.5)));
+        assert_eq!(visible, VisibleRanges { indices: Vec::new(), instances: vec![0..1] });
+    }
+
+    #[test]
+    fn rotated_instances_are_bounded_where_they_land() {
+        let index = Drawing::new().index();
+        let diamond = index.spans.iter().find(|(_, span)| *span == CullSpan::Instances(2..3)).unwrap().0;
+        let reach = 10.0 * std::f32::consts::SQRT_2;
+        assert!((diamond.min - point(500.0 - reach, -reach)).length() < 1e-3, "{:?}", diamond);
+        assert!((diamond.max - point(500.0 + reach, reach)).length() < 1e-3, "{:?}", diamond);
+        assert_eq!(index.visible(&region((513.0, -0.5), (513.5, 0.5))).instances, vec![2..3]);
+        assert!(index.visible(&region((515.0, -0.5), (516.0, 0.5))).instances.is_empty());
+        // An unrotated square this size would have stopped at 510.
+        assert_eq!(index.visible(&region((511.0, 11.0), (512.0, 12.0))).instances, vec![2..3]);
+    }
+
+    #[test]
+    fn appending_keeps_the_earlier_spans() {
+        // The first op, as a batch that later grows, e.g. a live stroke.
+        let full = Drawing::new();
+        let mut drawing = Drawing::new();
+        drawing.nodes = vec![batch(0..12, None)];
+        let mut index = drawing.index();
+        assert_eq!(span_ranges(&index), vec![CullSpan::Indices(0..12)]);
+        let first = index.spans[0].clone();
+
+        // Moving what's already measured doesn't re-measure it.
+        drawing.mesh.vertices[0].position = [-50.0, -50.0];
+        drawing.nodes = full.nodes.clone();
+        drawing.update(&mut index, 12, 0);
+        assert_eq!(index.spans[0], first);
+        assert_eq!(index.spans[1..], full.index().spans[1..]);
+    }
+
+    #[test]
+    fn changes_truncate_from_the_first_span_they_touch() {
+        let mut drawing = Drawing::new();
+        let mut index = drawing.index();
+        // Move the second op, which also shifts everything after it.
+        for vertex in drawing.mesh.vertices[8..12].iter_mut() {
+            vertex.position[1] += 100.0;
+        }
+        drawing.instances[1] = instance(Transform::translation(-300.0, 0.0));
+        drawing.update(&mut index, 15, 1);
+        assert_eq!(index.spans, drawing.index().spans);
+        assert_eq!(index.spans[1].0, region((100.0, 100.0), (110.0, 110.0)));
+        assert_eq!(index.visible(&region((-301.0, -1.0), (-299.0, 1.0))).instances, vec![1..2]);
+
+        // Removing geometry from the end leaves no spans behind.
+        drawing.instances.truncate(1);
+        drawing.nodes = vec![batch(0..12, None), batch(30..36, Some(0..1))];
+        drawing.update(&mut index, 12, 1);
+        assert_eq!(span_ranges(&index), vec![CullSpan::Indices(0..12), CullSpan::Instances(0..1)]);
+    }
+
+    #[test]
+    fn queries_match_a_scan_across_rebuilds() {
+        // A row of primitives of no op, enough to build the R-tree.
+        let count = CullIndex::MAX_UNINDEXED as u32 + 50;
+        let mut mesh = MeshBuffer::new();
+        for ix in 0..count {
+            quad(&mut mesh, (ix as f32 * 10.0, 0.0), 5.0, ix);
+        }
+        let nodes = vec![batch(0..count * 6, None)];
+        let mut index = CullIndex::new();
+        index.update(&mesh, &[], &nodes, &[], 0, 0);
+        assert_eq!(index.indexed, count as usize);
+        let scan = |index: &CullIndex, region: &Box2D| {
+            let hits = index.spans.iter()
+                .filter(|(bounds, _)| spatial::overlaps(bounds, region))
+                .map(|(_, span)| match span {
+                    CullSpan::Indices(range) => range.clone(),
+                    CullSpan::Instances(_) => unreachable!(),
+                })
+                .collect();
+            merge(hits)
+        };
+        let regions = [region((-5.0, 0.0), (1.0, 1.0)), region((102.0, 1.0), (1003.0, 2.0)), region((2000.0, 1.0), (9000.0, 2.0))];
+        for region in regions.iter() {
+            assert_eq!(index.visible(region).indices, scan(&index, region));
+        }
+        // A few more on the end stay out of the tree, and are still found.
+        for ix in count..count + 10 {
+            quad(&mut mesh, (ix as f32 * 10.0, 0.0), 5.0, ix);
+        }
+        let nodes = vec![batch(0..(count + 10) * 6, None)];
+        index.update(&mesh, &[], &nodes, &[], count * 6, 0);
+        assert_eq!(index.indexed, count as usize);
+        for region in regions.iter() {
+            assert_eq!(index.visible(region).indices, scan(&index, region));
+        }
+        // Truncating into the tree rebuilds it.
+        index.update(&mesh, &[], &nodes, &[], 600, 0);
+        assert_eq!(index.indexed, index.len());
+        for region in regions.iter() {
+            assert_eq!(index.visible(region).indices, scan(&index, region));
+        }
+    }
+
+    #[test]
+    fn merging_joins_touching_and_overlapping_ranges() {
+        assert_eq!(merge(vec![5..8, 0..2, 2..4, 7..10, 12..14]), vec![0..4, 5..10, 12..14]);
+        assert_eq!(merge(vec![0..10, 2..3]), vec![0..10]);
+        assert_eq!(merge(Vec::new()), Vec::<Range<u32>>::new());
+    }
+
+    #[test]
+    fn clipping_stops_at_batch_boundaries() {
+        let visible = VisibleRanges { indices: vec![0..6, 12..30, 36..42], instances: vec![2..5] };
+        let clip_indices = |range: Range<u32>| visible.clip_indices(range).collect::<Vec<_>>();
+        assert_eq!(clip_indices(0..6), vec![0..6]);
+        assert_eq!(clip_indices(6..12), vec![]);
+        assert_eq!(clip_indices(30..36), vec![]);
+        assert_eq!(clip_indices(3..18), vec![3..6, 12..18]);
+        assert_eq!(clip_indices(18..100), vec![18..30, 36..42]);
+        assert_eq!(clip_indices(42..48), vec![]);
+        let clip_instances = |range: Range<u32>| visible.clip_instances(range).collect::<Vec<_>>();
+        assert_eq!(clip_instances(0..2), vec![]);
+        assert_eq!(clip_instances(0..3), vec![2..3]);
+        assert_eq!(clip_instances(4..8), vec![4..5]);
+    }
+}
@@ -0,0 +1,366 @@
+use std::ops::Range;
+use lyon::math::Point;
+use lyon::path::{Attributes, AttributeStore, Event, Path};
+use lyon::path::builder::PathBuilder;
+use lyon::algorithms::measure::{PathMeasurements, SampleType};
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// STROKE DASHES
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// Splits a stroke into dashes before it is tessellated. Each dash is its own
+/// subpath, so the stroke's caps are drawn at both ends of every dash. The
+/// pattern restarts at the beginning of each subpath. Lengths are in picture
+/// units.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StrokeDash {
+    /// Alternating on and off lengths, starting with "on". An odd number of
+    /// entries is repeated once, as with SVG `stroke-dasharray`. Zero length
+    /// dashes are kept, and draw just the caps.
+    Dashes { array: Vec<f32>, offset: f32 },
+    /// Round dots, as wide as the stroke, with centers `spacing` apart.
+    Dots { spacing: f32, offset: f32 },
+}
+
+impl StrokeDash {
+    /// Patterns that repeat more often than this along a subpath are drawn
+    /// solid, like those shorter than the tolerance.
+    pub const MAX_PERIODS: f32 = 1_000_000.0;
+
+    pub fn dashes(array: impl Into<Vec<f32>>) -> Self {
+        StrokeDash::Dashes { array: array.into(), offset: 0.0 }
+    }
+    pub fn dots(spacing: f32) -> Self {
+        StrokeDash::Dots { spacing, offset: 0.0 }
+    }
+    pub fn with_offset(self, offset: f32) -> Self {
+        match self {
+            StrokeDash::Dashes { array, .. } => StrokeDash::Dashes { array, offset },
+            StrokeDash::Dots { spacing, .. } => StrokeDash::Dots { spacing, offset },
+        }
+    }
+    /// Dots are zero length dashes, which only show up with round caps.
+    pub fn stroke_options(&self, options: lyon::tessellation::StrokeOptions) -> lyon::tessellation::StrokeOptions {
+        match self {
+            StrokeDash::Dashes { .. } => options,
+            StrokeDash::Dots { .. } => options.with_line_cap(lyon::path::LineCap::Round),
+        }
+    }
+    /// The normalized on/off pattern and offset, or `None` when the pattern
+    /// can't be followed (negative or non-finite entries, or a zero total
+    /// length) and the stroke should be drawn solid.
+    fn pattern(&self) -> Option<(Vec<f32>, f32)> {
+        let (mut pattern, offset) = match self {
+            StrokeDash::Dashes { array, offset } => (array.clone(), *offset),
+            StrokeDash::Dots { spacing, offset } => (vec![0.0, *spacing], *offset),
+        };
+        let valid = pattern.iter().all(|length| length.is_finite() && *length >= 0.0)
+            && pattern.iter().sum::<f32>() > 0.0
+            && offset.is_finite();
+        if !valid {
+            return None
+        }
+        if pattern.len() % 2 == 1 {
+            pattern.extend_from_within(..);
+        }
+        Some((pattern, offset))
+    }
+    /// Returns the dashes of `path` as a new path, with any custom attributes
+    /// interpolated at the dash ends. `tolerance` is used to measure curves.
+    pub fn apply(&self, path: &Path, tolerance: f32) -> Path {
+        let (pattern, offset) = match self.pattern() {
+            Some(pattern) => pattern,
+            None => return path.clone(),
+        };
+        let num_attributes = path.num_attributes();
+        let mut output = Path::builder_with_attributes(num_attributes);
+        for subpath in subpaths(path) {
+            dash_subpath(&subpath, num_attributes, &pattern, offset, tolerance, &mut output);
+        }
+        output.build()
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// INTERNAL HELPERS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+type SubpathEvent<'l> = Event<(Point, Attributes<'l>), Point>;
+
+fn subpaths(path: &Path) -> Vec<Vec<SubpathEvent<'_>>> {
+    let mut subpaths = Vec::new();
+    for event in path.iter_with_attributes() {
+        if let Event::Begin { .. } = event {
+            subpaths.push(Vec::new());
+        }
+        if let Some(subpath) = subpaths.last_mut() {
+            subpath.push(event);
+        }
+    }
+    subpaths
+}
+
+fn is_closed(subpath: &[SubpathEvent]) -> bool {
+    matches!(subpath.last(), Some(Event::End { close: true, .. }))
+}
+
+/// Replays a subpath. With `laps > 1` a closed subpath is walked that many
+/// times as one open subpath, so dashes can run across its starting point.
+fn replay(subpath: &[SubpathEvent], laps: usize, output: &mut dyn PathBuilder) {
+    let closed = is_closed(subpath);
+    for lap in 0..laps {
+        for event in subpath {
+            match *event {
+                Event::Begin { at } => {
+                    if lap == 0 {
+                        output.begin(at.0, at.1);
+                    }
+                }
+                Event::Line { to, .. } => {
+                    output.line_to(to.0, to.1);
+                }
+                Event::Quadratic { ctrl, to, .. } => {
+                    output.quadratic_bezier_to(ctrl, to.0, to.1);
+                }
+                Event::Cubic { ctrl1, ctrl2, to, .. } => {
+                    output.cubic_bezier_to(ctrl1, ctrl2, to.0, to.1);
+                }
+                Event::End { first, .. } => {
+                    if laps > 1 && closed {
+                        output.line_to(first.0, first.1);
+                    }
+                }
+            }
+        }
+    }
+    output.end(laps == 1 && closed);
+}
+
+fn dash_subpath(
+    subpath: &[SubpathEvent],
+    num_attributes: usize,
+    pattern: &[f32],
+    offset: f32,
+    tolerance: f32,
+    output: &mut dyn PathBuilder,
+) {
+    let closed = is_closed(subpath);
+    let laps = if closed { 2 } else { 1 };
+    let mut measured = Path::builder_with_attributes(num_attributes);
+    replay(subpath, laps, &mut measured);
+    let measured = measured.build();
+    let measurements = PathMeasurements::from_path(&measured, tolerance);
+    let mut sampler = measurements.create_sampler_with_attributes(&measured, &measured, SampleType::Distance);
+    let length = sampler.length() / laps as f32;
+    // Too fine a pattern can't be told from a solid stroke, and would take
+    // that many dashes to draw.
+    let total: f32 = pattern.iter().sum();
+    if length <= 0.0 || total < tolerance || length / total > StrokeDash::MAX_PERIODS {
+        replay(subpath, 1, output);
+        return
+    }
+    let mut intervals = dash_intervals(pattern, offset, length);
+    if closed && intervals.len() > 1 {
+        // A dash that is on across the starting point continues into the
+        // second lap instead of being drawn as two capped halves.
+        let first = intervals[0].clone();
+        let last = intervals.last_mut().unwrap();
+        if first.start <= 0.0 && last.end >= length {
+            last.end = length + first.end;
+            intervals.remove(0);
+        }
+    }
+    if closed && intervals.len() == 1 && intervals[0].start <= 0.0 && intervals[0].end >= length {
+        replay(subpath, 1, output);
+        return
+    }
+    for interval in intervals {
+        if interval.is_empty() {
+            let mut sample = sampler.sample(interval.start);
+            output.begin(sample.position(), sample.attributes());
+            output.end(false);
+        } else {
+            sampler.split_range(interval, output);
+        }
+    }
+}
+
+/// The parts of `0..=length` where the pattern is on. Zero length dashes
+/// give empty ranges. Dash ends are measured from the start of their
+/// period rather than summed up one after another, so they don't drift
+/// on long paths, and there's one pass of the pattern per period.
+fn dash_intervals(pattern: &[f32], offset: f32, length: f32) -> Vec<Range<f32>> {
+    let total: f32 = pattern.iter().sum();
+    let mut phase = offset.rem_euclid(total);
+    if phase >= total {
+        phase = 0.0;
+    }
+    let mut ix = 0;
+    while phase > 0.0 && phase >= pattern[ix] {
+        phase -= pattern[ix];
+        ix = (ix + 1) % pattern.len();
+    }
+    // The pattern from the entry the path starts in, with where each entry
+    // ends within a period.
+    let mut ends = Vec::with_capacity(pattern.len());
+    let mut sum = 0.0;
+    for step in 0..pattern.len() {
+        sum += pattern[(ix + step) % pattern.len()];
+        ends.push(sum);
+    }
+    let periods = ((length + phase) / total).ceil() as usize + 1;
+    let mut intervals = Vec::new();
+    for period in 0..periods {
+        let base = period as f32 * total - phase;
+        for step in 0..pattern.len() {
+            let start = base + if step == 0 { 0.0 } else { ends[step - 1] };
+            if start > length {
+                return intervals
+            }
+            let end = base + ends[step];
+            if (ix + step) % 2 == 0 && end >= 0.0 {
+                intervals.push(start.max(0.0)..end.min(length));
+            }
+        }
+    }
+    intervals
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// TESTS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lyon::math::point;
+
+    fn polyline(points: &[(f32, f32)], closed: bool) -> Path {
+        let mut builder = Path::builder();
+        builder.begin(point(points[0].0, points[0].1));
+        for (x, y) in points[1..].iter() {
+            builder.line_to(point(*x, *y));
+        }
+        builder.end(closed);
+        builder.build()
+    }
+
+    /// Each subpath's points, and whether it's closed.
+    fn dashes(path: &Path) -> Vec<(Vec<Point>, bool)> {
+        let mut dashes: Vec<(Vec<Point>, bool)> = Vec::new();
+        for event in path.iter() {
+            match event {
+                Event::Begin { at } => dashes.push((vec![at], false)),
+                Event::Line { to, .. } | Event::Quadratic { to, .. } | Event::Cubic { to, .. } => dashes.last_mut().unwrap().0.push(to),
+                Event::End { close, .. } => dashes.last_mut().unwrap().1 = close,
+            }
+        }
+        dashes
+    }
+
+    fn length(path: &Path) -> f32 {
+        PathMeasurements::from_path(path, 1e-4).length()
+    }
+
+    fn assert_near(a: Point, b: (f32, f32)) {
+        assert!((a - point(b.0, b.1)).length() < 1e-3, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn offsets_shift_the_pattern_either_way() {
+        assert_eq!(dash_intervals(&[4.0, 2.0], 0.0, 10.0), vec![0.0..4.0, 6.0..10.0]);
+        // A dash starting right at the end is kept as a point, like a
+        // zero length dash.
+        assert_eq!(dash_intervals(&[4.0, 2.0], 0.0, 12.0), vec![0.0..4.0, 6.0..10.0, 12.0..12.0]);
+        assert_eq!(dash_intervals(&[4.0, 2.0], 1.0, 12.0), vec![0.0..3.0, 5.0..9.0, 11.0..12.0]);
+        assert_eq!(dash_intervals(&[4.0, 2.0], -1.0, 12.0), vec![1.0..5.0, 7.0..11.0]);
+        // A whole period of offset changes nothing.
+        assert_eq!(dash_intervals(&[4.0, 2.0], 13.0, 12.0), dash_intervals(&[4.0, 2.0], 1.0, 12.0));
+        let path = polyline(&[(0.0, 0.0), (12.0, 0.0)], false);
+        let dashed = dashes(&StrokeDash::dashes([4.0, 2.0]).with_offset(1.0).apply(&path, 0.01));
+        assert_eq!(dashed.len(), 3);
+        assert_near(dashed[1].0[0], (5.0, 0.0));
+        assert_near(*dashed[1].0.last().unwrap(), (9.0, 0.0));
+    }
+
+    #[test]
+    fn odd_arrays_repeat_and_invalid_ones_draw_solid() {
+        assert_eq!(StrokeDash::dashes([3.0]).pattern(), Some((vec![3.0, 3.0], 0.0)));
+        assert_eq!(StrokeDash::dashes([1.0, 2.0, 3.0]).pattern(), Some((vec![1.0, 2.0, 3.0, 1.0, 2.0, 3.0], 0.0)));
+        assert_eq!(dash_intervals(&[1.0, 2.0, 3.0, 1.0, 2.0, 3.0], 0.0, 12.0), vec![0.0..1.0, 3.0..6.0, 7.0..9.0, 12.0..12.0]);
+        for invalid in [StrokeDash::dashes([]), StrokeDash::dashes([0.0, 0.0]), StrokeDash::dashes([2.0, -1.0]), StrokeDash::dashes([f32::NAN])] {
+            assert_eq!(invalid.pattern(), None);
+        }
+        let path = polyline(&[(0.0, 0.0), (12.0, 0.0)], false);
+        assert_eq!(dashes(&StrokeDash::dashes([2.0, -1.0]).apply(&path, 0.01)), dashes(&path));
+    }
+
+    #[test]
+    fn zero_length_dashes_and_dots_are_single_points() {
+        assert_eq!(dash_intervals(&[0.0, 5.0], 0.0, 10.0), vec![0.0..0.0, 5.0..5.0, 10.0..10.0]);
+        let path = polyline(&[(0.0, 0.0), (10.0, 0.0)], false);
+        for dash in [StrokeDash::dashes([0.0, 5.0]), StrokeDash::dots(5.0)] {
+            let dashed = dashes(&dash.apply(&path, 0.01));
+            assert_eq!(dashed.len(), 3);
+            for ((points, closed), x) in dashed.iter().zip([0.0, 5.0, 10.0]) {
+                assert_eq!(points.len(), 1);
+                assert!(!closed);
+                assert_near(points[0], (x, 0.0));
+            }
+        }
+        let options = lyon::tessellation::StrokeOptions::default();
+        assert_eq!(StrokeDash::dots(5.0).stroke_options(options).start_cap, lyon::path::LineCap::Round);
+        assert_eq!(StrokeDash::dashes([1.0]).stroke_options(options).start_cap, options.start_cap);
+    }
+
+    #[test]
+    fn curves_are_dashed_by_arc_length() {
+        let mut builder = Path::builder();
+        builder.begin(point(0.0, 0.0));
+        builder.quadratic_bezier_to(point(10.0, 20.0), point(20.0, 0.0));
+        builder.end(false);
+        let curve = builder.build();
+        let dashed = StrokeDash::dashes([1.0, 1.0]).apply(&curve, 0.001);
+        let pieces = dashes(&dashed);
+        assert_eq!(pieces.len(), (length(&curve) / 2.0).ceil() as usize);
+        assert!((length(&dashed) - length(&curve) / 2.0).abs() < 1.0, "{} of {}", length(&dashed), length(&curve));
+        // Every dash is a piece of the curve, at most a dash long.
+        for (points, _) in pieces.iter() {
+            let piece = polyline(&points.iter().map(|point| (point.x, point.y)).collect::<Vec<_>>(), false);
+            assert!(length(&piece) <= 1.0 + 1e-3);
+        }
+    }
+
+    #[test]
+    fn dashes_run_across_the_start_of_closed_subpaths() {
+        let square = polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)], true);
+        let dashed = dashes(&StrokeDash::dashes([6.0, 4.0]).with_offset(3.0).apply(&square, 0.01));
+        assert_eq!(dashed.len(), 4);
+        assert!(dashed.iter().all(|(_, closed)| !closed));
+        // The dash that was on at the start continues from the end of the
+        // last side round the corner, instead of being two capped halves.
+        let (across, _) = dashed.last().unwrap();
+        assert_near(across[0], (0.0, 3.0));
+        assert!(across.iter().any(|corner| corner.to_vector().length() < 1e-3));
+        assert_near(*across.last().unwrap(), (3.0, 0.0));
+        // A dash longer than the whole loop leaves it closed and solid.
+        let solid = dashes(&StrokeDash::dashes([50.0, 1.0]).apply(&square, 0.01));
+        assert_eq!(solid.len(), 1);
+        assert!(solid[0].1);
+    }
+
+    #[test]
+    fn patterns_too_fine_to_see_draw_solid() {
+        let path = polyline(&[(0.0, 0.0), (10_000.0, 0.0)], false);
+        let solid = dashes(&StrokeDash::dashes([1e-6, 1e-6]).apply(&path, 0.01));
+        assert_eq!(solid, dashes(&path));
+        let solid = dashes(&StrokeDash::dots(1e-3).apply(&path, 0.01));
+        assert_eq!(solid, dashes(&path));
+        // Far from the start, dashes still land where the pattern says.
+        let intervals = dash_intervals(&[0.5, 0.5], 0.0, 1e6);
+        assert_eq!(intervals.len(), 1_000_001);
+        assert_eq!(intervals[999_999], 999_999.0..999_999.5);
+    }
+}
@@ -1,4 +1,8 @@
 use crate::data::{self, Resolution, PictureResolution};
+use crate::data::color::ThemeColor;
+use crate::data::filters::Filter;
+use crate::data::dash::StrokeDash;
+use crate::data::symbols::{Symbol, SymbolInstance};
 use lyon::math::Point;
 use lyon::path::PathEvent;
 use lyon::tessellation::geometry_builder::*;
@@ -14,29 +18,147 @@ pub enum DrawOp {
     Stroke(StrokeOp),
     Fill(FillOp),
     FillStroke(FillStrokeOp),
+    Group(GroupOp),
+    Instances(InstancesOp),
 }
 
 #[derive(Debug, Clone)]
 pub struct StrokeOp {
     pub path: lyon::path::Path,
-    pub stroke_color: crate::data::RGBA<u8>,
+    pub stroke_color: ThemeColor,
     pub stroke_settings: lyon::tessellation::StrokeOptions,
+    pub stroke_dash: Option<StrokeDash>,
+    pub blend_mode: BlendMode,
 }
 
 #[derive(Debug, Clone)]
 pub struct FillOp {
     pub path: lyon::path::Path,
-    pub fill_color: crate::data::RGBA<u8>,
+    pub fill_color: ThemeColor,
     pub fill_settings: lyon::tessellation::FillOptions,
+    pub blend_mode: BlendMode,
 }
 
 #[derive(Debug, Clone)]
 pub struct FillStrokeOp {
     pub path: lyon::path::Path,
-    pub fill_color: crate::data::RGBA<u8>,
-    pub stroke_color: crate::data::RGBA<u8>,
+    pub fill_color: ThemeColor,
+    pub stroke_color: ThemeColor,
     pub fill_settings: lyon::tessellation::FillOptions,
     pub stroke_settings: lyon::tessellation::StrokeOptions,
+    pub stroke_dash: Option<StrokeDash>,
+    pub stroke_alignment: StrokeAlignment,
+    pub blend_mode: BlendMode,
+}
+
+/// Where a `FillStrokeOp`'s stroke lies relative to the edge of its fill.
+/// Inner and outer strokes are cut against the fill, so they're drawn as
+/// fills of their outline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum StrokeAlignment {
+    /// Centered on the edge, half inside the fill and half outside.
+    #[default]
+    Center,
+    /// Entirely within the fill, so the shape keeps its size.
+    Inside,
+    /// Entirely outside the fill, so none of the fill is covered.
+    Outside,
+}
+
+impl DrawOp {
+    pub fn blend_mode(&self) -> BlendMode {
+        match self {
+            DrawOp::Stroke(op) => op.blend_mode,
+            DrawOp::Fill(op) => op.blend_mode,
+            DrawOp::FillStroke(op) => op.blend_mode,
+            DrawOp::Group(op) => op.blend_mode,
+            DrawOp::Instances(op) => op.blend_mode,
+        }
+    }
+    /// Attaches a filter effect. Filters run on a group's offscreen layer, so
+    /// anything other than a group is wrapped in one first.
+    pub fn with_filter(self, filter: Filter) -> DrawOp {
+        match self {
+            DrawOp::Group(group) => group.with_filter(filter).into(),
+            op => GroupOp::new([op]).with_filter(filter).into(),
+        }
+    }
+}
+
+/// Ops that are drawn together into an isolated offscreen layer, which is then
+/// composited onto the parent once with the group's opacity and blend mode.
+/// Overlapping children therefore don't show through each other. Filters run
+/// on the layer, in order, before it is composited.
+#[derive(Debug, Clone)]
+pub struct GroupOp {
+    pub items: Vec<DrawOp>,
+    pub opacity: f32,
+    pub blend_mode: BlendMode,
+    pub filters: Vec<Filter>,
+}
+
+impl GroupOp {
+    pub fn new(items: impl IntoIterator<Item = DrawOp>) -> Self {
+        GroupOp {
+            items: items.into_iter().collect(),
+            opacity: 1.0,
+            blend_mode: BlendMode::default(),
+            filters: Vec::new(),
+        }
+    }
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity;
+        self
+    }
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filters.push(filter);
+        self
+    }
+    pub fn push(&mut self, op: impl Into<DrawOp>) {
+        self.items.push(op.into());
+    }
+    /// An opaque source-over group whose children are all source-over looks
+    /// the same isolated or not, so it can skip the offscreen target.
+    pub fn is_passthrough(&self) -> bool {
+        self.opacity >= 1.0
+            && self.filters.is_empty()
+            && self.blend_mode == BlendMode::SourceOver
+            && self.items.iter().all(|item| match item {
+                DrawOp::Group(group) => group.is_passthrough(),
+                item => item.blend_mode() == BlendMode::SourceOver,
+            })
+    }
+}
+
+/// Draws a symbol once per instance. The symbol's mesh is shared by every
+/// op that uses it, and each instance only adds its transform and color to
+/// an instance buffer.
+#[derive(Debug, Clone)]
+pub struct InstancesOp {
+    pub symbol: std::sync::Arc<Symbol>,
+    pub instances: Vec<SymbolInstance>,
+    pub blend_mode: BlendMode,
+}
+
+impl InstancesOp {
+    pub fn new(symbol: std::sync::Arc<Symbol>, instances: impl IntoIterator<Item = SymbolInstance>) -> Self {
+        InstancesOp {
+            symbol,
+            instances: instances.into_iter().collect(),
+            blend_mode: BlendMode::default(),
+        }
+    }
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+    pub fn push(&mut self, instance: SymbolInstance) {
+        self.instances.push(instance);
+    }
 }
 
 
@@ -49,7 +171,48 @@ impl From<FillOp> for DrawOp {
 impl From<StrokeOp> for DrawOp {
     fn from(op: StrokeOp) -> Self { DrawOp::Stroke(op) }
 }
+impl From<GroupOp> for DrawOp {
+    fn from(op: GroupOp) -> Self { DrawOp::Group(op) }
+}
+impl From<InstancesOp> for DrawOp {
+    fn from(op: InstancesOp) -> Self { DrawOp::Instances(op) }
+}
+
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// BLEND MODES
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// How an op's (premultiplied) color is combined with whatever is already
+/// drawn beneath it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BlendMode {
+    #[default]
+    SourceOver,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    Additive,
+}
 
+impl BlendMode {
+    /// Overlay, darken and lighten need the backdrop color as an input to a
+    /// per-channel function, which fixed-function blending can't express for
+    /// translucent sources. These are drawn offscreen and then composited by
+    /// a shader that reads back the destination.
+    pub fn requires_composite_pass(&self) -> bool {
+        matches!(self, BlendMode::Overlay | BlendMode::Darken | BlendMode::Lighten)
+    }
+    /// Whether drawing onto the given backdrop needs a composite pass.
+    /// Fixed-function multiply is only exact over an opaque backdrop, which
+    /// the transparent target of a group isn't.
+    pub fn requires_composite_pass_over(&self, opaque_backdrop: bool) -> bool {
+        self.requires_composite_pass() || (*self == BlendMode::Multiply && !opaque_backdrop)
+    }
+}
 
 
 //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
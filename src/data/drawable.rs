@@ -1,4 +1,9 @@
-use super::geometry::{Point, PointVec, PointVecRef};
+use super::geometry::{InkSampleVec, Point, PointVec, PointVecRef};
+use lyon::math::Angle;
+use super::color::{ColorToken, ThemeColor};
+use super::ink;
+use super::draw_cmds::DrawOp;
+use crate::frontend::DrawableObject;
 // use crate::data::g
 
 //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
@@ -21,11 +26,175 @@ use super::geometry::{Point, PointVec, PointVecRef};
 // }
 
 pub enum DrawCmd {
-    Path(PointVec, PathOptions)
+    Path(PointVec, PathOptions),
+    /// A pen stroke whose width follows pressure and speed. The `width` of
+    /// the `PathOptions` is unused.
+    Ink(InkSampleVec, PathOptions, InkWidth),
+    /// A flat-nib calligraphy stroke, filled in the `PathOptions` color. The
+    /// `width` of the `PathOptions` is unused.
+    Nib(PointVec, PathOptions, NibPen),
 }
 
+/// How raw pointer samples become a stroke. Lengths are in picture units.
+#[derive(Debug, Clone, PartialEq)]
 pub struct PathOptions {
+    pub color: ThemeColor,
+    pub width: f32,
+    /// Samples closer than this to the previous one are dropped.
+    pub min_distance: f32,
+    pub smoothing: SmoothingFilter,
+    /// How far the fitted curves may stray from the smoothed samples.
+    pub fit_tolerance: f32,
+}
+
+/// Low-pass filters for jittery input. Stronger smoothing rounds off corners.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SmoothingFilter {
+    None,
+    /// Averages each sample with `radius` neighbours on either side.
+    MovingAverage { radius: usize },
+    /// An exponential filter run in both directions. `strength` ranges from
+    /// `0.0` (off) to just below `1.0`.
+    Exponential { strength: f32 },
+}
+
+impl Default for PathOptions {
+    fn default() -> Self {
+        PathOptions {
+            color: ColorToken::Foreground.into(),
+            width: 2.0,
+            min_distance: 0.5,
+            smoothing: SmoothingFilter::Exponential { strength: 0.5 },
+            fit_tolerance: 0.5,
+        }
+    }
+}
+
+impl PathOptions {
+    pub fn with_color(mut self, color: impl Into<ThemeColor>) -> Self {
+        self.color = color.into();
+        self
+    }
+    pub fn with_width(mut self, width: f32) -> Self {
+        self.width = width;
+        self
+    }
+    pub fn with_smoothing(mut self, smoothing: SmoothingFilter) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+    pub fn with_fit_tolerance(mut self, fit_tolerance: f32) -> Self {
+        self.fit_tolerance = fit_tolerance;
+        self
+    }
+}
+
+/// Maps pen input to stroke width, in picture units. Pressure picks a width
+/// between `min_width` and `max_width`, fast movement thins it, and the
+/// first and last `taper_*` lengths of the stroke narrow to a point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InkWidth {
+    pub min_width: f32,
+    pub max_width: f32,
+    pub pressure_response: ResponseCurve,
+    /// The fraction of the width lost at `max_speed`, from `0.0` to `1.0`.
+    pub velocity_thinning: f32,
+    pub velocity_response: ResponseCurve,
+    /// Picture units per second.
+    pub max_speed: f32,
+    pub taper_start: f32,
+    pub taper_end: f32,
+    /// How far the width may stray from the samples between the ends of a
+    /// fitted curve, before the curve is split.
+    pub width_tolerance: f32,
+}
+
+/// Shapes a normalized input (pressure, speed) before it's used, mapping
+/// `0.0..=1.0` onto `0.0..=1.0`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResponseCurve {
+    Linear,
+    /// Exponents above one need a firmer press, below one a lighter one.
+    Power { exponent: f32 },
+    SmoothStep,
+}
+
+impl ResponseCurve {
+    pub fn apply(&self, input: f32) -> f32 {
+        let x = if input.is_finite() { input.clamp(0.0, 1.0) } else { 0.0 };
+        match self {
+            ResponseCurve::Linear => x,
+            ResponseCurve::Power { exponent } => x.powf(exponent.max(0.0)),
+            ResponseCurve::SmoothStep => x * x * (3.0 - 2.0 * x),
+        }
+    }
+}
+
+impl Default for InkWidth {
+    fn default() -> Self {
+        InkWidth {
+            min_width: 0.5,
+            max_width: 4.0,
+            pressure_response: ResponseCurve::Power { exponent: 1.5 },
+            velocity_thinning: 0.3,
+            velocity_response: ResponseCurve::SmoothStep,
+            max_speed: 2000.0,
+            taper_start: 8.0,
+            taper_end: 12.0,
+            width_tolerance: 0.1,
+        }
+    }
+}
+
+/// A flat calligraphy nib: a `width` by `thickness` rectangle, held at
+/// `angle` from +x (clockwise on screen). The stroke is widest moving across
+/// the nib and thinnest, `thickness`, moving along it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NibPen {
+    pub width: f32,
+    pub thickness: f32,
+    pub angle: Angle,
+    /// Measure `angle` from the direction the pen leans, so the nib turns
+    /// with the hand. Only for input with tilt; an upright pen keeps the last
+    /// angle.
+    pub follow_tilt: bool,
+}
+
+impl Default for NibPen {
+    fn default() -> Self {
+        NibPen {
+            width: 6.0,
+            thickness: 1.0,
+            angle: Angle::frac_pi_4(),
+            follow_tilt: false,
+        }
+    }
+}
+
+impl NibPen {
+    pub fn with_size(mut self, width: f32, thickness: f32) -> Self {
+        self.width = width;
+        self.thickness = thickness;
+        self
+    }
+    pub fn with_angle(mut self, angle: Angle) -> Self {
+        self.angle = angle;
+        self
+    }
+    pub fn with_follow_tilt(mut self, follow_tilt: bool) -> Self {
+        self.follow_tilt = follow_tilt;
+        self
+    }
+}
 
+impl DrawableObject for DrawCmd {
+    fn draw(&self) -> DrawOp {
+        match self {
+            DrawCmd::Path(points, options) => ink::ink_stroke(points, options).into(),
+            DrawCmd::Ink(samples, options, width) => ink::variable_ink_stroke(samples, options, width).into(),
+            DrawCmd::Nib(points, options, nib) => ink::nib_stroke(points, options, nib).into(),
+        }
+    }
 }
 
 
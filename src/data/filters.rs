@@ -0,0 +1,309 @@
+use crate::data::RGBA;
+use crate::data::gpu_types::GpuPrimitive;
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// FILTER EFFECTS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// An effect applied to the rendered pixels of a group. Lengths are in
+/// picture units.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    /// `radius` is the standard deviation of the Gaussian, as with CSS `blur()`.
+    GaussianBlur { radius: f32 },
+    DropShadow { offset: lyon::math::Vector, color: RGBA<u8>, blur_radius: f32 },
+    /// A 4x5 row-major matrix applied to unpremultiplied RGBA, with the last
+    /// column as the offset, as with SVG `feColorMatrix`.
+    ColorMatrix([f32; 20]),
+}
+
+impl Filter {
+    pub const IDENTITY_COLOR_MATRIX: [f32; 20] = [
+        1.0, 0.0, 0.0, 0.0, 0.0,
+        0.0, 1.0, 0.0, 0.0, 0.0,
+        0.0, 0.0, 1.0, 0.0, 0.0,
+        0.0, 0.0, 0.0, 1.0, 0.0,
+    ];
+
+    pub fn grayscale() -> Filter {
+        let (r, g, b) = (0.2126, 0.7152, 0.0722);
+        Filter::ColorMatrix([
+            r, g, b, 0.0, 0.0,
+            r, g, b, 0.0, 0.0,
+            r, g, b, 0.0, 0.0,
+            0.0, 0.0, 0.0, 1.0, 0.0,
+        ])
+    }
+    /// How far the filter can move pixels outside the unfiltered bounds, as
+    /// `(min, max)` offsets in picture units.
+    pub fn outset(&self) -> (lyon::math::Vector, lyon::math::Vector) {
+        use lyon::math::vector;
+        match self {
+            Filter::GaussianBlur { radius } => {
+                let extent = kernel_extent(*radius);
+                (vector(extent, extent), vector(extent, extent))
+            }
+            Filter::DropShadow { offset, blur_radius, .. } => {
+                let extent = kernel_extent(*blur_radius);
+                let min = vector((extent - offset.x).max(0.0), (extent - offset.y).max(0.0));
+                let max = vector((extent + offset.x).max(0.0), (extent + offset.y).max(0.0));
+                (min, max)
+            }
+            Filter::ColorMatrix(_) => (vector(0.0, 0.0), vector(0.0, 0.0)),
+        }
+    }
+    /// The bounds of a group after applying `filters` in order.
+    pub fn expand_bounds(filters: &[Filter], bounds: lyon::math::Box2D) -> lyon::math::Box2D {
+        filters.iter().fold(bounds, |bounds, filter| {
+            let (min, max) = filter.outset();
+            lyon::math::Box2D::new(bounds.min - min, bounds.max + max)
+        })
+    }
+}
+
+/// Three standard deviations, past which the Gaussian contributes nothing
+/// visible.
+pub fn kernel_extent(sigma: f32) -> f32 {
+    (sigma * 3.0).ceil().max(0.0)
+}
+
+/// Normalized weights for offsets `-radius..=radius`, where `radius` is
+/// `kernel_extent(sigma)`. The GPU passes compute the same weights.
+pub fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    if sigma <= 0.0 {
+        return vec![1.0]
+    }
+    let radius = kernel_extent(sigma) as i32;
+    let weights = (-radius..=radius)
+        .map(|i| (-((i * i) as f32) / (2.0 * sigma * sigma)).exp())
+        .collect::<Vec<_>>();
+    let total: f32 = weights.iter().sum();
+    weights.into_iter().map(|w| w / total).collect()
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// CPU REFERENCE IMPLEMENTATION
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// A linear, premultiplied RGBA image, with reference versions of the GPU
+/// filter passes. They follow the same sampling rules: reads past the edge
+/// clamp to the nearest pixel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<[f32; 4]>,
+}
+
+impl FilterImage {
+    pub fn new(width: usize, height: usize) -> Self {
+        FilterImage { width, height, pixels: vec![[0.0; 4]; width * height] }
+    }
+    pub fn get(&self, x: isize, y: isize) -> [f32; 4] {
+        if self.pixels.is_empty() {
+            return [0.0; 4]
+        }
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        self.pixels[y * self.width + x]
+    }
+    pub fn set(&mut self, x: usize, y: usize, pixel: [f32; 4]) {
+        self.pixels[y * self.width + x] = pixel;
+    }
+    fn map_pixels(&self, f: impl Fn(isize, isize) -> [f32; 4]) -> FilterImage {
+        let mut output = FilterImage::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                output.set(x, y, f(x as isize, y as isize));
+            }
+        }
+        output
+    }
+    /// Convolves along one axis; `direction` is `(1, 0)` or `(0, 1)`.
+    fn convolve(&self, kernel: &[f32], direction: (isize, isize)) -> FilterImage {
+        let radius = (kernel.len() / 2) as isize;
+        self.map_pixels(|x, y| {
+            let mut sum = [0.0; 4];
+            for (i, weight) in kernel.iter().enumerate() {
+                let i = i as isize - radius;
+                let pixel = self.get(x + i * direction.0, y + i * direction.1);
+                for c in 0..4 {
+                    sum[c] += pixel[c] * weight;
+                }
+            }
+            sum
+        })
+    }
+    /// `scale` converts picture units to pixels of this image.
+    pub fn apply(&self, filter: &Filter, scale: f32) -> FilterImage {
+        match filter {
+            Filter::GaussianBlur { radius } => self.gaussian_blur(radius * scale),
+            Filter::DropShadow { offset, color, blur_radius } => {
+                self.drop_shadow((offset.x * scale, offset.y * scale), *color, blur_radius * scale)
+            }
+            Filter::ColorMatrix(matrix) => self.color_matrix(matrix),
+        }
+    }
+    pub fn gaussian_blur(&self, sigma: f32) -> FilterImage {
+        let kernel = gaussian_kernel(sigma);
+        self.convolve(&kernel, (1, 0)).convolve(&kernel, (0, 1))
+    }
+    /// `offset` and `sigma` are in pixels. The shadow is the source's alpha,
+    /// tinted, shifted and blurred, with the source drawn over it.
+    pub fn drop_shadow(&self, offset: (f32, f32), color: RGBA<u8>, sigma: f32) -> FilterImage {
+        let color = GpuPrimitive::from_u8_rgba(color).color;
+        let (dx, dy) = (offset.0.round() as isize, offset.1.round() as isize);
+        let shadow = self.map_pixels(|x, y| {
+            let alpha = self.get(x - dx, y - dy)[3];
+            color.map(|channel| channel * alpha)
+        });
+        let shadow = shadow.gaussian_blur(sigma);
+        self.map_pixels(|x, y| {
+            let src = self.get(x, y);
+            let dst = shadow.get(x, y);
+            let mut out = [0.0; 4];
+            for c in 0..4 {
+                out[c] = src[c] + dst[c] * (1.0 - src[3]);
+            }
+            out
+        })
+    }
+    pub fn color_matrix(&self, matrix: &[f32; 20]) -> FilterImage {
+        self.map_pixels(|x, y| {
+            let pixel = self.get(x, y);
+            let alpha = pixel[3];
+            let unpremultiplied = if alpha > 0.0 {
+                [pixel[0] / alpha, pixel[1] / alpha, pixel[2] / alpha, alpha]
+            } else {
+                [0.0; 4]
+            };
+            let mut out = [0.0; 4];
+            for row in 0..4 {
+                let m = &matrix[row * 5..row * 5 + 5];
+                let value = m[0] * unpremultiplied[0]
+                    + m[1] * unpremultiplied[1]
+                    + m[2] * unpremultiplied[2]
+                    + m[3] * unpremultiplied[3]
+                    + m[4];
+                out[row] = value.clamp(0.0, 1.0);
+            }
+            [out[0] * out[3], out[1] * out[3], out[2] * out[3], out[3]]
+        })
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// TESTS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: [f32; 4], expected: [f32; 4]) {
+        let close = actual.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-5);
+        assert!(close, "{:?} != {:?}", actual, expected);
+    }
+
+    /// A `size` x `size` transparent image with one pixel set at its center.
+    fn dot(size: usize, pixel: [f32; 4]) -> FilterImage {
+        let mut image = FilterImage::new(size, size);
+        image.set(size / 2, size / 2, pixel);
+        image
+    }
+
+    #[test]
+    fn gaussian_kernel_weights() {
+        assert_eq!(gaussian_kernel(0.0), vec![1.0]);
+        let kernel = gaussian_kernel(1.0);
+        assert_eq!(kernel.len(), 7);
+        assert!((kernel.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+        // exp(-i² / 2) over its sum, for i in -3..=3.
+        let expected = [0.004432, 0.053991, 0.241971, 0.398943, 0.241971, 0.053991, 0.004432];
+        for (weight, expected) in kernel.iter().zip(expected) {
+            assert!((weight - expected).abs() < 1e-3, "{:?}", kernel);
+        }
+        assert_eq!(gaussian_kernel(2.5).len(), 2 * 8 + 1);
+    }
+
+    #[test]
+    fn blur_spreads_a_dot_by_the_kernel() {
+        let image = dot(15, [1.0; 4]).gaussian_blur(1.0);
+        let kernel = gaussian_kernel(1.0);
+        for (dy, ky) in kernel.iter().enumerate() {
+            for (dx, kx) in kernel.iter().enumerate() {
+                let pixel = image.get(4 + dx as isize, 4 + dy as isize);
+                assert_close(pixel, [kx * ky; 4]);
+            }
+        }
+        assert_close(image.get(0, 0), [0.0; 4]);
+        let total: f32 = image.pixels.iter().map(|pixel| pixel[3]).sum();
+        assert!((total - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn blur_keeps_flat_images_and_clamps_at_edges() {
+        let mut image = FilterImage::new(4, 3);
+        image.pixels.fill([0.25, 0.5, 0.125, 0.5]);
+        let blurred = image.gaussian_blur(2.0);
+        for pixel in blurred.pixels {
+            assert_close(pixel, [0.25, 0.5, 0.125, 0.5]);
+        }
+        assert!(FilterImage::new(0, 0).gaussian_blur(1.0).pixels.is_empty());
+    }
+
+    #[test]
+    fn drop_shadow_offsets_a_premultiplied_tint() {
+        let image = dot(9, [0.0, 0.0, 1.0, 1.0]);
+        // Half transparent red: the shadow must be premultiplied once.
+        let color = RGBA::RED.with_alpha(0.5);
+        let tint = GpuPrimitive::from_u8_rgba(color).color;
+        assert!((tint[0] - tint[3]).abs() < 1e-6);
+        let shadowed = image.drop_shadow((2.0, -1.0), color, 0.0);
+        assert_close(shadowed.get(4, 4), [0.0, 0.0, 1.0, 1.0]);
+        assert_close(shadowed.get(6, 3), tint);
+        let shadow_pixels = shadowed.pixels.iter().filter(|pixel| pixel[3] > 0.0).count();
+        assert_eq!(shadow_pixels, 2);
+    }
+
+    #[test]
+    fn drop_shadow_sits_under_the_source() {
+        let image = dot(9, [0.0, 0.5, 0.0, 0.5]);
+        let shadowed = image.drop_shadow((0.0, 0.0), RGBA::BLACK, 0.0);
+        // Source over an opaque black shadow of its own alpha.
+        assert_close(shadowed.get(4, 4), [0.0, 0.5, 0.0, 0.75]);
+        let blurred = image.drop_shadow((0.0, 0.0), RGBA::BLACK, 1.0);
+        let weight = gaussian_kernel(1.0)[3];
+        let shadow_alpha = 0.5 * weight * weight;
+        assert_close(blurred.get(4, 4), [0.0, 0.5, 0.0, 0.5 + shadow_alpha * 0.5]);
+    }
+
+    #[test]
+    fn color_matrix_on_unpremultiplied_color() {
+        let image = dot(3, [0.5, 0.0, 0.0, 0.5]);
+        assert_eq!(image.color_matrix(&Filter::IDENTITY_COLOR_MATRIX), image);
+        let gray = match Filter::grayscale() {
+            Filter::ColorMatrix(matrix) => image.color_matrix(&matrix),
+            _ => unreachable!(),
+        };
+        let luma = 0.2126 * 0.5;
+        assert_close(gray.get(1, 1), [luma, luma, luma, 0.5]);
+        assert_close(gray.get(0, 0), [0.0; 4]);
+        // Results are clamped before they're premultiplied again.
+        let mut boost = Filter::IDENTITY_COLOR_MATRIX;
+        boost[0] = 4.0;
+        boost[9] = 0.5;
+        assert_close(image.color_matrix(&boost).get(1, 1), [0.5, 0.25, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn apply_scales_picture_units() {
+        let image = dot(9, [1.0; 4]);
+        let filter = Filter::DropShadow { offset: lyon::math::vector(1.0, 1.0), color: RGBA::BLACK, blur_radius: 0.0 };
+        assert_eq!(image.apply(&filter, 2.0), image.drop_shadow((2.0, 2.0), RGBA::BLACK, 0.0));
+        let blur = Filter::GaussianBlur { radius: 0.5 };
+        assert_eq!(image.apply(&blur, 2.0), image.gaussian_blur(1.0));
+    }
+}
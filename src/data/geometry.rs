@@ -7,20 +7,33 @@ use serde::{Serializer, Deserializer, Serialize, Deserialize};
 // GEOMETRY PRIMITIVES
 //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[repr(C)]
 pub struct Point {
     pub x: f32,
     pub y: f32,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 #[repr(C)]
 pub struct Rect {
     pub min: Point,
     pub max: Point,
 }
 
+/// One pointer sample of a pen stroke. Devices without pressure report
+/// `0.5`, as with pointer events.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct InkSample {
+    pub position: Point,
+    /// From `0.0` to `1.0`.
+    pub pressure: f32,
+    /// Pen tilt along x and y in radians, zero when upright.
+    pub tilt: [f32; 2],
+    /// Seconds, from any fixed origin.
+    pub timestamp: f64,
+}
+
 //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
 // HEAP ALLOCATED GEOMETRY TYPES
 //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
@@ -35,4 +48,51 @@ pub struct PointVecRef<'a> {
     pub(super) points: &'a [Point],
 }
 
+/// The samples of a pen stroke, in input order.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InkSampleVec {
+    pub(super) samples: Vec<InkSample>,
+}
+
+impl InkSampleVec {
+    pub fn new() -> Self {
+        InkSampleVec::default()
+    }
+    pub fn push(&mut self, sample: InkSample) {
+        self.samples.push(sample);
+    }
+    pub fn samples(&self) -> &[InkSample] {
+        &self.samples
+    }
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+}
+
+impl FromIterator<InkSample> for InkSampleVec {
+    fn from_iter<T: IntoIterator<Item = InkSample>>(iter: T) -> Self {
+        InkSampleVec { samples: iter.into_iter().collect() }
+    }
+}
+
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// CONVERSIONS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
 
+impl From<Point> for lyon::math::Point {
+    fn from(point: Point) -> Self { lyon::math::point(point.x, point.y) }
+}
+impl From<lyon::math::Point> for Point {
+    fn from(point: lyon::math::Point) -> Self { Point { x: point.x, y: point.y } }
+}
+impl From<Rect> for lyon::math::Box2D {
+    fn from(rect: Rect) -> Self { lyon::math::Box2D::new(rect.min.into(), rect.max.into()) }
+}
+impl From<lyon::math::Box2D> for Rect {
+    fn from(rect: lyon::math::Box2D) -> Self { Rect { min: rect.min.into(), max: rect.max.into() } }
+}
@@ -1,291 +1,607 @@
-// use std::borrow::Cow;
-// use serde::{Serializer, Deserializer, Serialize, Deserialize};
-// use itertools::Itertools;
-// use geo::{ConcaveHull, ConvexHull, Scale, BoundingRect, Intersects, Contains, EuclideanLength, Within};
-// use parry2d::query::PointQuery;
-// // use super::{graphics::*, geometry::*, drawing::edit_tool, drawing::stroke_style};
-
-
-// //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
-// // GEOMETRY PRIMITIVES
-// //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
-
-// impl Point {
-//     pub fn new(x: f32, y: f32) -> Self {
-//         Point { x, y }
-//     }
-//     pub fn x(&self) -> f32 {self.x}
-//     pub fn y(&self) -> f32 {self.y}
-//     pub fn into_geo_coordinate(self) -> geo::Coord {
-//         geo::Coord {x: self.x as f64, y: self.y as f64}
-//     }
-//     pub fn into_point2(self) -> parry2d::na::Point2<f32> {
-//         parry2d::na::Point2::new(self.x, self.y)
-//     }
-//     // pub fn into_skia_point(self) -> skia_safe::Point {
-//     //     skia_safe::Point::new(self.x, self.y)
-//     // }
-//     pub fn is_finite(self) -> bool {
-//         self.x.is_finite() && self.y.is_finite()
-//     }
-//     pub fn total_cmp(self, other: Self) -> std::cmp::Ordering {
-//         let x = self.x.total_cmp(&other.x);
-//         let y = self.y.total_cmp(&other.y);
-//         x.then(y)
-//     }
-//     pub fn apply_linear_scale(&self, scale: LinearScale) -> Self {
-//         Point { x: scale.map(self.x), y: scale.map(self.y)}
-//     }
-//     pub fn scale_x(&self, scale: LinearScale) -> Self {
-//         Point { x: scale.scale(self.x), y: self.y}
-//     }
-//     pub fn scale_y(&self, scale: LinearScale) -> Self {
-//         Point { x: self.x, y: scale.scale(self.y)}
-//     }
-// }
-
-// impl From<geo::Coord> for Point {
-//     fn from(a: geo::Coord) -> Self {Point{x: a.x as f32, y: a.y as f32}}
-// }
-// impl From<parry2d::na::Point2<f32>> for Point {
-//     fn from(a: parry2d::na::Point2<f32>) -> Self {Point{x: a.x, y: a.y}}
-// }
-// // impl From<skia_safe::Point> for Point {
-// //     fn from(a: skia_safe::Point) -> Self {Point{x: a.x, y: a.y}}
-// // }
-// impl From<(f32, f32)> for Point {
-//     fn from((x, y): (f32, f32)) -> Self {Point{x, y}}
-// }
-// impl From<[f32; 2]> for Point {
-//     fn from([x, y]: [f32; 2]) -> Self {Point{x, y}}
-// }
-// impl From<(f64, f64)> for Point {
-//     fn from((x, y): (f64, f64)) -> Self {Point{x: x as f32, y: y as f32}}
-// }
-// impl From<SamplePoint> for Point {
-//     fn from(sample: SamplePoint) -> Self {sample.point}
-// }
-// impl std::ops::Mul for Point {
-//     type Output = Point;
-//     fn mul(self, rhs: Self) -> Self::Output {Point {x: self.x * rhs.x, y: self.y * rhs.y}}
-// }
-// impl std::ops::Mul<f32> for Point {
-//     type Output = Point;
-//     fn mul(self, constant: f32) -> Self::Output {Point {x: self.x * constant, y: self.y * constant}}
-// }
-
-// impl Rect {
-//     pub fn width(&self) -> f32 {
-//         self.max.x - self.min.x
-//     }
-//     pub fn height(&self) -> f32 {
-//         self.max.y - self.min.y
-//     }
-//     // pub fn as_skia_rect(&self) -> skia_safe::Rect {
-//     //     let width = self.width();
-//     //     let height = self.height();
-//     //     let min_x = self.min.x;
-//     //     let min_y = self.min.y;
-//     //     let top_left = skia_safe::Point::new(min_x as f32, min_y as f32);
-//     //     let size = skia_safe::Size::new(width as f32, height as f32);
-//     //     skia_safe::Rect::from_point_and_size(
-//     //         top_left,
-//     //         size
-//     //     )
-//     // }
-//     pub fn contains_point(&self, point: impl Into<Point>) -> bool {
-//         let min = self.min.into_point2();
-//         let max = self.max.into_point2();
-//         let point = point.into().into_point2();
-//         min <= point && point <= max
-//     }
-//     pub fn intersects(&self, other: Rect) -> bool {
-//         let min = self.min.into_point2().coords.sup(&other.min.into_point2().coords);
-//         let max = self.max.into_point2().coords.inf(&other.max.into_point2().coords);
-//         if min.x > max.x || min.y > max.y {
-//             return false;
-//         }
-//         true
-//     }
-// }
-
-// impl FrameSize {
-//     pub fn clamped_width(&self) -> u32 {
-//         if self.width > 0.0 { self.width as u32 } else { 100 }
-//     }
-//     pub fn clamped_height(&self) -> u32 {
-//         if self.height > 0.0 { self.height as u32 } else { 100 }
-//     }
-// }
-
-// //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
-// // HEAP ALLOCATED GEOMETRY TYPES
-// //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
-
-// impl PointVec {
-//     pub fn from_normal_vec(points: Vec<Point>) -> Self {
-//         PointVec {points}
-//     }
-//     pub fn from_iter<T: Into<Point>>(points: impl IntoIterator<Item=T>) -> Self {
-//         PointVec { points: points.into_iter().map(|x| x.into()).collect_vec() }
-//     }
-//     pub fn into_ref<'a>(&'a self) -> PointVecRef<'a> {
-//         PointVecRef {points: &self.points}
-//     }
-//     pub fn push_points(&mut self, other: PointVec) {
-//         self.points.extend(other.points);
-//     }
-//     pub fn from_nested_iter(xs: impl IntoIterator<Item=PointVec>) -> Self {
-//         let points = xs
-//             .into_iter()
-//             .flat_map(|x| x.points)
-//             .collect_vec();
-//         PointVec{points}
-//     }
-//     pub fn is_empty(&self) -> bool {
-//         self.points.is_empty()
-//     }
-//     pub fn map_mut(&mut self, f: impl Fn(&mut Point)) {
-//         self.points
-//             .iter_mut()
-//             .for_each(f)
-//     }
-// }
-
-// pub trait PointVecOps {
-//     fn points(&self) -> &[Point];
-//     fn multiply_by(&self, mul: impl Into<f32>) -> PointVec {
-//         let mul = mul.into();
-//         let points = self.points().into_iter().map(|a| *a * mul).collect_vec();
-//         PointVec{points}
-//     }
-//     fn apply_linear_scale(&self, scale: LinearScale) -> PointVec {
-//         let points = self.points()
-//             .into_iter()
-//             .map(|a| a.apply_linear_scale(scale))
-//             .collect_vec();
-//         PointVec{points}
-//     }
-//     fn into_owned(&self) -> PointVec {
-//         PointVec {points: self.points().to_vec()}
-//     }
-//     fn into_geo_coordinates(&self) -> Vec<geo::Coord> {
-//         self.points().into_iter().copied().map(Point::into_geo_coordinate).collect_vec()
-//     }
-//     fn into_parry2d_points(&self) -> Vec<parry2d::na::Point2<f32>> {
-//         self.points().into_iter().copied().map(Point::into_point2).collect_vec()
-//     }
-//     // fn into_skia_points(&self) -> Vec<skia_safe::Point> {
-//     //     self.points().into_iter().copied().map(Point::into_skia_point).collect_vec()
-//     // }
-//     fn into_parry2d_polyline(&self) -> parry2d::shape::Polyline {
-//         parry2d::shape::Polyline::new(self.into_parry2d_points(), None)
-//     }
-//     fn into_geo_line_string(&self) -> geo::LineString {
-//         geo::LineString::new(self.into_geo_coordinates())
-//     }
-//     fn convex_hull(&self) -> geo::Polygon {
-//         self.into_geo_line_string().convex_hull()
-//     }
-//     fn convex_hull_exterior(&self) -> PointVec {
-//         let points = self
-//             .into_geo_line_string();
-//         let points = points
-//             .convex_hull();
-//         let points = points
-//             .exterior()
-//             .points()
-//             .map(|a| Point{x: a.x() as f32, y: a.y() as f32});
-//         PointVec::from_iter(points)
-//     }
-//     // fn into_sk_polygon(&self) -> skia_safe::Path {
-//     //     let points = self.into_skia_points();
-//     //     skia_safe::Path::polygon(&points, true, None, None)
-//     // }
-//     fn min(&self) -> Option<Point> {
-//         self.points()
-//             .iter()
-//             .copied()
-//             .filter(|a| a.is_finite())
-//             .min_by(|a, b| a.total_cmp(*b))
-//     }
-//     fn max(&self) -> Option<Point> {
-//         self.points()
-//             .into_iter()
-//             .copied()
-//             .filter(|a| a.is_finite())
-//             .max_by(|a, b| a.total_cmp(*b))
-//     }
-//     fn min_x(&self) -> Option<f32> {
-//         self.points()
-//             .into_iter()
-//             .map(Point::x)
-//             .filter(|a| a.is_finite())
-//             .min_by(|a, b| a.total_cmp(&b))
-//     }
-//     fn min_y(&self) -> Option<f32> {
-//         self.points()
-//             .into_iter()
-//             .map(Point::y)
-//             .filter(|a| a.is_finite())
-//             .min_by(|a, b| a.total_cmp(&b))
-//     }
-//     fn max_x(&self) -> Option<f32> {
-//         self.points()
-//             .into_iter()
-//             .map(Point::x)
-//             .filter(|a| a.is_finite())
-//             .max_by(|a, b| a.total_cmp(&b))
-//     }
-//     fn max_y(&self) -> Option<f32> {
-//         self.points()
-//             .into_iter()
-//             .map(Point::y)
-//             .filter(|a| a.is_finite())
-//             .max_by(|a, b| a.total_cmp(&b))
-//     }
-//     fn center_point(&self) -> Point {
-//         let bounds = self.convex_hull_exterior().into_parry2d_polyline();
-//         let bounds = bounds.local_aabb();
-//         bounds.center().to_owned().into()
-//     }
-//     fn aabb_any_point_overlap<T: PointVecOps>(&self, other: &T) -> bool {
-//         let a = self.into_parry2d_polyline();
-//         let a = a.local_aabb();
-//         other
-//             .into_parry2d_points()
-//             .into_iter()
-//             .any(|x| {
-//                 a.contains_local_point(&x)
-//             })
-//     }
-//     fn aabb_contains_point(&self, other: Point) -> bool  {
-//         let bounds = self.convex_hull_exterior().into_parry2d_polyline();
-//         let bounds = bounds.local_aabb();
-//         bounds.contains_local_point(&other.into_point2())
-//     }
-//     fn intersects<T: PointVecOps>(
-//         &self,
-//         other: &T,
-//     ) -> bool {
-//         use geo::algorithm::closest_point::ClosestPoint;
-//         use geo::EuclideanDistance;
-//         let a = self.convex_hull();
-//         let b = other.convex_hull();
-//         let a = a.exterior();
-//         let b = b.exterior();
-//         a.intersects(b)
-//     }
-//     fn into_rect(&self) -> Option<Rect> {
-//         let min = self.min()?;
-//         let max = self.max()?;
-//         Rect {min, max}.into()
-//     }
-// }
-
-// impl PointVecOps for PointVec {
-//     fn points(&self) -> &[Point] {&self.points}
-// }
-// impl PointVecOps for PointVecRef<'_> {
-//     fn points(&self) -> &[Point] {self.points}
-// }
+use crate::data::LinearScale;
+use super::geometry::{Point, PointVec, PointVecRef, Rect};
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// GEOMETRY PRIMITIVES
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+impl Point {
+    pub const fn new(x: f32, y: f32) -> Self {
+        Point { x, y }
+    }
+    pub fn x(&self) -> f32 {self.x}
+    pub fn y(&self) -> f32 {self.y}
+    pub fn is_finite(self) -> bool {
+        self.x.is_finite() && self.y.is_finite()
+    }
+    /// Orders by `x`, then by `y`. A total order, so NaNs sort too.
+    pub fn total_cmp(self, other: Self) -> std::cmp::Ordering {
+        let x = self.x.total_cmp(&other.x);
+        let y = self.y.total_cmp(&other.y);
+        x.then(y)
+    }
+    /// The smaller of each coordinate. A NaN loses to a number.
+    pub fn min(self, other: Self) -> Self {
+        Point { x: self.x.min(other.x), y: self.y.min(other.y) }
+    }
+    /// The larger of each coordinate. A NaN loses to a number.
+    pub fn max(self, other: Self) -> Self {
+        Point { x: self.x.max(other.x), y: self.y.max(other.y) }
+    }
+    pub fn dot(self, other: Self) -> f32 {
+        self.x * other.x + self.y * other.y
+    }
+    /// The z of the 3D cross product: positive when `other` is
+    /// counter-clockwise of `self` with y pointing up.
+    pub fn cross(self, other: Self) -> f32 {
+        self.x * other.y - self.y * other.x
+    }
+    pub fn length(self) -> f32 {
+        self.x.hypot(self.y)
+    }
+    pub fn distance_to(self, other: Self) -> f32 {
+        (other - self).length()
+    }
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+    pub fn apply_linear_scale(&self, scale: LinearScale) -> Self {
+        Point { x: scale.map(self.x), y: scale.map(self.y)}
+    }
+    pub fn scale_x(&self, scale: LinearScale) -> Self {
+        Point { x: scale.scale(self.x), y: self.y}
+    }
+    pub fn scale_y(&self, scale: LinearScale) -> Self {
+        Point { x: self.x, y: scale.scale(self.y)}
+    }
+}
+
+impl From<(f32, f32)> for Point {
+    fn from((x, y): (f32, f32)) -> Self {Point{x, y}}
+}
+impl From<[f32; 2]> for Point {
+    fn from([x, y]: [f32; 2]) -> Self {Point{x, y}}
+}
+impl From<(f64, f64)> for Point {
+    fn from((x, y): (f64, f64)) -> Self {Point{x: x as f32, y: y as f32}}
+}
+impl From<Point> for [f32; 2] {
+    fn from(point: Point) -> Self {[point.x, point.y]}
+}
+impl From<lyon::math::Vector> for Point {
+    fn from(vector: lyon::math::Vector) -> Self {Point{x: vector.x, y: vector.y}}
+}
+impl From<Point> for lyon::math::Vector {
+    fn from(point: Point) -> Self {lyon::math::vector(point.x, point.y)}
+}
+impl std::ops::Add for Point {
+    type Output = Point;
+    fn add(self, rhs: Self) -> Self::Output {Point {x: self.x + rhs.x, y: self.y + rhs.y}}
+}
+impl std::ops::Sub for Point {
+    type Output = Point;
+    fn sub(self, rhs: Self) -> Self::Output {Point {x: self.x - rhs.x, y: self.y - rhs.y}}
+}
+impl std::ops::Neg for Point {
+    type Output = Point;
+    fn neg(self) -> Self::Output {Point {x: -self.x, y: -self.y}}
+}
+/// Multiplies each coordinate, i.e. scales by a different factor per axis.
+impl std::ops::Mul for Point {
+    type Output = Point;
+    fn mul(self, rhs: Self) -> Self::Output {Point {x: self.x * rhs.x, y: self.y * rhs.y}}
+}
+impl std::ops::Mul<f32> for Point {
+    type Output = Point;
+    fn mul(self, constant: f32) -> Self::Output {Point {x: self.x * constant, y: self.y * constant}}
+}
+impl std::ops::Div<f32> for Point {
+    type Output = Point;
+    fn div(self, constant: f32) -> Self::Output {Point {x: self.x / constant, y: self.y / constant}}
+}
+impl std::ops::AddAssign for Point {
+    fn add_assign(&mut self, rhs: Self) {*self = *self + rhs}
+}
+impl std::ops::SubAssign for Point {
+    fn sub_assign(&mut self, rhs: Self) {*self = *self - rhs}
+}
+
+/// Edges are inside: a rect contains its corners, and rects that only touch
+/// intersect. A rect with `min` past `max` on either axis is empty, and
+/// contains and intersects nothing.
+impl Rect {
+    /// The rect spanned by two opposite corners, in any order.
+    pub fn new(a: impl Into<Point>, b: impl Into<Point>) -> Self {
+        let (a, b) = (a.into(), b.into());
+        Rect { min: a.min(b), max: a.max(b) }
+    }
+    pub fn from_origin_and_size(origin: impl Into<Point>, width: f32, height: f32) -> Self {
+        let origin = origin.into();
+        Rect::new(origin, origin + Point::new(width, height))
+    }
+    pub fn width(&self) -> f32 {
+        self.max.x - self.min.x
+    }
+    pub fn height(&self) -> f32 {
+        self.max.y - self.min.y
+    }
+    pub fn center(&self) -> Point {
+        self.min.lerp(self.max, 0.5)
+    }
+    /// Also true with a NaN on either corner. Zero sized rects aren't empty.
+    pub fn is_empty(&self) -> bool {
+        !(self.min.x <= self.max.x && self.min.y <= self.max.y)
+    }
+    pub fn contains_point(&self, point: impl Into<Point>) -> bool {
+        let point = point.into();
+        self.min.x <= point.x && point.x <= self.max.x && self.min.y <= point.y && point.y <= self.max.y
+    }
+    pub fn contains_rect(&self, other: Rect) -> bool {
+        !other.is_empty() && self.contains_point(other.min) && self.contains_point(other.max)
+    }
+    pub fn intersects(&self, other: Rect) -> bool {
+        self.intersection(other).is_some()
+    }
+    /// The overlap, which has zero width or height when the rects only
+    /// touch.
+    pub fn intersection(&self, other: Rect) -> Option<Rect> {
+        let overlap = Rect { min: self.min.max(other.min), max: self.max.min(other.max) };
+        let valid = !self.is_empty() && !other.is_empty() && !overlap.is_empty();
+        valid.then_some(overlap)
+    }
+    /// The smallest rect around both. An empty rect adds nothing.
+    pub fn union(&self, other: Rect) -> Rect {
+        match (self.is_empty(), other.is_empty()) {
+            (true, _) => other,
+            (false, true) => *self,
+            (false, false) => Rect { min: self.min.min(other.min), max: self.max.max(other.max) },
+        }
+    }
+    /// Grows each side by `amount`, or shrinks it when negative.
+    pub fn inflate(&self, amount: f32) -> Rect {
+        let amount = Point::new(amount, amount);
+        Rect { min: self.min - amount, max: self.max + amount }
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// HEAP ALLOCATED GEOMETRY TYPES
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+impl PointVec {
+    pub fn new() -> Self {
+        PointVec { points: Vec::new() }
+    }
+    pub fn from_normal_vec(points: Vec<Point>) -> Self {
+        PointVec {points}
+    }
+    pub fn to_ref(&self) -> PointVecRef<'_> {
+        PointVecRef {points: &self.points}
+    }
+    /// The original name of `to_ref`.
+    #[allow(clippy::wrong_self_convention)]
+    pub fn into_ref(&self) -> PointVecRef<'_> {
+        self.to_ref()
+    }
+    pub fn push(&mut self, point: impl Into<Point>) {
+        self.points.push(point.into());
+    }
+    pub fn push_points(&mut self, other: PointVec) {
+        self.points.extend(other.points);
+    }
+    pub fn from_nested_iter(xs: impl IntoIterator<Item=PointVec>) -> Self {
+        let points = xs
+            .into_iter()
+            .flat_map(|x| x.points)
+            .collect();
+        PointVec{points}
+    }
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+    pub fn map_mut(&mut self, f: impl FnMut(&mut Point)) {
+        self.points
+            .iter_mut()
+            .for_each(f)
+    }
+}
+
+impl Default for PointVec {
+    fn default() -> Self {
+        PointVec::new()
+    }
+}
+
+impl<T: Into<Point>> FromIterator<T> for PointVec {
+    fn from_iter<I: IntoIterator<Item = T>>(points: I) -> Self {
+        PointVec { points: points.into_iter().map(Into::into).collect() }
+    }
+}
+
+impl<'a> PointVecRef<'a> {
+    pub fn new(points: &'a [Point]) -> Self {
+        PointVecRef { points }
+    }
+}
+
+impl<'a> From<&'a PointVec> for PointVecRef<'a> {
+    fn from(points: &'a PointVec) -> Self {
+        points.to_ref()
+    }
+}
+
+/// Queries shared by owned and borrowed point lists, read as a polyline.
+///
+/// Points with a NaN or infinite coordinate, e.g. from a dropped pointer
+/// sample, are skipped by every query rather than poisoning its result.
+pub trait PointVecOps {
+    fn points(&self) -> &[Point];
+    fn finite_points(&self) -> Box<dyn Iterator<Item = Point> + '_> {
+        Box::new(self.points().iter().copied().filter(|point| point.is_finite()))
+    }
+    fn multiply_by(&self, mul: impl Into<f32>) -> PointVec {
+        let mul = mul.into();
+        self.points().iter().map(|a| *a * mul).collect()
+    }
+    fn apply_linear_scale(&self, scale: LinearScale) -> PointVec {
+        self.points().iter().map(|a| a.apply_linear_scale(scale)).collect()
+    }
+    fn to_point_vec(&self) -> PointVec {
+        PointVec {points: self.points().to_vec()}
+    }
+    /// The original name of `to_point_vec`.
+    #[allow(clippy::wrong_self_convention)]
+    fn into_owned(&self) -> PointVec {
+        self.to_point_vec()
+    }
+    /// The smallest `x` and the smallest `y`, which needn't be the same
+    /// point's.
+    fn min(&self) -> Option<Point> {
+        self.finite_points().reduce(Point::min)
+    }
+    /// The largest `x` and the largest `y`, which needn't be the same
+    /// point's.
+    fn max(&self) -> Option<Point> {
+        self.finite_points().reduce(Point::max)
+    }
+    fn min_x(&self) -> Option<f32> {
+        self.min().map(|point| point.x)
+    }
+    fn min_y(&self) -> Option<f32> {
+        self.min().map(|point| point.y)
+    }
+    fn max_x(&self) -> Option<f32> {
+        self.max().map(|point| point.x)
+    }
+    fn max_y(&self) -> Option<f32> {
+        self.max().map(|point| point.y)
+    }
+    fn bounds(&self) -> Option<Rect> {
+        Some(Rect { min: self.min()?, max: self.max()? })
+    }
+    /// The original name of `bounds`.
+    #[allow(clippy::wrong_self_convention)]
+    fn into_rect(&self) -> Option<Rect> {
+        self.bounds()
+    }
+    /// The center of `bounds`, which is also that of the convex hull's. See
+    /// `centroid` for the center of mass.
+    fn center_point(&self) -> Option<Point> {
+        self.bounds().map(|bounds| bounds.center())
+    }
+    /// Whether `point` is within `bounds`, edges included.
+    fn aabb_contains_point(&self, point: Point) -> bool {
+        self.bounds().is_some_and(|bounds| bounds.contains_point(point))
+    }
+    /// Whether any finite point of `other` is within `bounds`, edges
+    /// included.
+    fn aabb_any_point_overlap<T: PointVecOps + ?Sized>(&self, other: &T) -> bool {
+        match self.bounds() {
+            Some(bounds) => other.finite_points().any(|point| bounds.contains_point(point)),
+            None => false,
+        }
+    }
+    /// Total length of the polyline's segments.
+    fn length(&self) -> f32 {
+        let points = self.finite_points().collect::<Vec<_>>();
+        points.windows(2).map(|pair| pair[0].distance_to(pair[1])).sum()
+    }
+    /// The hull's corners, starting from the leftmost, counter-clockwise
+    /// when y points up (so clockwise on a y-down screen), with no point
+    /// repeated. Points on the hull's edges aren't corners. Fewer than three
+    /// corners when every point is on one line.
+    fn convex_hull(&self) -> PointVec {
+        let mut points = self.finite_points().collect::<Vec<_>>();
+        points.sort_by(|a, b| a.total_cmp(*b));
+        points.dedup();
+        if points.len() < 3 {
+            return PointVec::from_normal_vec(points)
+        }
+        // Andrew's monotone chain: the lower hull left to right, then the
+        // upper hull back, each keeping only left turns.
+        let mut hull: Vec<Point> = Vec::with_capacity(points.len() + 1);
+        for point in points.iter() {
+            while hull.len() >= 2 && orientation(hull[hull.len() - 2], hull[hull.len() - 1], *point) <= 0.0 {
+                hull.pop();
+            }
+            hull.push(*point);
+        }
+        let lower = hull.len() + 1;
+        for point in points.iter().rev().skip(1) {
+            while hull.len() >= lower && orientation(hull[hull.len() - 2], hull[hull.len() - 1], *point) <= 0.0 {
+                hull.pop();
+            }
+            hull.push(*point);
+        }
+        // The last point is the first one again.
+        hull.pop();
+        if hull.len() < 3 {
+            // Every point was on one line; keep its ends.
+            hull = vec![points[0], points[points.len() - 1]];
+        }
+        PointVec::from_normal_vec(hull)
+    }
+    /// The center of mass of the polygon the points enclose, closing it back
+    /// to the first point. When it encloses no area, the center of the
+    /// polyline's segments by length, and when those have no length either,
+    /// the average point. `None` without finite points.
+    fn centroid(&self) -> Option<Point> {
+        let points = self.finite_points().collect::<Vec<_>>();
+        let first = *points.first()?;
+        // Relative to the first point, in f64, to keep large coordinates
+        // from cancelling out.
+        let relative = points
+            .iter()
+            .map(|point| ((point.x - first.x) as f64, (point.y - first.y) as f64))
+            .collect::<Vec<_>>();
+        let to_point = |(x, y): (f64, f64)| Point::new(first.x + x as f32, first.y + y as f32);
+        let mut area = 0.0;
+        let (mut x, mut y) = (0.0, 0.0);
+        for ix in 0..relative.len() {
+            let (a, b) = (relative[ix], relative[(ix + 1) % relative.len()]);
+            let cross = a.0 * b.1 - b.0 * a.1;
+            area += cross;
+            x += (a.0 + b.0) * cross;
+            y += (a.1 + b.1) * cross;
+        }
+        let extent = relative.iter().fold(0.0f64, |extent, (x, y)| extent.max(x.abs()).max(y.abs()));
+        if area.abs() > extent * extent * 1e-9 {
+            return Some(to_point((x / (3.0 * area), y / (3.0 * area))))
+        }
+        let mut length = 0.0;
+        let (mut x, mut y) = (0.0, 0.0);
+        for pair in relative.windows(2) {
+            let segment = (pair[1].0 - pair[0].0).hypot(pair[1].1 - pair[0].1);
+            length += segment;
+            x += (pair[0].0 + pair[1].0) / 2.0 * segment;
+            y += (pair[0].1 + pair[1].1) / 2.0 * segment;
+        }
+        if length > 0.0 {
+            return Some(to_point((x / length, y / length)))
+        }
+        Some(first)
+    }
+    /// Where the two polylines cross or touch: one point per pair of
+    /// segments that meet, in the order of this polyline's segments. A single
+    /// point counts as a segment of no length.
+    fn intersections<T: PointVecOps + ?Sized>(&self, other: &T) -> Vec<Point> {
+        let (a, b) = (segments(self), segments(other));
+        let mut found = Vec::new();
+        let (a_bounds, b_bounds) = match (self.bounds(), other.bounds()) {
+            (Some(a_bounds), Some(b_bounds)) if a_bounds.intersects(b_bounds) => (a_bounds, b_bounds),
+            _ => return found,
+        };
+        for segment in a.iter().filter(|segment| b_bounds.intersects(Rect::new(segment[0], segment[1]))) {
+            for other in b.iter().filter(|other| a_bounds.intersects(Rect::new(other[0], other[1]))) {
+                if let Some(point) = segment_intersection(*segment, *other) {
+                    found.push(point);
+                }
+            }
+        }
+        found
+    }
+    /// Whether the two polylines cross or touch.
+    fn intersects<T: PointVecOps + ?Sized>(&self, other: &T) -> bool {
+        let (a, b) = (segments(self), segments(other));
+        a.iter().any(|segment| b.iter().any(|other| segment_intersection(*segment, *other).is_some()))
+    }
+    /// Whether `point` is inside the convex hull, edges included.
+    fn hull_contains_point(&self, point: Point) -> bool {
+        let hull = self.convex_hull();
+        match hull.points.len() {
+            0 => false,
+            1 | 2 => segments(&hull).iter().any(|segment| segment_intersection(*segment, [point, point]).is_some()),
+            count => (0..count).all(|ix| orientation(hull.points[ix], hull.points[(ix + 1) % count], point) >= 0.0),
+        }
+    }
+}
+
+impl PointVecOps for PointVec {
+    fn points(&self) -> &[Point] {&self.points}
+}
+impl PointVecOps for PointVecRef<'_> {
+    fn points(&self) -> &[Point] {self.points}
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// SEGMENTS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// Twice the signed area of `a`, `b`, `c`: positive when they turn
+/// counter-clockwise with y pointing up, zero when they're on a line.
+/// Computed in f64 so nearly collinear points still get the right sign.
+fn orientation(a: Point, b: Point, c: Point) -> f64 {
+    let (abx, aby) = (b.x as f64 - a.x as f64, b.y as f64 - a.y as f64);
+    let (acx, acy) = (c.x as f64 - a.x as f64, c.y as f64 - a.y as f64);
+    abx * acy - aby * acx
+}
+
+/// The polyline's segments, skipping non-finite points. A lone point is a
+/// segment from itself to itself.
+fn segments<T: PointVecOps + ?Sized>(points: &T) -> Vec<[Point; 2]> {
+    let points = points.finite_points().collect::<Vec<_>>();
+    match points.len() {
+        1 => vec![[points[0], points[0]]],
+        _ => points.windows(2).map(|pair| [pair[0], pair[1]]).collect(),
+    }
+}
+
+/// Whether `point`, known to be on the line through `segment`, is within it.
+fn within(segment: [Point; 2], point: Point) -> bool {
+    Rect::new(segment[0], segment[1]).contains_point(point)
+}
+
+/// A point where the segments meet, ends included. Collinear segments that
+/// overlap meet at the first end of one that lies on the other.
+fn segment_intersection(a: [Point; 2], b: [Point; 2]) -> Option<Point> {
+    let [p, q] = a;
+    let [r, s] = b;
+    let (o1, o2) = (orientation(p, q, r), orientation(p, q, s));
+    let (o3, o4) = (orientation(r, s, p), orientation(r, s, q));
+    if o1 * o2 < 0.0 && o3 * o4 < 0.0 {
+        // A proper crossing, so the denominator isn't zero.
+        let t = o3 / (o3 - o4);
+        let point = Point::new(
+            (p.x as f64 + (q.x as f64 - p.x as f64) * t) as f32,
+            (p.y as f64 + (q.y as f64 - p.y as f64) * t) as f32,
+        );
+        return Some(point)
+    }
+    [(o1, a, r), (o2, a, s), (o3, b, p), (o4, b, q)]
+        .into_iter()
+        .find(|(orientation, segment, point)| *orientation == 0.0 && within(*segment, *point))
+        .map(|(_, _, point)| point)
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// TESTS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(points: &[(f32, f32)]) -> PointVec {
+        points.iter().copied().collect()
+    }
+
+    fn assert_near(a: Point, b: Point) {
+        assert!(a.distance_to(b) < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn queries_skip_non_finite_points() {
+        let line = points(&[(0.0, 0.0), (f32::NAN, 5.0), (4.0, f32::INFINITY), (4.0, 0.0), (f32::NEG_INFINITY, -9.0), (4.0, 3.0)]);
+        assert_eq!(line.min(), Some(Point::new(0.0, 0.0)));
+        assert_eq!(line.max(), Some(Point::new(4.0, 3.0)));
+        assert_eq!(line.bounds(), Some(Rect::new((0.0, 0.0), (4.0, 3.0))));
+        assert_eq!(line.length(), 7.0);
+        assert_near(line.centroid().unwrap(), Point::new(8.0 / 3.0, 1.0));
+
+        let nothing = points(&[(f32::NAN, 0.0), (0.0, f32::INFINITY)]);
+        assert_eq!(nothing.min(), None);
+        assert_eq!(nothing.max(), None);
+        assert_eq!(nothing.bounds(), None);
+        assert_eq!(nothing.length(), 0.0);
+        assert_eq!(nothing.centroid(), None);
+        assert!(nothing.convex_hull().is_empty());
+    }
+
+    #[test]
+    fn centroid_falls_back_without_area() {
+        // Collinear: the segments' center by length.
+        assert_near(points(&[(0.0, 0.0), (2.0, 0.0), (6.0, 0.0)]).centroid().unwrap(), Point::new(3.0, 0.0));
+        // No length either: the first point.
+        assert_eq!(points(&[(1.0, 1.0), (1.0, 1.0)]).centroid(), Some(Point::new(1.0, 1.0)));
+        // Far from the origin, a square still finds its center.
+        let far = points(&[(1e6, 1e6), (1e6 + 2.0, 1e6), (1e6 + 2.0, 1e6 + 2.0), (1e6, 1e6 + 2.0)]);
+        assert_near(far.centroid().unwrap(), Point::new(1e6 + 1.0, 1e6 + 1.0));
+    }
+
+    #[test]
+    fn convex_hull_drops_duplicates_and_edge_points() {
+        let square = points(&[(0.0, 0.0), (2.0, 0.0), (1.0, 0.0), (2.0, 2.0), (0.0, 2.0), (0.0, 0.0), (2.0, 2.0), (1.0, 1.0), (0.0, 1.0)]);
+        assert_eq!(square.convex_hull().points(), points(&[(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)]).points());
+        let collinear = points(&[(3.0, 3.0), (1.0, 1.0), (0.0, 0.0), (2.0, 2.0), (1.0, 1.0), (f32::NAN, 1.0)]);
+        assert_eq!(collinear.convex_hull().points(), points(&[(0.0, 0.0), (3.0, 3.0)]).points());
+        assert_eq!(points(&[(1.0, 1.0), (1.0, 1.0), (1.0, 1.0)]).convex_hull().points(), [Point::new(1.0, 1.0)]);
+        assert!(square.hull_contains_point(Point::new(2.0, 1.0)));
+        assert!(!square.hull_contains_point(Point::new(2.1, 1.0)));
+        assert!(collinear.hull_contains_point(Point::new(1.5, 1.5)));
+        assert!(!collinear.hull_contains_point(Point::new(1.5, 1.6)));
+    }
+
+    #[test]
+    fn rects_that_touch_intersect_and_empty_rects_dont() {
+        let a = Rect::new((0.0, 0.0), (1.0, 1.0));
+        let beside = Rect::new((1.0, 0.0), (2.0, 1.0));
+        let corner = Rect::new((1.0, 1.0), (2.0, 2.0));
+        let apart = Rect::new((1.5, 0.0), (2.0, 1.0));
+        let empty = Rect { min: Point::new(0.8, 0.8), max: Point::new(0.2, 0.2) };
+        let nan = Rect { min: Point::new(f32::NAN, 0.0), max: Point::new(1.0, 1.0) };
+
+        assert!(empty.is_empty() && nan.is_empty());
+        assert!(!Rect::new((1.0, 1.0), (1.0, 1.0)).is_empty());
+        assert_eq!(a.intersection(beside), Some(Rect::new((1.0, 0.0), (1.0, 1.0))));
+        assert_eq!(a.intersection(corner), Some(Rect::new((1.0, 1.0), (1.0, 1.0))));
+        assert_eq!(a.intersection(apart), None);
+        assert_eq!(a.intersection(empty), None);
+        assert_eq!(empty.intersection(a), None);
+        assert_eq!(a.intersection(nan), None);
+        assert!(!a.contains_rect(empty));
+
+        assert_eq!(a.union(beside), Rect::new((0.0, 0.0), (2.0, 1.0)));
+        assert_eq!(a.union(empty), a);
+        assert_eq!(empty.union(a), a);
+        assert_eq!(nan.union(a), a);
+        assert!(empty.union(empty).is_empty());
+    }
+
+    #[test]
+    fn segments_meet_at_ends_and_overlaps() {
+        let p = Point::new;
+        // Collinear overlap: the first end of one that lies on the other.
+        assert_eq!(segment_intersection([p(0.0, 0.0), p(2.0, 0.0)], [p(1.0, 0.0), p(3.0, 0.0)]), Some(p(1.0, 0.0)));
+        assert_eq!(segment_intersection([p(1.0, 0.0), p(3.0, 0.0)], [p(0.0, 0.0), p(2.0, 0.0)]), Some(p(2.0, 0.0)));
+        assert_eq!(segment_intersection([p(0.0, 0.0), p(1.0, 0.0)], [p(2.0, 0.0), p(3.0, 0.0)]), None);
+        // Only the ends touch.
+        assert_eq!(segment_intersection([p(0.0, 0.0), p(1.0, 1.0)], [p(1.0, 1.0), p(2.0, 0.0)]), Some(p(1.0, 1.0)));
+        assert_eq!(segment_intersection([p(0.0, 0.0), p(2.0, 0.0)], [p(1.0, 0.0), p(1.0, 5.0)]), Some(p(1.0, 0.0)));
+        assert_eq!(segment_intersection([p(0.0, 0.0), p(2.0, 0.0)], [p(1.0, 0.001), p(1.0, 5.0)]), None);
+        // A point on a segment, and a proper crossing.
+        assert_eq!(segment_intersection([p(0.0, 0.0), p(2.0, 2.0)], [p(1.0, 1.0), p(1.0, 1.0)]), Some(p(1.0, 1.0)));
+        assert_eq!(segment_intersection([p(0.0, 0.0), p(2.0, 2.0)], [p(0.0, 2.0), p(2.0, 0.0)]), Some(p(1.0, 1.0)));
+
+        let zigzag = points(&[(0.0, 0.0), (2.0, 2.0), (4.0, 0.0)]);
+        let floor = points(&[(0.0, 0.0), (4.0, 0.0)]);
+        assert_eq!(zigzag.intersections(&floor), vec![p(0.0, 0.0), p(4.0, 0.0)]);
+        assert!(!zigzag.intersects(&points(&[(0.0, -1.0), (4.0, -1.0)])));
+    }
+
+    #[test]
+    fn original_names_still_work() {
+        let line = points(&[(0.0, 0.0), (4.0, 0.0), (4.0, 2.0)]);
+        assert_eq!(line.into_ref().points(), line.points());
+        assert_eq!(line.into_ref().into_owned().points(), line.points());
+        assert_eq!(line.into_rect(), line.bounds());
+        let line = points(&[(0.0, 0.0), (4.0, 0.0), (f32::NAN, 1.0), (4.0, 2.0)]);
+        // The middle of the bounds, not the center of mass.
+        assert_eq!(line.center_point(), Some(Point::new(2.0, 1.0)));
+        assert_ne!(line.center_point(), line.centroid());
+        assert!(line.aabb_contains_point(Point::new(4.0, 2.0)));
+        assert!(line.aabb_contains_point(Point::new(1.0, 1.0)));
+        assert!(!line.aabb_contains_point(Point::new(4.5, 1.0)));
+        assert!(line.aabb_any_point_overlap(&points(&[(9.0, 9.0), (3.0, 1.0)])));
+        assert!(!line.aabb_any_point_overlap(&points(&[(9.0, 9.0), (f32::NAN, 1.0)])));
+        let nothing = points(&[(f32::NAN, 0.0)]);
+        assert_eq!(nothing.center_point(), None);
+        assert!(!nothing.aabb_contains_point(Point::new(0.0, 0.0)));
+        assert!(!nothing.aabb_any_point_overlap(&line));
+    }
+}
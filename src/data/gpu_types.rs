@@ -18,10 +18,11 @@ pub struct GpuPrimitive {
 }
 
 impl GpuPrimitive {
+    /// Uploads linear, premultiplied colors, which is what the pipelines
+    /// blend.
     pub fn from_u8_rgba(color: super::RGBA<u8>) -> Self {
-        let super::RGBA{red, green, blue, alpha} = color.to_f32();
         GpuPrimitive {
-            color: [red, green, blue, alpha],
+            color: color.to_premultiplied(),
         }
     }
 }
@@ -30,7 +31,46 @@ impl GpuPrimitive {
 #[derive(Copy, Clone, Debug)]
 pub struct GpuGlobals {
     pub picture_resolution: [f32; 2],
-    pub _pad: f32,
+    pub _pad: [f32; 2],
+    /// Picture space to clip space, from `Camera::view_matrix`.
+    pub view_matrix: [[f32; 4]; 4],
+}
+
+/// Per-instance data of an instanced symbol draw. `transform` holds the
+/// columns of a 2D affine transform, and `color` multiplies the symbol's
+/// colors. `prim_id` is the instancing op's own primitive, written by the
+/// picking pass in place of the symbol's.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct GpuInstance {
+    pub transform: [[f32; 2]; 3],
+    pub color: [f32; 4],
+    pub prim_id: u32,
+}
+
+/// A corner of the quad that composites a group's offscreen texture onto its
+/// parent target.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct GpuGroupVertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    pub opacity: f32,
+}
+
+/// Uniforms for one filter pass. Only the fields used by the pass's stage are
+/// meaningful. Offsets and directions are in pixels.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct GpuFilterParams {
+    pub color_matrix: [[f32; 4]; 4],
+    pub color_offset: [f32; 4],
+    pub color: [f32; 4],
+    pub direction: [i32; 2],
+    pub offset: [i32; 2],
+    pub sigma: f32,
+    pub radius: i32,
+    pub _pad: [f32; 2],
 }
 
 impl FillVertexConstructor<GpuVertex> for super::VertexConstructor {
@@ -57,4 +97,10 @@ unsafe impl bytemuck::Pod for GpuVertex {}
 unsafe impl bytemuck::Zeroable for GpuVertex {}
 unsafe impl bytemuck::Pod for GpuPrimitive {}
 unsafe impl bytemuck::Zeroable for GpuPrimitive {}
+unsafe impl bytemuck::Pod for GpuInstance {}
+unsafe impl bytemuck::Zeroable for GpuInstance {}
+unsafe impl bytemuck::Pod for GpuGroupVertex {}
+unsafe impl bytemuck::Zeroable for GpuGroupVertex {}
+unsafe impl bytemuck::Pod for GpuFilterParams {}
+unsafe impl bytemuck::Zeroable for GpuFilterParams {}
 
@@ -0,0 +1,477 @@
+use lyon::math::{Box2D, Point, Transform};
+use lyon::geom::LineSegment;
+use lyon::path::{Event, FillRule, Path};
+use lyon::path::iterator::PathIterator;
+use lyon::algorithms::hit_test::hit_test_path;
+use crate::data::draw_cmds::*;
+use crate::data::picture::Picture;
+use crate::data::spatial::{self, RTree};
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// HIT TEST INDEX
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// Answers "what is under this point?" for a list of draw ops, in picture
+/// space. Results are indices into the op list, topmost (last drawn) first.
+/// A group is hit when any of its children is, and an instances op when any
+/// of its instances is.
+///
+/// The outlines are flattened once up front and kept in an R-tree, so
+/// build this once per picture change rather than per query.
+#[derive(Debug, Clone)]
+pub struct HitTestIndex {
+    shapes: Vec<HitShape>,
+    /// The shapes of each op, which are contiguous.
+    op_shapes: Vec<std::ops::Range<usize>>,
+    tree: RTree<usize>,
+}
+
+/// Which ops a marquee selects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarqueeMode {
+    /// Any part of the op's fill or stroke is inside the rectangle.
+    Touching,
+    /// The op's fill and stroke are entirely inside the rectangle.
+    Enclosed,
+}
+
+#[derive(Debug, Clone)]
+struct HitShape {
+    op_index: usize,
+    bounds: Box2D,
+    geometry: HitGeometry,
+}
+
+#[derive(Debug, Clone)]
+enum HitGeometry {
+    Fill {
+        path: Path,
+        fill_rule: FillRule,
+        tolerance: f32,
+        edges: Vec<LineSegment<f32>>,
+    },
+    /// Dash gaps are treated as part of the stroke, so dashed lines are as
+    /// easy to pick as solid ones.
+    Stroke {
+        segments: Vec<StrokeSegment>,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct StrokeSegment {
+    line: LineSegment<f32>,
+    half_widths: (f32, f32),
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// BUILD
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+impl HitTestIndex {
+    pub fn new<'a>(ops: impl IntoIterator<Item = &'a DrawOp>) -> Self {
+        let mut shapes = Vec::new();
+        let mut op_shapes = Vec::new();
+        for (op_index, op) in ops.into_iter().enumerate() {
+            let first = shapes.len();
+            collect_shapes(op_index, op, &mut shapes);
+            op_shapes.push(first..shapes.len());
+        }
+        let tree = RTree::new(shapes.iter().enumerate().map(|(ix, shape)| (shape.bounds, ix)));
+        HitTestIndex { shapes, op_shapes, tree }
+    }
+}
+
+fn collect_shapes(op_index: usize, op: &DrawOp, shapes: &mut Vec<HitShape>) {
+    let mut push = |geometry: Option<(Box2D, HitGeometry)>| {
+        if let Some((bounds, geometry)) = geometry {
+            shapes.push(HitShape { op_index, bounds, geometry });
+        }
+    };
+    match op {
+        DrawOp::Fill(op) => push(fill_geometry(&op.path, &op.fill_settings)),
+        DrawOp::Stroke(op) => push(stroke_geometry(&op.path, &op.stroke_settings)),
+        DrawOp::FillStroke(op) => {
+            push(fill_geometry(&op.path, &op.fill_settings));
+            match op.stroke_alignment {
+                StrokeAlignment::Center => push(stroke_geometry(&op.path, &op.stroke_settings)),
+                StrokeAlignment::Inside | StrokeAlignment::Outside => {
+                    let tolerance = op.stroke_settings.tolerance;
+                    push(fill_geometry(&op.stroke_outline(tolerance), &lyon::tessellation::FillOptions::tolerance(tolerance)));
+                }
+            }
+        }
+        DrawOp::Group(group) => {
+            for item in group.items.iter() {
+                collect_shapes(op_index, item, shapes);
+            }
+        }
+        DrawOp::Instances(op) => {
+            let mut symbol_shapes = Vec::new();
+            collect_shapes(op_index, op.symbol.shape(), &mut symbol_shapes);
+            for instance in op.instances.iter() {
+                shapes.extend(symbol_shapes.iter().filter_map(|shape| shape.transformed(&instance.transform)));
+            }
+        }
+    }
+}
+
+fn fill_geometry(path: &Path, options: &lyon::tessellation::FillOptions) -> Option<(Box2D, HitGeometry)> {
+    let mut edges = Vec::new();
+    for event in path.iter().flattened(options.tolerance) {
+        match event {
+            Event::Line { from, to } => edges.push(LineSegment { from, to }),
+            // Fills are implicitly closed.
+            Event::End { last, first, .. } if last != first => edges.push(LineSegment { from: last, to: first }),
+            _ => {}
+        }
+    }
+    let geometry = HitGeometry::Fill {
+        path: path.clone(),
+        fill_rule: options.fill_rule,
+        tolerance: options.tolerance,
+        edges,
+    };
+    Some((geometry.bounds()?, geometry))
+}
+
+fn stroke_geometry(path: &Path, options: &lyon::tessellation::StrokeOptions) -> Option<(Box2D, HitGeometry)> {
+    let half_width = |attributes: &[f32]| match options.variable_line_width {
+        Some(ix) => options.line_width * attributes[ix] * 0.5,
+        None => options.line_width * 0.5,
+    };
+    let mut segments = Vec::new();
+    let mut push = |line: &LineSegment<f32>, t: std::ops::Range<f32>, widths: (f32, f32)| {
+        let lerp = |t: f32| widths.0 + (widths.1 - widths.0) * t;
+        segments.push(StrokeSegment { line: *line, half_widths: (lerp(t.start), lerp(t.end)) });
+    };
+    let mut subpath_is_empty = true;
+    for event in path.iter_with_attributes() {
+        match event {
+            Event::Begin { .. } => {
+                subpath_is_empty = true;
+            }
+            Event::Line { from, to } => {
+                push(&LineSegment { from: from.0, to: to.0 }, 0.0..1.0, (half_width(from.1), half_width(to.1)));
+                subpath_is_empty = false;
+            }
+            Event::Quadratic { from, ctrl, to } => {
+                let widths = (half_width(from.1), half_width(to.1));
+                lyon::geom::QuadraticBezierSegment { from: from.0, ctrl, to: to.0 }
+                    .for_each_flattened_with_t(options.tolerance, &mut |line, t| push(line, t, widths));
+                subpath_is_empty = false;
+            }
+            Event::Cubic { from, ctrl1, ctrl2, to } => {
+                let widths = (half_width(from.1), half_width(to.1));
+                lyon::geom::CubicBezierSegment { from: from.0, ctrl1, ctrl2, to: to.0 }
+                    .for_each_flattened_with_t(options.tolerance, &mut |line, t| push(line, t, widths));
+                subpath_is_empty = false;
+            }
+            Event::End { last, first, close } => {
+                let widths = (half_width(last.1), half_width(first.1));
+                if close {
+                    push(&LineSegment { from: last.0, to: first.0 }, 0.0..1.0, widths);
+                } else if subpath_is_empty && options.start_cap != lyon::path::LineCap::Butt {
+                    // A lone point is drawn as just its caps.
+                    push(&LineSegment { from: first.0, to: first.0 }, 0.0..1.0, widths);
+                }
+            }
+        }
+    }
+    let geometry = HitGeometry::Stroke { segments };
+    Some((geometry.bounds()?, geometry))
+}
+
+impl HitGeometry {
+    fn bounds(&self) -> Option<Box2D> {
+        match self {
+            HitGeometry::Fill { edges, .. } => edges
+                .iter()
+                .map(|edge| Box2D::from_points([edge.from, edge.to]))
+                .reduce(|a, b| spatial::union(&a, &b)),
+            HitGeometry::Stroke { segments } => segments
+                .iter()
+                .map(|segment| {
+                    let outset = segment.half_widths.0.max(segment.half_widths.1);
+                    Box2D::from_points([segment.line.from, segment.line.to])
+                        .inflate(outset, outset)
+                })
+                .reduce(|a, b| spatial::union(&a, &b)),
+        }
+    }
+}
+
+impl HitShape {
+    /// The shape as placed by a symbol instance. Stroke widths are scaled by
+    /// the transform's mean scale factor.
+    fn transformed(&self, transform: &Transform) -> Option<HitShape> {
+        let geometry = match &self.geometry {
+            HitGeometry::Fill { path, fill_rule, tolerance, edges } => HitGeometry::Fill {
+                path: path.clone().transformed(transform),
+                fill_rule: *fill_rule,
+                tolerance: *tolerance,
+                edges: edges.iter().map(|edge| edge.transformed(transform)).collect(),
+            },
+            HitGeometry::Stroke { segments } => {
+                let scale = transform.determinant().abs().sqrt();
+                HitGeometry::Stroke {
+                    segments: segments
+                        .iter()
+                        .map(|segment| StrokeSegment {
+                            line: segment.line.transformed(transform),
+                            half_widths: (segment.half_widths.0 * scale, segment.half_widths.1 * scale),
+                        })
+                        .collect(),
+                }
+            }
+        };
+        Some(HitShape { op_index: self.op_index, bounds: geometry.bounds()?, geometry })
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// QUERIES
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+impl HitTestIndex {
+    /// The topmost op whose fill contains `point`, or whose stroke is within
+    /// `tolerance` of it. `tolerance` is in picture units, and also applies to
+    /// the outline of fills.
+    pub fn hit_test(&self, point: Point, tolerance: f32) -> Option<usize> {
+        let mut candidates = self.candidates(&Box2D::new(point, point).inflate(tolerance, tolerance));
+        candidates.sort_by_key(|ix| std::cmp::Reverse(self.shapes[*ix].op_index));
+        candidates
+            .into_iter()
+            .find(|ix| self.shapes[*ix].contains(point, tolerance))
+            .map(|ix| self.shapes[ix].op_index)
+    }
+    /// Every op under `point`, topmost first.
+    pub fn hit_test_all(&self, point: Point, tolerance: f32) -> Vec<usize> {
+        let candidates = self.candidates(&Box2D::new(point, point).inflate(tolerance, tolerance));
+        let hits = candidates
+            .into_iter()
+            .filter(|ix| self.shapes[*ix].contains(point, tolerance))
+            .map(|ix| self.shapes[ix].op_index);
+        topmost_first(hits)
+    }
+    /// Every op selected by a marquee `rect`, topmost first.
+    pub fn hit_test_rect(&self, rect: Box2D, mode: MarqueeMode) -> Vec<usize> {
+        let candidates = self.candidates(&rect);
+        let hits = match mode {
+            MarqueeMode::Touching => candidates
+                .into_iter()
+                .filter(|ix| self.shapes[*ix].touches(&rect))
+                .map(|ix| self.shapes[ix].op_index)
+                .collect::<Vec<_>>(),
+            MarqueeMode::Enclosed => candidates
+                .into_iter()
+                .map(|ix| self.shapes[ix].op_index)
+                .filter(|op_index| {
+                    self.shapes[self.op_shapes[*op_index].clone()]
+                        .iter()
+                        .all(|shape| spatial::contains(&rect, &shape.bounds))
+                })
+                .collect::<Vec<_>>(),
+        };
+        topmost_first(hits)
+    }
+    fn candidates(&self, region: &Box2D) -> Vec<usize> {
+        let mut candidates = Vec::new();
+        self.tree.query(region, |_, ix| candidates.push(*ix));
+        candidates
+    }
+}
+
+fn topmost_first(op_indices: impl IntoIterator<Item = usize>) -> Vec<usize> {
+    let mut op_indices = op_indices.into_iter().collect::<Vec<_>>();
+    op_indices.sort_unstable_by(|a, b| b.cmp(a));
+    op_indices.dedup();
+    op_indices
+}
+
+impl HitShape {
+    fn contains(&self, point: Point, tolerance: f32) -> bool {
+        match &self.geometry {
+            HitGeometry::Fill { path, fill_rule, tolerance: flattening, edges } => {
+                hit_test_path(&point, path.iter(), *fill_rule, *flattening)
+                    || edges.iter().any(|edge| edge.distance_to_point(point) <= tolerance)
+            }
+            HitGeometry::Stroke { segments } => segments.iter().any(|segment| {
+                let (closest, t) = closest_point(&segment.line, point);
+                let half_width = segment.half_widths.0 + (segment.half_widths.1 - segment.half_widths.0) * t;
+                (closest - point).length() <= half_width + tolerance
+            }),
+        }
+    }
+    fn touches(&self, rect: &Box2D) -> bool {
+        match &self.geometry {
+            HitGeometry::Fill { path, fill_rule, tolerance, edges } => {
+                edges.iter().any(|edge| segment_rect_distance(edge, rect) <= 0.0)
+                    || hit_test_path(&rect.center(), path.iter(), *fill_rule, *tolerance)
+            }
+            HitGeometry::Stroke { segments } => segments.iter().any(|segment| {
+                let half_width = segment.half_widths.0.max(segment.half_widths.1);
+                segment_rect_distance(&segment.line, rect) <= half_width
+            }),
+        }
+    }
+}
+
+/// The closest point on `line` to `point`, and where it lies along the line.
+fn closest_point(line: &LineSegment<f32>, point: Point) -> (Point, f32) {
+    let direction = line.to_vector();
+    let length_squared = direction.square_length();
+    if length_squared <= 0.0 {
+        return (line.from, 0.0)
+    }
+    let t = ((point - line.from).dot(direction) / length_squared).clamp(0.0, 1.0);
+    (line.sample(t), t)
+}
+
+fn segment_rect_distance(line: &LineSegment<f32>, rect: &Box2D) -> f32 {
+    let inside = |p: Point| spatial::overlaps(&Box2D::new(p, p), rect);
+    if inside(line.from) || inside(line.to) {
+        return 0.0
+    }
+    let corners = [
+        rect.min,
+        lyon::math::point(rect.max.x, rect.min.y),
+        rect.max,
+        lyon::math::point(rect.min.x, rect.max.y),
+    ];
+    let crosses = (0..4).any(|ix| {
+        line.intersects(&LineSegment { from: corners[ix], to: corners[(ix + 1) % 4] })
+    });
+    if crosses {
+        return 0.0
+    }
+    let to_rect = |p: Point| (p.clamp(rect.min, rect.max) - p).length();
+    corners
+        .iter()
+        .map(|corner| line.distance_to_point(*corner))
+        .chain([to_rect(line.from), to_rect(line.to)])
+        .fold(f32::INFINITY, f32::min)
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// PICTURE API
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+impl Picture {
+    pub fn hit_test_index(&self) -> HitTestIndex {
+        HitTestIndex::new(self.items.iter())
+    }
+    /// One-off query; see [`HitTestIndex`] for repeated queries.
+    pub fn hit_test(&self, point: Point, tolerance: f32) -> Option<usize> {
+        self.hit_test_index().hit_test(point, tolerance)
+    }
+    /// One-off query; see [`HitTestIndex`] for repeated queries.
+    pub fn hit_test_all(&self, point: Point, tolerance: f32) -> Vec<usize> {
+        self.hit_test_index().hit_test_all(point, tolerance)
+    }
+    /// One-off query; see [`HitTestIndex`] for repeated queries.
+    pub fn hit_test_rect(&self, rect: Box2D, mode: MarqueeMode) -> Vec<usize> {
+        self.hit_test_index().hit_test_rect(rect, mode)
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// TESTS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lyon::math::point;
+    use crate::data::RGBA;
+    use crate::data::geometry::Rect;
+    use crate::data::shapes::{Shape, ShapeStyle};
+    use crate::data::symbols::{Symbol, SymbolInstance};
+
+    fn square(min: (f32, f32), size: f32, style: &ShapeStyle) -> DrawOp {
+        Shape::Rect(Rect::from_origin_and_size(min, size, size)).draw(style)
+    }
+
+    fn polyline(points: &[(f32, f32)], style: &ShapeStyle) -> DrawOp {
+        Shape::Polyline(points.iter().copied().collect()).draw(style)
+    }
+
+    #[test]
+    fn topmost_fill_wins() {
+        let fill = ShapeStyle::fill(RGBA::RED);
+        let ops = [square((0.0, 0.0), 10.0, &fill), square((5.0, 5.0), 10.0, &fill)];
+        let index = HitTestIndex::new(ops.iter());
+        assert_eq!(index.hit_test(point(7.0, 7.0), 0.0), Some(1));
+        assert_eq!(index.hit_test(point(2.0, 2.0), 0.0), Some(0));
+        assert_eq!(index.hit_test_all(point(7.0, 7.0), 0.0), vec![1, 0]);
+        assert_eq!(index.hit_test(point(20.0, 2.0), 0.0), None);
+        // The tolerance reaches past the outline of fills too.
+        assert_eq!(index.hit_test(point(-0.5, 2.0), 0.0), None);
+        assert_eq!(index.hit_test(point(-0.5, 2.0), 1.0), Some(0));
+    }
+
+    #[test]
+    fn strokes_are_hit_within_half_their_width() {
+        let ops = [polyline(&[(0.0, 0.0), (100.0, 0.0)], &ShapeStyle::stroke(RGBA::BLACK, 4.0))];
+        let index = HitTestIndex::new(ops.iter());
+        assert_eq!(index.hit_test(point(50.0, 1.9), 0.0), Some(0));
+        assert_eq!(index.hit_test(point(50.0, 2.5), 0.0), None);
+        assert_eq!(index.hit_test(point(50.0, 2.5), 1.0), Some(0));
+        assert_eq!(index.hit_test(point(103.0, 0.0), 0.0), None);
+    }
+
+    #[test]
+    fn outside_aligned_strokes_are_hit_outside_the_fill() {
+        let style = ShapeStyle::fill_stroke(RGBA::RED, RGBA::BLACK, 4.0);
+        let center = [square((0.0, 0.0), 10.0, &style)];
+        let outside = [square((0.0, 0.0), 10.0, &style.with_stroke_alignment(StrokeAlignment::Outside))];
+        assert_eq!(HitTestIndex::new(center.iter()).hit_test(point(-3.0, 5.0), 0.0), None);
+        assert_eq!(HitTestIndex::new(outside.iter()).hit_test(point(-3.0, 5.0), 0.0), Some(0));
+    }
+
+    #[test]
+    fn groups_and_instances_report_their_op() {
+        let fill = ShapeStyle::fill(RGBA::RED);
+        let group = GroupOp::new([square((0.0, 0.0), 10.0, &fill), square((20.0, 0.0), 10.0, &fill)]);
+        let symbol = Symbol::new(Shape::Circle { center: (0.0, 0.0).into(), radius: 5.0 }.draw(&fill));
+        let instances = InstancesOp::new(symbol, [
+            SymbolInstance::at(point(100.0, 100.0)),
+            SymbolInstance::at(point(200.0, 100.0)).with_scale(2.0),
+        ]);
+        let ops = [DrawOp::from(group), DrawOp::from(instances)];
+        let index = HitTestIndex::new(ops.iter());
+        assert_eq!(index.hit_test(point(25.0, 5.0), 0.0), Some(0));
+        assert_eq!(index.hit_test(point(15.0, 5.0), 0.0), None);
+        assert_eq!(index.hit_test(point(103.0, 100.0), 0.0), Some(1));
+        assert_eq!(index.hit_test(point(208.0, 100.0), 0.0), Some(1));
+        assert_eq!(index.hit_test(point(150.0, 100.0), 0.0), None);
+    }
+
+    #[test]
+    fn marquee_touching_and_enclosed() {
+        let fill = ShapeStyle::fill(RGBA::RED);
+        let ops = [
+            square((0.0, 0.0), 10.0, &fill),
+            square((20.0, 0.0), 10.0, &fill),
+            polyline(&[(0.0, 50.0), (100.0, 50.0)], &ShapeStyle::stroke(RGBA::BLACK, 2.0)),
+        ];
+        let index = HitTestIndex::new(ops.iter());
+        let rect = Box2D::new(point(-1.0, -1.0), point(25.0, 51.0));
+        assert_eq!(index.hit_test_rect(rect, MarqueeMode::Touching), vec![2, 1, 0]);
+        assert_eq!(index.hit_test_rect(rect, MarqueeMode::Enclosed), vec![0]);
+        // A marquee inside a fill touches it, without crossing its outline.
+        let inner = Box2D::new(point(2.0, 2.0), point(3.0, 3.0));
+        assert_eq!(index.hit_test_rect(inner, MarqueeMode::Touching), vec![0]);
+        assert!(index.hit_test_rect(inner, MarqueeMode::Enclosed).is_empty());
+    }
+
+    #[test]
+    fn empty_index() {
+        let index = HitTestIndex::new(std::iter::empty());
+        assert_eq!(index.hit_test(point(0.0, 0.0), 10.0), None);
+        assert!(index.hit_test_rect(Box2D::new(point(0.0, 0.0), point(1.0, 1.0)), MarqueeMode::Touching).is_empty());
+    }
+}
@@ -0,0 +1,821 @@
+use std::ops::Range;
+use lyon::math::{Angle, Point, Vector};
+use lyon::geom::CubicBezierSegment;
+use lyon::path::{Path, Polygon};
+use crate::data::draw_cmds::{BlendMode, FillOp, StrokeOp};
+use crate::data::drawable::{InkWidth, NibPen, PathOptions, SmoothingFilter};
+use crate::data::geometry::{InkSampleVec, PointVec};
+use crate::data::geometry_impl::PointVecOps;
+use crate::data::picture::{TesselationSettings, STROKE_WIDTH};
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// INK STROKES
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// Turns raw pointer samples into a smooth stroke: drops samples closer than
+/// `min_distance` to the last one kept, runs the smoothing filter, and fits
+/// piecewise cubic Béziers within `fit_tolerance`.
+pub fn ink_stroke(points: &PointVec, options: &PathOptions) -> StrokeOp {
+    StrokeOp {
+        path: ink_path(points, options),
+        stroke_color: options.color,
+        stroke_settings: TesselationSettings::default_stroke_options()
+            .with_line_width(options.width)
+            .with_line_cap(lyon::path::LineCap::Round)
+            .with_line_join(lyon::path::LineJoin::Round),
+        stroke_dash: None,
+        blend_mode: BlendMode::default(),
+    }
+}
+
+/// The fitted centerline of `ink_stroke`. A single sample gives a lone point,
+/// which strokes as a dot.
+pub fn ink_path(points: &PointVec, options: &PathOptions) -> Path {
+    let points = points.points.iter().map(|point| (*point).into()).collect::<Vec<Point>>();
+    let points = dedup_points(&points, options.min_distance);
+    let points = smooth(&points, options.smoothing);
+    let mut builder = Path::builder();
+    if let Some(first) = points.first() {
+        builder.begin(*first);
+        for segment in fit_cubics(&points, options.fit_tolerance) {
+            builder.cubic_bezier_to(segment.ctrl1, segment.ctrl2, segment.to);
+        }
+        builder.end(false);
+    }
+    builder.build()
+}
+
+/// Drops non-finite samples, and samples within `min_distance` of the last
+/// one kept. The final sample is always kept, replacing the last one kept if
+/// they're too close.
+pub fn dedup_points(points: &[Point], min_distance: f32) -> Vec<Point> {
+    dedup_indices(points, min_distance).into_iter().map(|ix| points[ix]).collect()
+}
+
+/// The indices of the samples kept by `dedup_points`.
+fn dedup_indices(points: &[Point], min_distance: f32) -> Vec<usize> {
+    let finite = (0..points.len())
+        .filter(|ix| points[*ix].x.is_finite() && points[*ix].y.is_finite())
+        .collect::<Vec<_>>();
+    let mut output: Vec<usize> = Vec::with_capacity(finite.len());
+    for ix in finite.iter().copied() {
+        if output.last().is_none_or(|last| (points[ix] - points[*last]).length() > min_distance) {
+            output.push(ix);
+        }
+    }
+    // Keep the pen-up position, which is where the stroke was aimed.
+    if let (Some(end), true) = (finite.last(), output.len() > 1) {
+        *output.last_mut().unwrap() = *end;
+    }
+    output
+}
+
+/// Smooths the interior samples. The endpoints stay where they were drawn.
+pub fn smooth(points: &[Point], filter: SmoothingFilter) -> Vec<Point> {
+    let vectors = points.iter().map(|point| point.to_vector()).collect::<Vec<_>>();
+    smooth_values(&vectors, filter, Vector::zero())
+        .into_iter()
+        .map(|vector| vector.to_point())
+        .collect()
+}
+
+fn smooth_values<T>(values: &[T], filter: SmoothingFilter, zero: T) -> Vec<T>
+where
+    T: Copy + std::ops::Add<Output = T> + std::ops::Mul<f32, Output = T>,
+{
+    let lerp = |a: T, b: T, t: f32| a * (1.0 - t) + b * t;
+    if values.len() < 3 {
+        return values.to_vec()
+    }
+    let mut output = values.to_vec();
+    match filter {
+        SmoothingFilter::None => {}
+        SmoothingFilter::MovingAverage { radius } => {
+            for ix in 1..values.len() - 1 {
+                // Shrink the window near the ends so it stays centered.
+                let radius = radius.min(ix).min(values.len() - 1 - ix);
+                let window = &values[ix - radius..=ix + radius];
+                let sum = window.iter().fold(zero, |sum, value| sum + *value);
+                output[ix] = sum * (1.0 / window.len() as f32);
+            }
+        }
+        SmoothingFilter::Exponential { strength } => {
+            // A forward and a backward pass, so the result doesn't lag behind
+            // the input.
+            let alpha = 1.0 - strength.clamp(0.0, 0.99);
+            for ix in 1..values.len() {
+                output[ix] = lerp(output[ix - 1], output[ix], alpha);
+            }
+            for ix in (0..values.len() - 1).rev() {
+                output[ix] = lerp(output[ix + 1], output[ix], alpha);
+            }
+            output[0] = values[0];
+            output[values.len() - 1] = values[values.len() - 1];
+        }
+    }
+    output
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// BÉZIER FITTING
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// Fits a chain of cubic Béziers through `points`, each within `tolerance`
+/// of the samples it covers, using Schneider's algorithm ("An Algorithm for
+/// Automatically Fitting Digitized Curves", Graphics Gems, 1990). Segments
+/// are split at the worst sample, and keep a continuous tangent there.
+pub fn fit_cubics(points: &[Point], tolerance: f32) -> Vec<CubicBezierSegment<f32>> {
+    fit_cubic_spans(points, tolerance).into_iter().map(|(segment, _)| segment).collect()
+}
+
+/// Like `fit_cubics`, along with the range of samples each segment covers,
+/// endpoints included.
+fn fit_cubic_spans(points: &[Point], tolerance: f32) -> Vec<(CubicBezierSegment<f32>, Range<usize>)> {
+    let mut segments = Vec::new();
+    if points.len() < 2 {
+        return segments
+    }
+    let last = points.len() - 1;
+    let start_tangent = tangent(points[0], points[1]);
+    let end_tangent = tangent(points[last], points[last - 1]);
+    fit_range(points, 0, start_tangent, end_tangent, tolerance.max(1e-3), &mut segments);
+    segments
+}
+
+const MAX_REPARAMETERIZATIONS: usize = 4;
+
+/// `offset` is the index of `points[0]` among all the samples.
+fn fit_range(
+    points: &[Point],
+    offset: usize,
+    start_tangent: Vector,
+    end_tangent: Vector,
+    tolerance: f32,
+    output: &mut Vec<(CubicBezierSegment<f32>, Range<usize>)>,
+) {
+    let (first, last) = (points[0], points[points.len() - 1]);
+    let span = offset..offset + points.len();
+    if points.len() == 2 {
+        let distance = (last - first).length() / 3.0;
+        let segment = CubicBezierSegment {
+            from: first,
+            ctrl1: first + start_tangent * distance,
+            ctrl2: last + end_tangent * distance,
+            to: last,
+        };
+        output.push((segment, span));
+        return
+    }
+    let mut parameters = chord_length_parameters(points);
+    let mut segment = generate_bezier(points, &parameters, start_tangent, end_tangent);
+    let (mut error, mut split) = max_error(points, &segment, &parameters);
+    if error <= tolerance {
+        output.push((segment, span));
+        return
+    }
+    // Close misses are usually just badly parameterized.
+    if error <= tolerance * 4.0 {
+        for _ in 0..MAX_REPARAMETERIZATIONS {
+            parameters = reparameterize(points, &segment, &parameters);
+            segment = generate_bezier(points, &parameters, start_tangent, end_tangent);
+            (error, split) = max_error(points, &segment, &parameters);
+            if error <= tolerance {
+                output.push((segment, span));
+                return
+            }
+        }
+    }
+    let center_tangent = {
+        let tangent = points[split - 1] - points[split + 1];
+        if tangent.square_length() > 0.0 {
+            tangent.normalize()
+        } else {
+            tangent_or_default(points[split - 1] - points[split])
+        }
+    };
+    fit_range(&points[..=split], offset, start_tangent, center_tangent, tolerance, output);
+    fit_range(&points[split..], offset + split, -center_tangent, end_tangent, tolerance, output);
+}
+
+fn tangent(from: Point, towards: Point) -> Vector {
+    tangent_or_default(towards - from)
+}
+
+fn tangent_or_default(vector: Vector) -> Vector {
+    if vector.square_length() > 0.0 {
+        vector.normalize()
+    } else {
+        lyon::math::vector(1.0, 0.0)
+    }
+}
+
+fn chord_length_parameters(points: &[Point]) -> Vec<f32> {
+    let mut parameters = Vec::with_capacity(points.len());
+    let mut length = 0.0;
+    parameters.push(0.0);
+    for pair in points.windows(2) {
+        length += (pair[1] - pair[0]).length();
+        parameters.push(length);
+    }
+    if length > 0.0 {
+        for parameter in parameters.iter_mut() {
+            *parameter /= length;
+        }
+    }
+    parameters
+}
+
+/// The least squares fit for the control point distances along the fixed end
+/// tangents. Falls back to a third of the chord when that's degenerate.
+fn generate_bezier(points: &[Point], parameters: &[f32], start_tangent: Vector, end_tangent: Vector) -> CubicBezierSegment<f32> {
+    let (first, last) = (points[0], points[points.len() - 1]);
+    let mut c = [[0.0f32; 2]; 2];
+    let mut x = [0.0f32; 2];
+    for (point, t) in points.iter().zip(parameters) {
+        let mt = 1.0 - t;
+        let a0 = start_tangent * (3.0 * mt * mt * t);
+        let a1 = end_tangent * (3.0 * mt * t * t);
+        c[0][0] += a0.dot(a0);
+        c[0][1] += a0.dot(a1);
+        c[1][1] += a1.dot(a1);
+        let on_chord = first.to_vector() * (mt * mt * mt + 3.0 * mt * mt * t)
+            + last.to_vector() * (3.0 * mt * t * t + t * t * t);
+        let residual = point.to_vector() - on_chord;
+        x[0] += a0.dot(residual);
+        x[1] += a1.dot(residual);
+    }
+    c[1][0] = c[0][1];
+    let det = c[0][0] * c[1][1] - c[1][0] * c[0][1];
+    let (alpha_start, alpha_end) = if det.abs() > 1e-12 {
+        (
+            (x[0] * c[1][1] - x[1] * c[0][1]) / det,
+            (c[0][0] * x[1] - c[1][0] * x[0]) / det,
+        )
+    } else {
+        (0.0, 0.0)
+    };
+    let chord = (last - first).length();
+    let epsilon = 1e-6 * chord;
+    let (alpha_start, alpha_end) = if alpha_start < epsilon || alpha_end < epsilon {
+        (chord / 3.0, chord / 3.0)
+    } else {
+        (alpha_start, alpha_end)
+    };
+    CubicBezierSegment {
+        from: first,
+        ctrl1: first + start_tangent * alpha_start,
+        ctrl2: last + end_tangent * alpha_end,
+        to: last,
+    }
+}
+
+/// The largest distance from a sample to its point on the curve, and the
+/// index of that sample.
+fn max_error(points: &[Point], segment: &CubicBezierSegment<f32>, parameters: &[f32]) -> (f32, usize) {
+    let mut worst = (0.0, points.len() / 2);
+    for ix in 1..points.len() - 1 {
+        let distance = (segment.sample(parameters[ix]) - points[ix]).length();
+        if distance > worst.0 {
+            worst = (distance, ix);
+        }
+    }
+    worst
+}
+
+/// One Newton-Raphson step towards each sample's closest point on the curve.
+fn reparameterize(points: &[Point], segment: &CubicBezierSegment<f32>, parameters: &[f32]) -> Vec<f32> {
+    points
+        .iter()
+        .zip(parameters)
+        .map(|(point, t)| {
+            let offset = segment.sample(*t) - *point;
+            let first = segment.derivative(*t);
+            let second = second_derivative(segment, *t);
+            let denominator = first.dot(first) + offset.dot(second);
+            if denominator.abs() < 1e-12 {
+                *t
+            } else {
+                (t - offset.dot(first) / denominator).clamp(0.0, 1.0)
+            }
+        })
+        .collect()
+}
+
+fn second_derivative(segment: &CubicBezierSegment<f32>, t: f32) -> Vector {
+    let a = segment.ctrl1 - segment.from;
+    let b = segment.ctrl2 - segment.ctrl1;
+    let c = segment.to - segment.ctrl2;
+    ((b - a) * (1.0 - t) + (c - b) * t) * 6.0
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// VARIABLE WIDTH INK
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// Like `ink_stroke`, with a width per sample from `width`. The path carries
+/// the width as its `STROKE_WIDTH` attribute, on a unit line width.
+pub fn variable_ink_stroke(samples: &InkSampleVec, options: &PathOptions, width: &InkWidth) -> StrokeOp {
+    StrokeOp {
+        path: variable_ink_path(samples, options, width),
+        stroke_color: options.color,
+        stroke_settings: TesselationSettings::default_stroke_options()
+            .with_line_width(1.0)
+            .with_variable_line_width(STROKE_WIDTH)
+            .with_line_cap(lyon::path::LineCap::Round)
+            .with_line_join(lyon::path::LineJoin::Round),
+        stroke_dash: None,
+        blend_mode: BlendMode::default(),
+    }
+}
+
+/// The fitted centerline of `variable_ink_stroke`, with one attribute: the
+/// width. Curves are split wherever the width between their ends strays from
+/// a straight interpolation by more than `width.width_tolerance`.
+pub fn variable_ink_path(samples: &InkSampleVec, options: &PathOptions, width: &InkWidth) -> Path {
+    let positions = samples.samples.iter().map(|sample| sample.position.into()).collect::<Vec<Point>>();
+    let kept = dedup_indices(&positions, options.min_distance);
+    let samples = kept.iter().map(|ix| samples.samples[*ix]).collect::<Vec<_>>();
+    let points = smooth(&kept.iter().map(|ix| positions[*ix]).collect::<Vec<_>>(), options.smoothing);
+    let widths = sample_widths(&samples, &points, options.smoothing, width);
+    let mut builder = Path::builder_with_attributes(1);
+    if let Some(first) = points.first() {
+        builder.begin(*first, &[widths[0]]);
+        for (segment, span) in fit_cubic_spans(&points, options.fit_tolerance) {
+            split_by_width(&segment, &points[span.clone()], &widths[span], width.width_tolerance, &mut builder);
+        }
+        builder.end(false);
+    }
+    builder.build()
+}
+
+/// The width at each sample: pressure, thinned by speed, then tapered by the
+/// distance from either end. Strokes shorter than both tapers taper over
+/// their whole length instead, and a tapered tap is a dot of `min_width`.
+fn sample_widths(samples: &[crate::data::geometry::InkSample], points: &[Point], smoothing: SmoothingFilter, width: &InkWidth) -> Vec<f32> {
+    let count = samples.len();
+    let speeds = (0..count)
+        .map(|ix| {
+            let (before, after) = (ix.saturating_sub(1), (ix + 1).min(count - 1));
+            let elapsed = samples[after].timestamp - samples[before].timestamp;
+            if elapsed > 0.0 {
+                (points[after] - points[before]).length() / elapsed as f32
+            } else {
+                0.0
+            }
+        })
+        .collect::<Vec<_>>();
+    let speeds = smooth_values(&speeds, smoothing, 0.0);
+    let mut distances = Vec::with_capacity(count);
+    let mut length = 0.0;
+    for ix in 0..count {
+        if ix > 0 {
+            length += (points[ix] - points[ix - 1]).length();
+        }
+        distances.push(length);
+    }
+    let (taper_start, taper_end) = (width.taper_start.max(0.0), width.taper_end.max(0.0));
+    if length <= 0.0 && taper_start + taper_end > 0.0 {
+        return vec![width.min_width.max(0.0); count]
+    }
+    let fit = if taper_start + taper_end > length { length / (taper_start + taper_end) } else { 1.0 };
+    let (taper_start, taper_end) = (taper_start * fit, taper_end * fit);
+    let taper = |distance: f32, taper_length: f32| {
+        if taper_length <= 0.0 {
+            return 1.0
+        }
+        // Eases out, so the taper is round rather than a sharp point.
+        let x = (distance / taper_length).clamp(0.0, 1.0);
+        x * (2.0 - x)
+    };
+    let widths = (0..count)
+        .map(|ix| {
+            let pressure = width.pressure_response.apply(samples[ix].pressure);
+            let speed = width.velocity_response.apply(speeds[ix] / width.max_speed.max(f32::EPSILON));
+            let thinning = 1.0 - width.velocity_thinning.clamp(0.0, 1.0) * speed;
+            let tapered = taper(distances[ix], taper_start) * taper(length - distances[ix], taper_end);
+            (width.min_width + (width.max_width - width.min_width) * pressure) * thinning * tapered
+        })
+        .collect::<Vec<_>>();
+    smooth_values(&widths, smoothing, 0.0)
+        .into_iter()
+        .map(|width| width.max(0.0))
+        .collect()
+}
+
+/// Appends `segment`, splitting it at the sample whose width strays the
+/// furthest from a linear interpolation, until none is beyond `tolerance`.
+/// `points` and `widths` are the samples the segment covers, endpoints
+/// included.
+fn split_by_width(segment: &CubicBezierSegment<f32>, points: &[Point], widths: &[f32], tolerance: f32, builder: &mut lyon::path::path::BuilderWithAttributes) {
+    let parameters = chord_length_parameters(points);
+    let (start, end) = (widths[0], widths[widths.len() - 1]);
+    let worst = (1..widths.len().saturating_sub(1))
+        .map(|ix| (ix, (widths[ix] - (start + (end - start) * parameters[ix])).abs()))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .filter(|(_, error)| *error > tolerance.max(0.0));
+    match worst {
+        Some((ix, _)) => {
+            let (head, tail) = segment.split(parameters[ix]);
+            split_by_width(&head, &points[..=ix], &widths[..=ix], tolerance, builder);
+            split_by_width(&tail, &points[ix..], &widths[ix..], tolerance, builder);
+        }
+        None => {
+            builder.cubic_bezier_to(segment.ctrl1, segment.ctrl2, segment.to, &[end]);
+        }
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// CALLIGRAPHY
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// Tilt below which the pen counts as upright, in radians.
+const MIN_TILT: f32 = 0.05;
+
+/// A flat-nib stroke along the fitted centerline of `points`: the outline of
+/// everything the nib sweeps over, filled. Sharp turns come out the way the
+/// nib draws them, so there's no join style to pick.
+pub fn nib_stroke(points: &PointVec, options: &PathOptions, nib: &NibPen) -> FillOp {
+    let points = points.points.iter().map(|point| (*point).into()).collect::<Vec<Point>>();
+    let points = smooth(&dedup_points(&points, options.min_distance), options.smoothing);
+    let angles = vec![nib.angle; points.len()];
+    nib_fill(nib_outline(&points, &angles, options.fit_tolerance, nib), options)
+}
+
+/// Like `nib_stroke`, turning the nib with the pen when `nib.follow_tilt` is
+/// set.
+pub fn nib_ink_stroke(samples: &InkSampleVec, options: &PathOptions, nib: &NibPen) -> FillOp {
+    let positions = samples.samples.iter().map(|sample| sample.position.into()).collect::<Vec<Point>>();
+    let kept = dedup_indices(&positions, options.min_distance);
+    let points = smooth(&kept.iter().map(|ix| positions[*ix]).collect::<Vec<_>>(), options.smoothing);
+    let mut angle = nib.angle;
+    let directions = kept
+        .iter()
+        .map(|ix| {
+            let [x, y] = samples.samples[*ix].tilt;
+            if nib.follow_tilt && x.hypot(y) > MIN_TILT {
+                angle = Angle::radians(y.atan2(x)) + nib.angle;
+            }
+            // Doubled, since the nib looks the same half a turn around, so
+            // that smoothing doesn't spin it the long way.
+            Vector::from_angle_and_length(angle * 2.0, 1.0)
+        })
+        .collect::<Vec<_>>();
+    let angles = smooth_values(&directions, options.smoothing, Vector::zero())
+        .into_iter()
+        .map(|direction| direction.angle_from_x_axis() / 2.0)
+        .collect::<Vec<_>>();
+    nib_fill(nib_outline(&points, &angles, options.fit_tolerance, nib), options)
+}
+
+fn nib_fill(path: Path, options: &PathOptions) -> FillOp {
+    FillOp {
+        path,
+        fill_color: options.color,
+        // The outline overlaps itself wherever the stroke turns back.
+        fill_settings: TesselationSettings::default_fill_options()
+            .with_fill_rule(lyon::tessellation::FillRule::NonZero),
+        blend_mode: BlendMode::default(),
+    }
+}
+
+/// Fits the centerline within `tolerance`, flattens it within the same, and
+/// outlines what the nib sweeps over: the convolution of the nib with the
+/// centerline there and back. That's the path of the nib corner furthest to
+/// the left of the motion, so one side of the stroke on the way out and the
+/// other on the way back, walking around the nib at turns and at both ends.
+/// Filled with the non-zero rule, it covers exactly the swept area, retraced
+/// parts included. The nib angle is interpolated between the samples.
+fn nib_outline(points: &[Point], angles: &[Angle], tolerance: f32, nib: &NibPen) -> Path {
+    let mut centerline: Vec<(Point, Angle)> = Vec::new();
+    let mut push = |center: Point, angle: Angle| match centerline.last_mut() {
+        // Too short to have a direction, so the point just turns the nib.
+        Some(last) if (center - last.0).square_length() <= f32::EPSILON => last.1 = angle,
+        _ => centerline.push((center, angle)),
+    };
+    if let Some(first) = points.first() {
+        push(*first, angles[0]);
+    }
+    for (segment, span) in fit_cubic_spans(points, tolerance) {
+        let (start, end) = (angles[span.start], angles[span.end - 1]);
+        segment.for_each_flattened_with_t(tolerance.max(1e-3), &mut |line, range| {
+            push(line.to, lerp_nib_angle(start, end, range.end));
+        });
+    }
+    // Clockwise on screen.
+    let corners = |(center, angle): (Point, Angle)| {
+        let along = Vector::from_angle_and_length(angle, nib.width.max(0.0) * 0.5);
+        let across = Vector::from_angle_and_length(angle + Angle::frac_pi_2(), nib.thickness.max(0.0) * 0.5);
+        [center - along - across, center + along - across, center + along + across, center - along + across]
+    };
+    let mut builder = Path::builder();
+    if let [single] = centerline[..] {
+        builder.add_polygon(Polygon { points: &corners(single), closed: true });
+        return builder.build()
+    }
+    // There and back, as a closed loop of segments.
+    let mut there_and_back = centerline.clone();
+    there_and_back.extend(centerline.iter().rev().skip(1));
+    let segments = there_and_back.windows(2).map(|pair| (pair[0], pair[1])).collect::<Vec<_>>();
+    // The corner furthest to the left of each segment, which is up on screen
+    // when moving right.
+    let support = |(from, to): ((Point, Angle), (Point, Angle))| {
+        let direction = to.0 - from.0;
+        let left = Vector::new(direction.y, -direction.x);
+        let offsets = corners((Point::origin(), from.1));
+        (0..4).max_by(|a, b| offsets[*a].to_vector().dot(left).total_cmp(&offsets[*b].to_vector().dot(left))).unwrap()
+    };
+    let supports = segments.iter().map(|segment| support(*segment)).collect::<Vec<_>>();
+    let mut outline = Vec::with_capacity(segments.len() * 3);
+    for (ix, (from, to)) in segments.iter().enumerate() {
+        let corner = supports[ix];
+        outline.push(corners(*from)[corner]);
+        outline.push(corners(*to)[corner]);
+        // Walk around the nib to the next segment's corner, the way the
+        // direction turns. Turning back counts as clockwise, which goes
+        // around the front of the nib at the end and the back at the start.
+        let next = (ix + 1) % segments.len();
+        let turn = (to.0 - from.0).cross(segments[next].1 .0 - segments[next].0 .0);
+        let reverses = turn == 0.0 && (to.0 - from.0).dot(segments[next].1 .0 - segments[next].0 .0) < 0.0;
+        let step = if turn > 0.0 || reverses { 1 } else { 3 };
+        let mut walk = corner;
+        while walk != supports[next] {
+            walk = (walk + step) % 4;
+            outline.push(corners(*to)[walk]);
+        }
+    }
+    builder.add_polygon(Polygon { points: &outline, closed: true });
+    builder.build()
+}
+
+/// Interpolates towards `to` the short way round, treating angles half a
+/// turn apart as the same nib.
+fn lerp_nib_angle(from: Angle, to: Angle, t: f32) -> Angle {
+    let pi = std::f32::consts::PI;
+    let delta = (to - from).radians;
+    let delta = delta - pi * (delta / pi).round();
+    from + Angle::radians(delta * t)
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// TESTS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lyon::math::point;
+    use crate::data::drawable::ResponseCurve;
+    use crate::data::geometry::InkSample;
+
+    /// Samples `count` points along `f`, one every `step` seconds.
+    fn ink_samples(count: usize, step: f64, f: impl Fn(usize) -> (Point, f32)) -> Vec<InkSample> {
+        (0..count)
+            .map(|ix| {
+                let (position, pressure) = f(ix);
+                InkSample { position: position.into(), pressure, tilt: [0.0; 2], timestamp: ix as f64 * step }
+            })
+            .collect()
+    }
+
+    fn widths_of(samples: &[InkSample], width: &InkWidth) -> Vec<f32> {
+        let points = samples.iter().map(|sample| sample.position.into()).collect::<Vec<Point>>();
+        sample_widths(samples, &points, SmoothingFilter::None, width)
+    }
+
+    fn untapered() -> InkWidth {
+        InkWidth { taper_start: 0.0, taper_end: 0.0, ..InkWidth::default() }
+    }
+
+    #[test]
+    fn dedup_drops_close_and_non_finite_samples() {
+        let points = [
+            point(0.0, 0.0),
+            point(0.2, 0.0),
+            point(f32::NAN, 1.0),
+            point(1.0, 0.0),
+            point(f32::INFINITY, 0.0),
+            point(1.3, 0.0),
+            point(2.0, 0.0),
+            point(2.1, 0.0),
+        ];
+        // The last sample replaces the one kept just before it.
+        assert_eq!(dedup_indices(&points, 0.5), vec![0, 3, 7]);
+        assert_eq!(dedup_points(&points, 0.5), vec![points[0], points[3], points[7]]);
+        assert_eq!(dedup_indices(&points, 0.0), vec![0, 1, 3, 5, 6, 7]);
+    }
+
+    #[test]
+    fn dedup_edge_cases() {
+        assert!(dedup_indices(&[], 1.0).is_empty());
+        assert!(dedup_indices(&[point(f32::NAN, 0.0)], 1.0).is_empty());
+        assert_eq!(dedup_indices(&[point(1.0, 1.0)], 1.0), vec![0]);
+        // Everything within reach of the first sample collapses into a dot.
+        assert_eq!(dedup_indices(&[point(0.0, 0.0), point(0.1, 0.0), point(0.2, 0.0)], 1.0), vec![0]);
+    }
+
+    #[test]
+    fn fitted_cubics_stay_within_tolerance() {
+        let points = (0..200)
+            .map(|ix| {
+                let t = ix as f32 * 0.05;
+                point(t * 10.0, (t * 1.3).sin() * 25.0 + (t * 3.1).cos() * 4.0)
+            })
+            .collect::<Vec<_>>();
+        for tolerance in [0.1, 0.5, 2.0] {
+            let segments = fit_cubics(&points, tolerance);
+            assert!(!segments.is_empty());
+            assert_eq!(segments[0].from, points[0]);
+            assert_eq!(segments.last().unwrap().to, *points.last().unwrap());
+            for pair in segments.windows(2) {
+                assert_eq!(pair[0].to, pair[1].from);
+            }
+            let flattened = segments
+                .iter()
+                .flat_map(|segment| (0..=64).map(move |ix| segment.sample(ix as f32 / 64.0)))
+                .collect::<Vec<_>>();
+            for sample in points.iter() {
+                let distance = flattened
+                    .windows(2)
+                    .map(|pair| lyon::geom::LineSegment { from: pair[0], to: pair[1] }.distance_to_point(*sample))
+                    .fold(f32::INFINITY, f32::min);
+                assert!(distance <= tolerance * 1.01, "{} > {}", distance, tolerance);
+            }
+        }
+    }
+
+    #[test]
+    fn fit_cubics_degenerate_input() {
+        assert!(fit_cubics(&[], 1.0).is_empty());
+        assert!(fit_cubics(&[point(1.0, 1.0)], 1.0).is_empty());
+        let segments = fit_cubics(&[point(0.0, 0.0), point(10.0, 0.0)], 1.0);
+        assert_eq!(segments.len(), 1);
+        assert_eq!((segments[0].from, segments[0].to), (point(0.0, 0.0), point(10.0, 0.0)));
+    }
+
+    #[test]
+    fn pressure_maps_between_min_and_max_width() {
+        let width = untapered();
+        let samples = ink_samples(5, 1.0, |ix| (point(ix as f32, 0.0), ix as f32 / 4.0));
+        let widths = widths_of(&samples, &width);
+        let expected = samples
+            .iter()
+            .map(|sample| width.min_width + (width.max_width - width.min_width) * width.pressure_response.apply(sample.pressure));
+        for (actual, expected) in widths.iter().zip(expected) {
+            assert!((actual - expected).abs() < 1e-5);
+        }
+        assert!((widths[0] - width.min_width).abs() < 1e-5);
+        assert!((widths[4] - width.max_width).abs() < 1e-5);
+    }
+
+    #[test]
+    fn speed_thins_the_stroke() {
+        let width = InkWidth { pressure_response: ResponseCurve::Linear, ..untapered() };
+        // 1000 units per second over samples 10 apart, against a max speed of 2000.
+        let slow = ink_samples(5, 1.0, |ix| (point(ix as f32 * 10.0, 0.0), 1.0));
+        let fast = ink_samples(5, 0.01, |ix| (point(ix as f32 * 10.0, 0.0), 1.0));
+        let slow = widths_of(&slow, &width);
+        let fast = widths_of(&fast, &width);
+        let thinning = 1.0 - width.velocity_thinning * width.velocity_response.apply(0.5);
+        for (slow, fast) in slow.iter().zip(fast.iter()) {
+            assert!((fast - slow * thinning).abs() < 1e-3, "{} {}", slow, fast);
+        }
+    }
+
+    #[test]
+    fn ends_taper_to_a_point() {
+        let width = InkWidth { taper_start: 5.0, taper_end: 10.0, ..InkWidth::default() };
+        let samples = ink_samples(41, 1.0, |ix| (point(ix as f32, 0.0), 1.0));
+        let widths = widths_of(&samples, &width);
+        assert_eq!(widths[0], 0.0);
+        assert_eq!(widths[40], 0.0);
+        // Full width past both tapers, eased in over them.
+        assert!((widths[20] - width.max_width).abs() < 1e-5);
+        assert!((widths[5] - width.max_width).abs() < 1e-5);
+        assert!((widths[30] - width.max_width).abs() < 1e-5);
+        assert!(widths[1] < widths[2] && widths[2] < widths[5]);
+        let half = 0.5 * (2.0 - 0.5);
+        assert!((widths[35] - width.max_width * half).abs() < 1e-5);
+    }
+
+    #[test]
+    fn short_strokes_fit_both_tapers() {
+        // Half as long as the tapers, so each is scaled down to fit, and the
+        // stroke reaches full width where they meet.
+        let width = InkWidth { taper_start: 4.0, taper_end: 16.0, ..InkWidth::default() };
+        let samples = ink_samples(11, 1.0, |ix| (point(ix as f32, 0.0), 1.0));
+        let widths = widths_of(&samples, &width);
+        assert_eq!(widths[0], 0.0);
+        assert_eq!(widths[10], 0.0);
+        assert!((widths[2] - width.max_width).abs() < 1e-5);
+        assert!(widths[..=2].windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(widths[2..].windows(2).all(|pair| pair[0] > pair[1]));
+    }
+
+    #[test]
+    fn taps_are_min_width_dots() {
+        let width = InkWidth { min_width: 1.5, ..InkWidth::default() };
+        let tap = ink_samples(1, 1.0, |_| (point(3.0, 4.0), 1.0));
+        assert_eq!(widths_of(&tap, &width), vec![1.5]);
+        // A pen held still counts as a tap too.
+        let held = ink_samples(4, 1.0, |_| (point(3.0, 4.0), 0.5));
+        assert_eq!(widths_of(&held, &width), vec![1.5; 4]);
+        // Untapered, a tap keeps its pressure width.
+        assert_eq!(widths_of(&tap, &untapered()), vec![width.max_width]);
+    }
+
+    #[test]
+    fn variable_ink_path_carries_the_widths() {
+        let samples = ink_samples(30, 1.0, |ix| (point(ix as f32 * 2.0, (ix as f32 * 0.3).sin() * 5.0), 1.0));
+        let samples = samples.into_iter().collect::<InkSampleVec>();
+        let options = PathOptions::default().with_smoothing(SmoothingFilter::None);
+        let path = variable_ink_path(&samples, &options, &InkWidth::default());
+        let mut widths = Vec::new();
+        for event in path.iter_with_attributes() {
+            if let lyon::path::Event::Begin { at } = event {
+                widths.push(at.1[0]);
+            }
+            if let lyon::path::Event::Cubic { to, .. } = event {
+                widths.push(to.1[0]);
+            }
+        }
+        assert_eq!(widths.first(), Some(&0.0));
+        assert_eq!(widths.last(), Some(&0.0));
+        assert!(widths.iter().all(|width| (0.0..=InkWidth::default().max_width).contains(width)));
+    }
+
+    fn nib_bounds(points: &[Point], nib: &NibPen) -> lyon::math::Box2D {
+        let angles = vec![nib.angle; points.len()];
+        let outline = nib_outline(points, &angles, 0.1, nib);
+        let points = outline
+            .iter()
+            .flat_map(|event| match event {
+                lyon::path::Event::Line { from, to } => vec![from, to],
+                _ => Vec::new(),
+            })
+            .collect::<Vec<_>>();
+        assert!(points.iter().all(|point| point.x.is_finite() && point.y.is_finite()));
+        lyon::math::Box2D::from_points(points)
+    }
+
+    #[test]
+    fn nib_sweeps_its_rectangle_along_the_stroke() {
+        let line = [point(0.0, 0.0), point(50.0, 0.0), point(100.0, 0.0)];
+        let along = NibPen { width: 6.0, thickness: 2.0, angle: Angle::zero(), follow_tilt: false };
+        let bounds = nib_bounds(&line, &along);
+        assert!((bounds.min - point(-3.0, -1.0)).length() < 1e-4, "{:?}", bounds);
+        assert!((bounds.max - point(103.0, 1.0)).length() < 1e-4, "{:?}", bounds);
+        let across = NibPen { angle: Angle::frac_pi_2(), ..along };
+        let bounds = nib_bounds(&line, &across);
+        assert!((bounds.min - point(-1.0, -3.0)).length() < 1e-4, "{:?}", bounds);
+        assert!((bounds.max - point(101.0, 3.0)).length() < 1e-4, "{:?}", bounds);
+    }
+
+    #[test]
+    fn nib_outline_is_one_loop_around_the_sweep() {
+        let line = [point(0.0, 0.0), point(50.0, 0.0), point(100.0, 0.0)];
+        let nib = NibPen { width: 6.0, thickness: 2.0, angle: Angle::frac_pi_4(), follow_tilt: false };
+        let outline = nib_outline(&line, &[nib.angle; 3], 0.1, &nib);
+        let loops = outline.iter().filter(|event| matches!(event, lyon::path::PathEvent::Begin { .. })).count();
+        assert_eq!(loops, 1);
+        // The nib at the start, plus its height across the motion over the
+        // length of the stroke.
+        let (sin, cos) = nib.angle.sin_cos();
+        let expected = 6.0 * 2.0 + 100.0 * (6.0 * sin + 2.0 * cos);
+        let area = lyon::algorithms::area::approximate_signed_area(0.01, outline.iter()).abs();
+        assert!((area - expected).abs() < 1e-2, "{} != {}", area, expected);
+    }
+
+    #[test]
+    fn retraced_nib_strokes_stay_filled() {
+        let nib = NibPen { width: 6.0, thickness: 2.0, angle: Angle::frac_pi_2(), follow_tilt: false };
+        let there_and_back = [point(0.0, 0.0), point(100.0, 0.0), point(100.0, 0.0), point(50.0, 0.0)];
+        let outline = nib_outline(&there_and_back, &[nib.angle; 4], 0.1, &nib);
+        let filled = |at: Point| {
+            lyon::algorithms::hit_test::hit_test_path(&at, outline.iter(), lyon::tessellation::FillRule::NonZero, 0.01)
+        };
+        // Covered once, twice, and by the nib at either end.
+        assert!(filled(point(25.0, 0.0)));
+        assert!(filled(point(75.0, 0.0)));
+        assert!(filled(point(75.0, 2.5)));
+        assert!(filled(point(100.5, 0.0)));
+        assert!(filled(point(-0.5, 0.0)));
+        assert!(!filled(point(75.0, 4.0)));
+        // The fit keeps its tangent through the turn, overshooting it a bit.
+        assert!(!filled(point(110.0, 0.0)));
+    }
+
+    #[test]
+    fn zero_width_nibs_stay_finite() {
+        let line = [point(0.0, 0.0), point(30.0, 10.0), point(60.0, 0.0)];
+        let hairline = NibPen { width: 0.0, thickness: 2.0, angle: Angle::frac_pi_4(), follow_tilt: false };
+        let bounds = nib_bounds(&line, &hairline);
+        assert!(bounds.height() > 10.0);
+        nib_bounds(&line, &NibPen { width: 0.0, thickness: 0.0, ..hairline });
+        nib_bounds(&[point(5.0, 5.0)], &NibPen { width: 0.0, ..hairline });
+    }
+}
@@ -0,0 +1,111 @@
+use crate::data::picture::TesselationSettings;
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// LEVEL OF DETAIL
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// A tessellation level. Level `n` flattens curves finely enough for up to
+/// `2^n` physical pixels per picture unit, so levels are a factor of two in
+/// zoom apart and small zooms never re-tessellate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LodLevel(pub i32);
+
+impl LodLevel {
+    pub const MIN: LodLevel = LodLevel(-16);
+    pub const MAX: LodLevel = LodLevel(16);
+    /// How far ahead of the zoom a finer level is requested, so it's usually
+    /// ready before curves start to facet.
+    pub const PREFETCH: f32 = 1.5;
+    /// How many levels finer than needed the current one may be before
+    /// zooming out switches to a coarser one.
+    pub const SLACK: i32 = 2;
+
+    /// The coarsest level fine enough for `scale` physical pixels per
+    /// picture unit.
+    pub fn for_scale(scale: f32) -> LodLevel {
+        if scale.is_nan() || scale <= 0.0 {
+            return LodLevel::MIN
+        }
+        let level = scale.log2().ceil();
+        LodLevel((level.max(LodLevel::MIN.0 as f32).min(LodLevel::MAX.0 as f32)) as i32)
+    }
+    /// The level to draw at `scale`, given the `current` one. The current
+    /// level is kept while it's fine enough and not far too fine, so zooming
+    /// back and forth around a threshold doesn't thrash.
+    pub fn target(current: Option<LodLevel>, scale: f32) -> LodLevel {
+        let needed = LodLevel::for_scale(scale);
+        let ahead = LodLevel::for_scale(scale * LodLevel::PREFETCH);
+        match current {
+            Some(current) if current >= needed && current.0 <= ahead.0 + LodLevel::SLACK => current,
+            _ => ahead,
+        }
+    }
+    /// Physical pixels per picture unit this level is fine enough for.
+    pub fn max_scale(&self) -> f32 {
+        2f32.powi(self.0)
+    }
+    pub fn settings(&self) -> TesselationSettings {
+        TesselationSettings::with_pixel_scale(self.max_scale())
+    }
+    pub fn tolerance(&self) -> f32 {
+        self.settings().tolerance()
+    }
+}
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// TESTS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_are_the_coarsest_fine_enough() {
+        assert_eq!(LodLevel::for_scale(1.0), LodLevel(0));
+        assert_eq!(LodLevel::for_scale(2.0), LodLevel(1));
+        assert_eq!(LodLevel::for_scale(3.0), LodLevel(2));
+        assert_eq!(LodLevel::for_scale(0.5), LodLevel(-1));
+        assert_eq!(LodLevel::for_scale(0.3), LodLevel(-1));
+        for scale in [0.01, 0.3, 1.0, 1.5, 7.0, 100.0, 4000.0] {
+            let level = LodLevel::for_scale(scale);
+            assert!(level.max_scale() >= scale && level.max_scale() < 2.0 * scale, "{} at {:?}", scale, level);
+        }
+        // Clamped, and collapsed or broken scales get the coarsest.
+        assert_eq!(LodLevel::for_scale(1e9), LodLevel::MAX);
+        assert_eq!(LodLevel::for_scale(1e-9), LodLevel::MIN);
+        for scale in [0.0, -2.0, f32::NAN] {
+            assert_eq!(LodLevel::for_scale(scale), LodLevel::MIN);
+        }
+    }
+
+    #[test]
+    fn targets_prefetch_finer_levels_and_keep_them_a_while() {
+        // Requested ahead of the zoom.
+        assert_eq!(LodLevel::target(None, 3.0), LodLevel::for_scale(3.0 * LodLevel::PREFETCH));
+        assert_eq!(LodLevel::target(None, 3.0), LodLevel(3));
+        // Kept while zooming around a threshold.
+        for scale in [3.9, 4.1, 3.9, 5.5, 2.5, 8.0] {
+            assert_eq!(LodLevel::target(Some(LodLevel(3)), scale), LodLevel(3), "at {}", scale);
+        }
+        // Replaced once it's too coarse.
+        assert_eq!(LodLevel::target(Some(LodLevel(3)), 8.5), LodLevel(4));
+        // Zooming out keeps up to `SLACK` levels more than the prefetch asks.
+        let current = LodLevel(4);
+        assert_eq!(LodLevel::target(Some(current), 2.0), current);
+        assert_eq!(LodLevel::for_scale(2.0 * LodLevel::PREFETCH).0 + LodLevel::SLACK, current.0);
+        assert_eq!(LodLevel::target(Some(current), 1.0), LodLevel(1));
+    }
+
+    #[test]
+    fn finer_levels_have_smaller_tolerances() {
+        let levels = (LodLevel::MIN.0..=LodLevel::MAX.0).map(LodLevel).collect::<Vec<_>>();
+        for pair in levels.windows(2) {
+            assert!(pair[1].tolerance() < pair[0].tolerance(), "{:?}", pair);
+        }
+        for level in levels {
+            let pixels = level.tolerance() * level.max_scale();
+            assert!((pixels - TesselationSettings::PIXEL_TOLERANCE).abs() < 1e-6, "{:?}", level);
+        }
+    }
+}
@@ -0,0 +1,553 @@
+use lyon::geom::{CubicBezierSegment, LineSegment, QuadraticBezierSegment};
+use lyon::math::{Point, Vector};
+use lyon::path::{Event, FillRule, LineCap, LineJoin, Path};
+use lyon::tessellation::{FillOptions, StrokeOptions};
+use crate::data::boolean::{BooleanOp, BooleanOptions};
+use crate::data::draw_cmds::{FillOp, FillStrokeOp, StrokeAlignment, StrokeOp};
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// PATH OFFSETTING
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// How `offset_path` fills in the corners that open up: the convex ones when
+/// growing, and the concave ones when shrinking.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OffsetOptions {
+    pub line_join: LineJoin,
+    /// As for strokes, with the offset as half the line width: longer miters
+    /// are beveled, or clipped for `MiterClip`.
+    pub miter_limit: f32,
+    pub tolerance: f32,
+    /// Decides what's inside the path.
+    pub fill_rule: FillRule,
+}
+
+impl Default for OffsetOptions {
+    fn default() -> Self {
+        OffsetOptions {
+            line_join: LineJoin::Miter,
+            miter_limit: StrokeOptions::DEFAULT_MITER_LIMIT,
+            tolerance: FillOptions::DEFAULT_TOLERANCE,
+            fill_rule: FillRule::NonZero,
+        }
+    }
+}
+
+impl OffsetOptions {
+    pub fn with_line_join(mut self, line_join: LineJoin) -> Self {
+        self.line_join = line_join;
+        self
+    }
+    pub fn with_miter_limit(mut self, miter_limit: f32) -> Self {
+        self.miter_limit = miter_limit;
+        self
+    }
+    pub fn with_tolerance(mut self, tolerance: f32) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+    pub fn with_fill_rule(mut self, fill_rule: FillRule) -> Self {
+        self.fill_rule = fill_rule;
+        self
+    }
+}
+
+/// Grows the region `path` fills by `distance`, or shrinks it when
+/// `distance` is negative, e.g. for padding. Subpaths are closed, as for
+/// fills. The result is made of lines, and winds as boolean results do.
+pub fn offset_path(path: &Path, distance: f32, options: &OffsetOptions) -> Path {
+    // The band a stroke twice as wide as the offset covers, added to or
+    // taken from the fill.
+    let band_options = StrokeOptions::tolerance(options.tolerance)
+        .with_line_width(distance.abs() * 2.0)
+        .with_line_join(options.line_join)
+        .with_miter_limit(options.miter_limit.max(StrokeOptions::MINIMUM_MITER_LIMIT));
+    let band = outline(path, &band_options, true);
+    let op = if distance >= 0.0 { BooleanOp::Union } else { BooleanOp::Difference };
+    let boolean_options = BooleanOptions::default()
+        .with_tolerance(options.tolerance)
+        .with_fill_rules(options.fill_rule, FillRule::NonZero);
+    op.apply(path, &band, &boolean_options)
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// STROKE OUTLINES
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// The region a stroke of `path` covers, as a path to fill with either fill
+/// rule. Joins, caps and miter limits are as in `options`, and so is a
+/// variable line width. Curves are flattened within `options.tolerance`.
+pub fn stroke_outline(path: &Path, options: &StrokeOptions) -> Path {
+    outline(path, options, false)
+}
+
+impl StrokeOp {
+    /// The outline of this stroke, dashes included, e.g. for export or
+    /// boolean operations.
+    pub fn outline(&self) -> Path {
+        let tolerance = self.stroke_settings.tolerance;
+        match self.stroke_dash.as_ref() {
+            Some(dash) => stroke_outline(&dash.apply(&self.path, tolerance), &dash.stroke_options(self.stroke_settings)),
+            None => stroke_outline(&self.path, &self.stroke_settings),
+        }
+    }
+    /// A fill that draws the same as this stroke.
+    pub fn to_fill(&self) -> FillOp {
+        FillOp {
+            path: self.outline(),
+            fill_color: self.stroke_color,
+            fill_settings: FillOptions::tolerance(self.stroke_settings.tolerance),
+            blend_mode: self.blend_mode,
+        }
+    }
+}
+
+impl FillStrokeOp {
+    /// The outline of the stroke, dashes included, cut against the fill for
+    /// inner and outer strokes. Those are centered strokes twice as wide,
+    /// with the other half cut away.
+    pub fn stroke_outline(&self, tolerance: f32) -> Path {
+        let options = self.stroke_settings.with_tolerance(tolerance);
+        let (path, options) = match self.stroke_dash.as_ref() {
+            Some(dash) => (std::borrow::Cow::Owned(dash.apply(&self.path, tolerance)), dash.stroke_options(options)),
+            None => (std::borrow::Cow::Borrowed(&self.path), options),
+        };
+        let op = match self.stroke_alignment {
+            StrokeAlignment::Center => return stroke_outline(&path, &options),
+            StrokeAlignment::Inside => BooleanOp::Intersection,
+            StrokeAlignment::Outside => BooleanOp::Difference,
+        };
+        let band = stroke_outline(&path, &options.with_line_width(options.line_width * 2.0));
+        let boolean_options = BooleanOptions::default()
+            .with_tolerance(tolerance)
+            .with_fill_rules(FillRule::NonZero, self.fill_settings.fill_rule);
+        op.apply(&band, &self.path, &boolean_options)
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// OUTLINE LOOPS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// A point of a flattened subpath, with the half width there.
+#[derive(Debug, Clone, Copy)]
+struct Vertex {
+    position: Point,
+    half_width: f32,
+    /// Between two segments of the path, where the join style applies,
+    /// rather than within a flattened curve, which is joined round.
+    corner: bool,
+}
+
+/// Traces each subpath's stroke as loops that wind positively over all of
+/// it, then unions them into one outline. With `close_all` every subpath
+/// is stroked as if closed.
+fn outline(path: &Path, options: &StrokeOptions, close_all: bool) -> Path {
+    let mut loops = Path::builder();
+    for (vertices, closed) in flatten(path, options, close_all) {
+        stroke_loops(&vertices, closed, options, &mut loops);
+    }
+    let boolean_options = BooleanOptions::default().with_tolerance(options.tolerance);
+    BooleanOp::Union.apply(&loops.build(), &Path::new(), &boolean_options)
+}
+
+/// Each subpath's vertices, without repeated points, and whether it's
+/// closed.
+fn flatten(path: &Path, options: &StrokeOptions, close_all: bool) -> Vec<(Vec<Vertex>, bool)> {
+    let half_width = |attributes: &[f32]| match options.variable_line_width {
+        Some(ix) => options.line_width * attributes.get(ix).copied().unwrap_or(1.0) * 0.5,
+        None => options.line_width * 0.5,
+    };
+    let mut subpaths = Vec::new();
+    let mut vertices: Vec<Vertex> = Vec::new();
+    let push = |vertices: &mut Vec<Vertex>, position: Point, half_width: f32, corner: bool| {
+        if !position.x.is_finite() || !position.y.is_finite() {
+            return
+        }
+        match vertices.last_mut() {
+            Some(last) if last.position == position => last.corner |= corner,
+            _ => vertices.push(Vertex { position, half_width: half_width.max(0.0), corner }),
+        }
+    };
+    for event in path.iter_with_attributes() {
+        match event {
+            Event::Begin { at } => {
+                vertices.clear();
+                push(&mut vertices, at.0, half_width(at.1), true);
+            }
+            Event::Line { to, .. } => push(&mut vertices, to.0, half_width(to.1), true),
+            Event::Quadratic { from, ctrl, to } => {
+                let widths = (half_width(from.1), half_width(to.1));
+                QuadraticBezierSegment { from: from.0, ctrl, to: to.0 }
+                    .for_each_flattened_with_t(options.tolerance, &mut |line: &LineSegment<f32>, t| {
+                        push(&mut vertices, line.to, widths.0 + (widths.1 - widths.0) * t.end, false)
+                    });
+                push(&mut vertices, to.0, widths.1, true);
+            }
+            Event::Cubic { from, ctrl1, ctrl2, to } => {
+                let widths = (half_width(from.1), half_width(to.1));
+                CubicBezierSegment { from: from.0, ctrl1, ctrl2, to: to.0 }
+                    .for_each_flattened_with_t(options.tolerance, &mut |line: &LineSegment<f32>, t| {
+                        push(&mut vertices, line.to, widths.0 + (widths.1 - widths.0) * t.end, false)
+                    });
+                push(&mut vertices, to.0, widths.1, true);
+            }
+            Event::End { close, .. } => {
+                let closed = close || close_all;
+                if closed && vertices.len() > 1 && vertices[0].position == vertices[vertices.len() - 1].position {
+                    let last = vertices.pop().unwrap();
+                    vertices[0].corner |= last.corner;
+                }
+                subpaths.push((std::mem::take(&mut vertices), closed));
+            }
+        }
+    }
+    subpaths
+}
+
+/// An open subpath is one loop: along its right side, around the end cap,
+/// back along its left side and around the start cap. A closed one is a
+/// loop per side.
+fn stroke_loops(vertices: &[Vertex], closed: bool, options: &StrokeOptions, loops: &mut lyon::path::path::Builder) {
+    let count = vertices.len();
+    if count == 0 || (count == 1 && closed) {
+        return
+    }
+    if count == 1 {
+        // A lone point is drawn as just its caps.
+        let vertex = vertices[0];
+        let out = Vector::new(1.0, 0.0);
+        let mut points = vec![vertex.position - left(out) * vertex.half_width];
+        push_cap(vertex, out, options.start_cap, options.tolerance, &mut points);
+        points.push(vertex.position + left(out) * vertex.half_width);
+        push_cap(vertex, -out, options.start_cap, options.tolerance, &mut points);
+        push_loop(&points, loops);
+        return
+    }
+    let reversed = vertices.iter().rev().copied().collect::<Vec<_>>();
+    let mut right = right_side(vertices, closed, options);
+    let left = right_side(&reversed, closed, options);
+    if closed {
+        push_loop(&right, loops);
+        push_loop(&left, loops);
+        return
+    }
+    let direction = |from: usize, to: usize| (vertices[to].position - vertices[from].position).normalize();
+    push_cap(vertices[count - 1], direction(count - 2, count - 1), options.end_cap, options.tolerance, &mut right);
+    right.extend(left);
+    push_cap(vertices[0], direction(1, 0), options.start_cap, options.tolerance, &mut right);
+    push_loop(&right, loops);
+}
+
+/// The right edge of a subpath's stroke, from its first vertex to its last,
+/// or all the way around a closed one. Outer joins follow the join style.
+/// Inner ones cut across where the two edges cross, or go by way of the
+/// vertex when they don't, so the loop still covers the stroke.
+fn right_side(vertices: &[Vertex], closed: bool, options: &StrokeOptions) -> Vec<Point> {
+    let count = vertices.len();
+    let segments = if closed { count } else { count - 1 };
+    let edges = (0..segments)
+        .map(|ix| {
+            let (from, to) = (vertices[ix], vertices[(ix + 1) % count]);
+            let direction = (to.position - from.position).normalize();
+            let right = -left(direction);
+            (direction, from.position + right * from.half_width, to.position + right * to.half_width)
+        })
+        .collect::<Vec<_>>();
+    let mut points = Vec::new();
+    if !closed {
+        points.push(edges[0].1);
+    }
+    // How far along the current edge the side starts, after an inner join.
+    let mut entered = 0.0;
+    let joins = if closed { 0..count } else { 1..count - 1 };
+    for ix in joins {
+        let (before, start, a) = edges[(ix + segments - 1) % segments];
+        let (after, b, end) = edges[ix];
+        let vertex = vertices[ix];
+        let turn = before.cross(after);
+        if turn == 0.0 && before.dot(after) > 0.0 {
+            points.extend([a, b]);
+            entered = 0.0;
+        } else if turn >= 0.0 {
+            // Turning left, so this side is the outer one. A U-turn is
+            // outer on both sides.
+            let line_join = if vertex.corner { options.line_join } else { LineJoin::Round };
+            push_join(vertex, before, after, (a, b), line_join, options.miter_limit, options.tolerance, &mut points);
+            entered = 0.0;
+        } else {
+            // The first join of a loop can't know where its edge ends up.
+            match crossing((start, a), (b, end)) {
+                Some((t, u, point)) if t > entered && !(closed && ix == 0) => {
+                    points.push(point);
+                    entered = u;
+                }
+                _ => {
+                    points.extend([a, vertex.position, b]);
+                    entered = 0.0;
+                }
+            }
+        }
+    }
+    if !closed {
+        points.push(edges[segments - 1].2);
+    }
+    points
+}
+
+/// Where two edges cross, strictly within both, with how far along each.
+fn crossing(first: (Point, Point), second: (Point, Point)) -> Option<(f64, f64, Point)> {
+    let (p, r) = (first.0.to_f64(), first.1.to_f64() - first.0.to_f64());
+    let (q, s) = (second.0.to_f64(), second.1.to_f64() - second.0.to_f64());
+    let denominator = r.cross(s);
+    let t = (q - p).cross(s) / denominator;
+    let u = (q - p).cross(r) / denominator;
+    let within = |t: f64| t > 0.0 && t < 1.0;
+    (within(t) && within(u)).then(|| (t, u, (p + r * t).to_f32()))
+}
+
+/// Appends the outer side of a join, between `ends`: where the edges before
+/// and after `vertex` stop.
+#[allow(clippy::too_many_arguments)]
+fn push_join(
+    vertex: Vertex,
+    before: Vector,
+    after: Vector,
+    ends: (Point, Point),
+    line_join: LineJoin,
+    miter_limit: f32,
+    tolerance: f32,
+    points: &mut Vec<Point>,
+) {
+    let (center, width) = (vertex.position, vertex.half_width);
+    let (a, b) = ends;
+    // Points out of the corner, and the cosine of half the turn.
+    let bisector = (before - after).normalize();
+    let half_cos = ((1.0 + before.dot(after).clamp(-1.0, 1.0)) * 0.5).sqrt();
+    match line_join {
+        LineJoin::Round if width > 0.0 => {
+            let (from, to) = (a - center, b - center);
+            let turn = from.cross(to);
+            let sweep = if turn == 0.0 {
+                // A U-turn goes around the front.
+                std::f32::consts::PI.copysign(from.cross(bisector))
+            } else {
+                turn.atan2(from.dot(to))
+            };
+            points.extend(arc(center, a, b, sweep, tolerance));
+        }
+        LineJoin::Miter | LineJoin::MiterClip if half_cos * miter_limit * 2.0 >= 1.0 => {
+            points.extend([a, center + bisector * (width / half_cos), b]);
+        }
+        LineJoin::MiterClip => {
+            // Cut square to the bisector at the miter limit.
+            let reach = |point: Point, along: Vector| {
+                point + along * ((miter_limit * width - (point - center).dot(bisector)) / along.dot(bisector))
+            };
+            points.extend([a, reach(a, before), reach(b, -after), b]);
+        }
+        _ => points.extend([a, b]),
+    }
+}
+
+/// Appends a cap from the right side of an end to its left, where `out`
+/// points away from the stroke.
+fn push_cap(vertex: Vertex, out: Vector, line_cap: LineCap, tolerance: f32, points: &mut Vec<Point>) {
+    let (center, width) = (vertex.position, vertex.half_width);
+    let normal = left(out) * width;
+    match line_cap {
+        LineCap::Butt => {}
+        LineCap::Square => points.extend([center - normal + out * width, center + normal + out * width]),
+        LineCap::Round if width > 0.0 => {
+            let arc = arc(center, center - normal, center + normal, std::f32::consts::PI, tolerance);
+            points.extend(&arc[1..arc.len() - 1]);
+        }
+        LineCap::Round => {}
+    }
+}
+
+/// Points along an arc around `center` from `from` to `to`, within
+/// `tolerance` of the circle. The ends are exactly `from` and `to`.
+fn arc(center: Point, from: Point, to: Point, sweep: f32, tolerance: f32) -> Vec<Point> {
+    let start = from - center;
+    let radius = start.length();
+    let step = match radius > tolerance {
+        true => 2.0 * (1.0 - tolerance / radius).acos(),
+        false => std::f32::consts::FRAC_PI_2,
+    };
+    let steps = (sweep.abs() / step.max(f32::EPSILON)).ceil().clamp(1.0, 1024.0) as usize;
+    let start_angle = start.y.atan2(start.x);
+    let mut points = vec![from];
+    points.extend((1..steps).map(|ix| {
+        let angle = start_angle + sweep * ix as f32 / steps as f32;
+        center + Vector::new(angle.cos(), angle.sin()) * radius
+    }));
+    points.push(to);
+    points
+}
+
+/// Pushes a closed loop, without repeated points.
+fn push_loop(points: &[Point], loops: &mut lyon::path::path::Builder) {
+    let mut points = points.to_vec();
+    points.dedup();
+    if points.len() > 1 && points[0] == points[points.len() - 1] {
+        points.pop();
+    }
+    if points.len() < 3 {
+        return
+    }
+    loops.begin(points[0]);
+    for point in points[1..].iter() {
+        loops.line_to(*point);
+    }
+    loops.end(true);
+}
+
+/// `direction` turned a quarter counter-clockwise (y up).
+fn left(direction: Vector) -> Vector {
+    Vector::new(-direction.y, direction.x)
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// TESTS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lyon::math::{point, Box2D};
+    use lyon::path::Winding;
+    use lyon::path::iterator::PathIterator;
+    use crate::data::RGBA;
+    use crate::data::draw_cmds::BlendMode;
+
+    fn square(min: f32, size: f32) -> Path {
+        let mut builder = Path::builder();
+        builder.add_rectangle(&Box2D::new(point(min, min), point(min + size, min + size)), Winding::Positive);
+        builder.build()
+    }
+
+    fn polyline(points: &[(f32, f32)]) -> Path {
+        let mut builder = Path::builder();
+        builder.begin(point(points[0].0, points[0].1));
+        for (x, y) in points[1..].iter() {
+            builder.line_to(point(*x, *y));
+        }
+        builder.end(false);
+        builder.build()
+    }
+
+    fn bounds(path: &Path) -> Box2D {
+        Box2D::from_points(path.iter().flattened(0.001).filter_map(|event| match event {
+            Event::Begin { at } => Some(at),
+            Event::Line { to, .. } => Some(to),
+            _ => None,
+        }))
+    }
+
+    /// Signed, so holes wound the other way are taken off.
+    fn area(path: &Path) -> f32 {
+        let mut area = 0.0;
+        for event in path.iter().flattened(0.001) {
+            if let Event::Line { from, to } | Event::End { last: from, first: to, close: true } = event {
+                area += from.x * to.y - to.x * from.y;
+            }
+        }
+        area / 2.0
+    }
+
+    fn assert_bounds(path: &Path, min: (f32, f32), max: (f32, f32), tolerance: f32) {
+        let bounds = bounds(path);
+        let near = |a: Point, (x, y): (f32, f32)| (a - point(x, y)).length() <= tolerance;
+        assert!(near(bounds.min, min) && near(bounds.max, max), "{:?} instead of {:?} to {:?}", bounds, min, max);
+    }
+
+    fn assert_area(path: &Path, expected: f32, tolerance: f32) {
+        assert!((area(path) - expected).abs() <= tolerance, "area {} instead of {}", area(path), expected);
+    }
+
+    #[test]
+    fn offsets_grow_and_shrink_a_square() {
+        let path = square(0.0, 10.0);
+        let corner = std::f32::consts::PI * 4.0;
+        for (line_join, grown_area) in [(LineJoin::Miter, 196.0), (LineJoin::Round, 180.0 + corner), (LineJoin::Bevel, 188.0)] {
+            let options = OffsetOptions::default().with_line_join(line_join).with_tolerance(0.01);
+            let grown = offset_path(&path, 2.0, &options);
+            assert_bounds(&grown, (-2.0, -2.0), (12.0, 12.0), 1e-3);
+            assert_area(&grown, grown_area, 0.1);
+            // The corners that open up when shrinking a convex shape are
+            // on its inside, so they stay sharp.
+            let shrunk = offset_path(&path, -2.0, &options);
+            assert_bounds(&shrunk, (2.0, 2.0), (8.0, 8.0), 1e-3);
+            assert_area(&shrunk, 36.0, 1e-3);
+        }
+        // Shrinking past the middle leaves nothing, and a zero offset the
+        // path itself.
+        assert!(offset_path(&path, -6.0, &OffsetOptions::default()).iter().next().is_none());
+        assert_area(&offset_path(&path, 0.0, &OffsetOptions::default()), 100.0, 1e-3);
+    }
+
+    #[test]
+    fn miters_past_the_limit_are_beveled() {
+        // A thin spike, whose tip's miter is far longer than the offset.
+        let mut builder = Path::builder();
+        builder.begin(point(0.0, 0.0));
+        builder.line_to(point(20.0, 1.0));
+        builder.line_to(point(0.0, 2.0));
+        builder.end(true);
+        let spike = builder.build();
+        let limited = offset_path(&spike, 1.0, &OffsetOptions::default().with_miter_limit(2.0));
+        assert!(bounds(&limited).max.x < 21.5, "{:?}", bounds(&limited));
+        let unlimited = offset_path(&spike, 1.0, &OffsetOptions::default().with_miter_limit(100.0));
+        assert!(bounds(&unlimited).max.x > 30.0, "{:?}", bounds(&unlimited));
+    }
+
+    #[test]
+    fn open_strokes_end_in_their_caps() {
+        let path = polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]);
+        let options = StrokeOptions::tolerance(0.01).with_line_width(2.0);
+        let butt = stroke_outline(&path, &options.with_line_cap(LineCap::Butt));
+        assert_bounds(&butt, (0.0, -1.0), (11.0, 10.0), 1e-3);
+        assert_area(&butt, 40.0, 1e-3);
+        let square = stroke_outline(&path, &options.with_line_cap(LineCap::Square));
+        assert_bounds(&square, (-1.0, -1.0), (11.0, 11.0), 1e-3);
+        assert_area(&square, 44.0, 1e-3);
+        let round = stroke_outline(&path, &options.with_line_cap(LineCap::Round));
+        assert_bounds(&round, (-1.0, -1.0), (11.0, 11.0), 0.02);
+        assert_area(&round, 40.0 + std::f32::consts::PI, 0.05);
+    }
+
+    #[test]
+    fn aligned_strokes_stay_on_their_side_of_the_fill() {
+        let fill_stroke = |stroke_alignment| FillStrokeOp {
+            path: square(0.0, 10.0),
+            fill_color: RGBA::RED.into(),
+            stroke_color: RGBA::BLACK.into(),
+            fill_settings: FillOptions::default(),
+            stroke_settings: StrokeOptions::default().with_line_width(2.0),
+            stroke_dash: None,
+            stroke_alignment,
+            blend_mode: BlendMode::default(),
+        };
+        let fill = square(0.0, 10.0);
+        let within = |outline: &Path| area(&BooleanOp::Intersection.apply(outline, &fill, &BooleanOptions::default()));
+
+        let center = fill_stroke(StrokeAlignment::Center).stroke_outline(0.01);
+        assert_bounds(&center, (-1.0, -1.0), (11.0, 11.0), 1e-3);
+        assert_area(&center, 144.0 - 64.0, 1e-3);
+        assert!((within(&center) - 36.0).abs() < 1e-3);
+
+        let inside = fill_stroke(StrokeAlignment::Inside).stroke_outline(0.01);
+        assert_bounds(&inside, (0.0, 0.0), (10.0, 10.0), 1e-3);
+        assert_area(&inside, 100.0 - 36.0, 1e-3);
+        assert!((within(&inside) - area(&inside)).abs() < 1e-3);
+
+        let outside = fill_stroke(StrokeAlignment::Outside).stroke_outline(0.01);
+        assert_bounds(&outside, (-2.0, -2.0), (12.0, 12.0), 1e-3);
+        assert_area(&outside, 196.0 - 100.0, 1e-3);
+        assert!(within(&outside).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,431 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::data::gpu_types::GpuVertex;
+use crate::data::picture::{DrawNode, MeshBuffer};
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// MESH OPTIMIZATION
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IndexFormat {
+    Uint16,
+    Uint32,
+}
+
+impl IndexFormat {
+    pub fn size(&self) -> usize {
+        match self {
+            IndexFormat::Uint16 => 2,
+            IndexFormat::Uint32 => 4,
+        }
+    }
+}
+
+/// A run of the optimized index buffer that is drawn with one index format
+/// and base vertex.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexChunk {
+    pub format: IndexFormat,
+    /// Where the chunk's indices are in `OptimizedMesh::index_bytes`.
+    pub bytes: Range<u64>,
+    /// The positions in the original index buffer the chunk stands for.
+    pub indices: Range<u32>,
+    /// Added to every index in the chunk.
+    pub base_vertex: i32,
+}
+
+/// A mesh with identical vertices welded, triangles reordered for the
+/// vertex cache, and indices packed into 16 bits wherever a chunk spans
+/// fewer than 65535 vertices.
+///
+/// Triangles only move within runs of the same `prim_id` that no batch
+/// boundary cuts, so batch index ranges keep drawing the same triangles,
+/// and reordering never changes how overlapping shapes blend. Draw a range
+/// of the original index buffer with the pieces from `split`.
+#[derive(Debug, Clone, Default)]
+pub struct OptimizedMesh {
+    pub vertices: Vec<GpuVertex>,
+    /// The chunks back to back, each starting on a four byte boundary.
+    pub index_bytes: Vec<u8>,
+    pub chunks: Vec<IndexChunk>,
+}
+
+impl OptimizedMesh {
+    /// Simulated post-transform cache size used to reorder triangles.
+    pub const CACHE_SIZE: usize = 32;
+    /// The widest vertex span a chunk may have to use 16-bit indices. Stays
+    /// clear of `u16::MAX`, the primitive restart value.
+    pub const MAX_U16_SPAN: u32 = u16::MAX as u32 - 1;
+
+    /// `nodes` are the ones drawn from `mesh`, whose batch boundaries are
+    /// kept.
+    pub fn new(mesh: &MeshBuffer, nodes: &[DrawNode]) -> Self {
+        let (welded, vertices) = weld(&mesh.vertices);
+        let mut triangles = mesh.indices
+            .chunks_exact(3)
+            .map(|triangle| [welded[triangle[0] as usize], welded[triangle[1] as usize], welded[triangle[2] as usize]])
+            .collect::<Vec<_>>();
+        for run in runs(&triangles, &vertices, nodes) {
+            reorder(&mut triangles[run]);
+        }
+        // Renumber by first use, so each chunk spans few vertices and
+        // vertex fetches go in order.
+        let mut renumbered = vec![u32::MAX; vertices.len()];
+        let mut ordered = Vec::with_capacity(vertices.len());
+        for vertex in triangles.iter_mut().flatten() {
+            if renumbered[*vertex as usize] == u32::MAX {
+                renumbered[*vertex as usize] = ordered.len() as u32;
+                ordered.push(vertices[*vertex as usize]);
+            }
+            *vertex = renumbered[*vertex as usize];
+        }
+        let mut optimized = OptimizedMesh { vertices: ordered, index_bytes: Vec::new(), chunks: Vec::new() };
+        let mut start = 0;
+        while start < triangles.len() {
+            let end = chunk_end(&triangles, start);
+            optimized.push_chunk(&triangles[start..end], start as u32 * 3);
+            start = end;
+        }
+        optimized
+    }
+    /// The draws for a range of the original index buffer: each chunk it
+    /// touches, with the range of the chunk's own indices to draw.
+    pub fn split(&self, indices: Range<u32>) -> impl Iterator<Item = (&IndexChunk, Range<u32>)> + '_ {
+        split(&self.chunks, indices)
+    }
+    fn push_chunk(&mut self, triangles: &[[u32; 3]], first_index: u32) {
+        let min = triangles.iter().flatten().copied().min().unwrap();
+        let max = triangles.iter().flatten().copied().max().unwrap();
+        let format = if max - min <= OptimizedMesh::MAX_U16_SPAN {
+            IndexFormat::Uint16
+        } else {
+            IndexFormat::Uint32
+        };
+        let start = self.index_bytes.len();
+        for index in triangles.iter().flatten() {
+            match format {
+                IndexFormat::Uint16 => self.index_bytes.extend_from_slice(&((index - min) as u16).to_le_bytes()),
+                IndexFormat::Uint32 => self.index_bytes.extend_from_slice(&(index - min).to_le_bytes()),
+            }
+        }
+        let end = self.index_bytes.len();
+        self.index_bytes.resize((end + 3) & !3, 0);
+        self.chunks.push(IndexChunk {
+            format,
+            bytes: start as u64..end as u64,
+            indices: first_index..first_index + triangles.len() as u32 * 3,
+            base_vertex: min as i32,
+        });
+    }
+}
+
+/// `OptimizedMesh::split`, for chunks kept without their mesh.
+pub fn split(chunks: &[IndexChunk], indices: Range<u32>) -> impl Iterator<Item = (&IndexChunk, Range<u32>)> + '_ {
+    let first = chunks.partition_point(|chunk| chunk.indices.end <= indices.start);
+    chunks[first..]
+        .iter()
+        .take_while(move |chunk| chunk.indices.start < indices.end)
+        .map(move |chunk| {
+            let start = indices.start.max(chunk.indices.start) - chunk.indices.start;
+            let end = indices.end.min(chunk.indices.end) - chunk.indices.start;
+            (chunk, start..end)
+        })
+        .filter(|(_, range)| !range.is_empty())
+}
+
+/// Maps each vertex to the first one identical to it, and returns the
+/// distinct vertices.
+fn weld(vertices: &[GpuVertex]) -> (Vec<u32>, Vec<GpuVertex>) {
+    let mut distinct: Vec<GpuVertex> = Vec::new();
+    let mut seen: HashMap<(u32, u32, u32), u32> = HashMap::with_capacity(vertices.len());
+    let welded = vertices
+        .iter()
+        .map(|vertex| {
+            // Adding zero turns -0.0 into 0.0, so both weld.
+            let [x, y] = vertex.position.map(|coordinate| (coordinate + 0.0).to_bits());
+            *seen.entry((x, y, vertex.prim_id)).or_insert_with(|| {
+                distinct.push(*vertex);
+                distinct.len() as u32 - 1
+            })
+        })
+        .collect();
+    (welded, distinct)
+}
+
+/// Triangle ranges that may be reordered: split at every batch boundary and
+/// wherever the primitive changes.
+fn runs(triangles: &[[u32; 3]], vertices: &[GpuVertex], nodes: &[DrawNode]) -> Vec<Range<usize>> {
+    let mut cuts = vec![false; triangles.len() + 1];
+    for node in nodes {
+        node.visit(&mut |node| {
+            if let DrawNode::Batch(batch) = node {
+                for index in [batch.indices.start, batch.indices.end] {
+                    if let Some(cut) = cuts.get_mut(index as usize / 3) {
+                        *cut = true;
+                    }
+                }
+            }
+        });
+    }
+    for (ix, pair) in triangles.windows(2).enumerate() {
+        if vertices[pair[1][0] as usize].prim_id != vertices[pair[0][0] as usize].prim_id {
+            cuts[ix + 1] = true;
+        }
+    }
+    cuts[triangles.len()] = true;
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for (ix, cut) in cuts.iter().enumerate().skip(1) {
+        if *cut {
+            runs.push(start..ix);
+            start = ix;
+        }
+    }
+    runs
+}
+
+/// Where the chunk starting at `start` ends: as far as the vertices it
+/// spans still fit 16-bit indices. A triangle too wide for that gets a
+/// 32-bit chunk of its own.
+fn chunk_end(triangles: &[[u32; 3]], start: usize) -> usize {
+    let span = |triangle: &[u32; 3]| {
+        let min = triangle.iter().min().unwrap();
+        let max = triangle.iter().max().unwrap();
+        (*min, *max)
+    };
+    let (mut min, mut max) = span(&triangles[start]);
+    if max - min > OptimizedMesh::MAX_U16_SPAN {
+        return start + 1
+    }
+    let mut end = start + 1;
+    while end < triangles.len() {
+        let (low, high) = span(&triangles[end]);
+        let (low, high) = (low.min(min), high.max(max));
+        if high - low > OptimizedMesh::MAX_U16_SPAN {
+            break
+        }
+        min = low;
+        max = high;
+        end += 1;
+    }
+    end
+}
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// VERTEX CACHE ORDER
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// Reorders triangles for a small post-transform vertex cache, after Tom
+/// Forsyth's "Linear-Speed Vertex Cache Optimisation": each step emits the
+/// best scoring triangle among those touching cached vertices, favouring
+/// recently used vertices and ones with few triangles left.
+fn reorder(triangles: &mut [[u32; 3]]) {
+    if triangles.len() < 3 {
+        return
+    }
+    let mut adjacency: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (ix, triangle) in triangles.iter().enumerate() {
+        for vertex in triangle {
+            adjacency.entry(*vertex).or_default().push(ix);
+        }
+    }
+    let mut remaining: HashMap<u32, u32> = adjacency.iter().map(|(vertex, tris)| (*vertex, tris.len() as u32)).collect();
+    let mut emitted = vec![false; triangles.len()];
+    let mut cache: Vec<u32> = Vec::with_capacity(OptimizedMesh::CACHE_SIZE + 3);
+    let mut order = Vec::with_capacity(triangles.len());
+    let mut next_unemitted = 0;
+    let vertex_score = |cache: &[u32], remaining: &HashMap<u32, u32>, vertex: u32| -> f32 {
+        let left = remaining[&vertex];
+        if left == 0 {
+            return -1.0
+        }
+        let cache_score = match cache.iter().position(|x| *x == vertex) {
+            Some(position) if position < 3 => 0.75,
+            Some(position) => {
+                let scale = 1.0 / (OptimizedMesh::CACHE_SIZE - 3) as f32;
+                (1.0 - (position - 3) as f32 * scale).powf(1.5)
+            }
+            None => 0.0,
+        };
+        cache_score + 2.0 / (left as f32).sqrt()
+    };
+    while order.len() < triangles.len() {
+        let best = cache
+            .iter()
+            .flat_map(|vertex| adjacency[vertex].iter().copied())
+            .filter(|ix| !emitted[*ix])
+            .map(|ix| {
+                let score = triangles[ix].iter().map(|vertex| vertex_score(&cache, &remaining, *vertex)).sum::<f32>();
+                (ix, score)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.cmp(&a.0)))
+            .map(|(ix, _)| ix);
+        let ix = match best {
+            Some(ix) => ix,
+            None => {
+                while emitted[next_unemitted] {
+                    next_unemitted += 1;
+                }
+                next_unemitted
+            }
+        };
+        emitted[ix] = true;
+        order.push(ix);
+        for vertex in triangles[ix] {
+            *remaining.get_mut(&vertex).unwrap() -= 1;
+            cache.retain(|x| *x != vertex);
+        }
+        for vertex in triangles[ix].iter().rev() {
+            cache.insert(0, *vertex);
+        }
+        cache.truncate(OptimizedMesh::CACHE_SIZE);
+    }
+    let reordered = order.iter().map(|ix| triangles[*ix]).collect::<Vec<_>>();
+    triangles.copy_from_slice(&reordered);
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// TESTS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::draw_cmds::BlendMode;
+    use crate::data::picture::DrawBatch;
+
+    fn vertex(x: f32, y: f32, prim_id: u32) -> GpuVertex {
+        GpuVertex { position: [x, y], prim_id }
+    }
+
+    fn batch(indices: Range<u32>) -> DrawNode {
+        DrawNode::Batch(DrawBatch { blend_mode: BlendMode::SourceOver, indices, instances: None })
+    }
+
+    /// A chunk's indices, with the base vertex added back.
+    fn chunk_indices(optimized: &OptimizedMesh, chunk: &IndexChunk) -> Vec<u32> {
+        let bytes = &optimized.index_bytes[chunk.bytes.start as usize..chunk.bytes.end as usize];
+        let indices = match chunk.format {
+            IndexFormat::Uint16 => bytes.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]]) as u32).collect::<Vec<_>>(),
+            IndexFormat::Uint32 => bytes.chunks_exact(4).map(|quad| u32::from_le_bytes([quad[0], quad[1], quad[2], quad[3]])).collect(),
+        };
+        indices.into_iter().map(|index| (index as i32 + chunk.base_vertex) as u32).collect()
+    }
+
+    /// The triangles drawn for a range of the original index buffer, as
+    /// vertex bits, sorted so that order doesn't matter.
+    fn drawn(optimized: &OptimizedMesh, indices: Range<u32>) -> Vec<[(u32, u32, u32); 3]> {
+        let mut triangles = optimized
+            .split(indices)
+            .flat_map(|(chunk, range)| chunk_indices(optimized, chunk)[range.start as usize..range.end as usize].to_vec())
+            .map(|index| {
+                let vertex = optimized.vertices[index as usize];
+                (vertex.position[0].to_bits(), vertex.position[1].to_bits(), vertex.prim_id)
+            })
+            .collect::<Vec<_>>()
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect::<Vec<_>>();
+        triangles.sort();
+        triangles
+    }
+
+    #[test]
+    fn welds_signed_zeros_but_not_primitives() {
+        let vertices = [vertex(0.0, 1.0, 0), vertex(-0.0, 1.0, 0), vertex(0.0, 1.0, 1), vertex(0.0, -0.0, 0), vertex(0.0, 0.0, 0)];
+        let (welded, distinct) = weld(&vertices);
+        assert_eq!(welded, vec![0, 0, 1, 2, 2]);
+        assert_eq!(distinct.len(), 3);
+        assert_eq!(distinct[1].prim_id, 1);
+    }
+
+    #[test]
+    fn chunks_split_where_the_span_outgrows_u16() {
+        let wide = OptimizedMesh::MAX_U16_SPAN + 10;
+        let triangles = [[0, 1, 2], [3, 4, 5], [wide, wide + 1, wide + 2], [0, wide, 1], [6, 7, 8]];
+        assert_eq!(chunk_end(&triangles, 0), 2);
+        assert_eq!(chunk_end(&triangles, 2), 3);
+        assert_eq!(chunk_end(&triangles, 3), 4);
+        assert_eq!(chunk_end(&triangles, 4), 5);
+        // A span of exactly `MAX_U16_SPAN` still fits.
+        assert_eq!(chunk_end(&[[0, 1, 2], [1, 2, OptimizedMesh::MAX_U16_SPAN]], 0), 2);
+
+        let mut optimized = OptimizedMesh::default();
+        let mut start = 0;
+        while start < triangles.len() {
+            let end = chunk_end(&triangles, start);
+            optimized.push_chunk(&triangles[start..end], start as u32 * 3);
+            start = end;
+        }
+        let formats = optimized.chunks.iter().map(|chunk| chunk.format).collect::<Vec<_>>();
+        assert_eq!(formats, [IndexFormat::Uint16, IndexFormat::Uint16, IndexFormat::Uint32, IndexFormat::Uint16]);
+        let base_vertices = optimized.chunks.iter().map(|chunk| chunk.base_vertex).collect::<Vec<_>>();
+        assert_eq!(base_vertices, [0, wide as i32, 0, 6]);
+        let ranges = optimized.chunks.iter().map(|chunk| chunk.indices.clone()).collect::<Vec<_>>();
+        assert_eq!(ranges, [0..6, 6..9, 9..12, 12..15]);
+        for chunk in optimized.chunks.iter() {
+            assert_eq!(chunk.bytes.start % 4, 0);
+            assert_eq!(chunk.bytes.end - chunk.bytes.start, (chunk.indices.len() * chunk.format.size()) as u64);
+        }
+        let indices = optimized.chunks.iter().flat_map(|chunk| chunk_indices(&optimized, chunk)).collect::<Vec<_>>();
+        assert_eq!(indices, triangles.iter().flatten().copied().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn split_clips_ranges_to_each_chunk() {
+        let chunk = |indices: Range<u32>| IndexChunk { format: IndexFormat::Uint16, bytes: 0..0, indices, base_vertex: 0 };
+        let chunks = [chunk(0..6), chunk(6..9), chunk(9..12)];
+        let pieces = |indices: Range<u32>| {
+            split(&chunks, indices).map(|(chunk, range)| (chunk.indices.start, range)).collect::<Vec<_>>()
+        };
+        assert_eq!(pieces(0..12), [(0, 0..6), (6, 0..3), (9, 0..3)]);
+        assert_eq!(pieces(3..10), [(0, 3..6), (6, 0..3), (9, 0..1)]);
+        assert_eq!(pieces(6..9), [(6, 0..3)]);
+        assert_eq!(pieces(7..8), [(6, 1..2)]);
+        assert_eq!(pieces(12..15), []);
+        assert_eq!(pieces(4..4), []);
+    }
+
+    #[test]
+    fn reordering_keeps_batches_and_primitives() {
+        // Two overlapping grids of quads, each quad with its own four
+        // vertices as the tessellators emit them, in a scrambled order.
+        let mut mesh = MeshBuffer::new();
+        for prim_id in 0..2 {
+            for ix in 0..36 {
+                let quad = ix * 7 % 36;
+                let (x, y) = ((quad % 6) as f32, (quad / 6) as f32);
+                let first = mesh.vertices.len() as u32;
+                mesh.vertices.extend([vertex(x, y, prim_id), vertex(x + 1.0, y, prim_id), vertex(x + 1.0, y + 1.0, prim_id), vertex(x, y + 1.0, prim_id)]);
+                mesh.indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+            }
+        }
+        // The first primitive is cut by a batch boundary, the second shares
+        // a batch with the end of the first.
+        let nodes = [batch(0..120), batch(120..432)];
+        let optimized = OptimizedMesh::new(&mesh, &nodes);
+        assert_eq!(optimized.vertices.len(), 2 * 49);
+
+        let original = OptimizedMesh {
+            vertices: mesh.vertices.clone(),
+            index_bytes: mesh.indices.iter().flat_map(|index| index.to_le_bytes()).collect(),
+            chunks: vec![IndexChunk {
+                format: IndexFormat::Uint32,
+                bytes: 0..mesh.indices.len() as u64 * 4,
+                indices: 0..mesh.indices.len() as u32,
+                base_vertex: 0,
+            }],
+        };
+        for range in [0..120, 120..216, 216..432, 0..432] {
+            assert_eq!(drawn(&optimized, range.clone()), drawn(&original, range));
+        }
+        assert_ne!(
+            optimized.chunks.iter().flat_map(|chunk| chunk_indices(&optimized, chunk)).take(120).collect::<Vec<_>>(),
+            mesh.indices[..120],
+            "nothing was reordered",
+        );
+    }
+}
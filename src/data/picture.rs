@@ -1,20 +1,28 @@
-use crate::data::{self, Resolution, PictureResolution};
+use crate::data::{self, Resolution, PictureResolution, ViewInfo};
 use crate::data::collections::CowCollection;
 use crate::data::draw_cmds::*;
-use crate::frontend::DrawableObject;
+use crate::data::filters::Filter;
+use crate::data::dash::StrokeDash;
+use crate::data::symbols::Symbol;
+use crate::data::spatial;
+use crate::data::color::ThemeColor;
+use crate::data::camera::Camera;
+use crate::frontend::{ColorScheme, DrawableObject};
 use itertools::Itertools;
+use either::Either;
+use rayon::prelude::*;
 use lyon::math::Point;
 use lyon::path::PathEvent;
 use lyon::tessellation::geometry_builder::*;
 use lyon::tessellation::{self, FillOptions, FillTessellator, StrokeOptions, StrokeTessellator};
 
-use super::gpu_types::GpuVertex;
+use super::gpu_types::{GpuInstance, GpuVertex};
 
 //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
 // TESSELATION-SETTINGS
 //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct TesselationSettings {
     tolerance: f32,
 }
@@ -30,6 +38,27 @@ impl Default for TesselationSettings {
 impl TesselationSettings {
     // pub const DEFAULT_TOLERANCE: f32 = lyon::tessellation::FillOptions::DEFAULT_TOLERANCE;
     pub const DEFAULT_TOLERANCE: f32 = 0.01;
+    /// The largest distance, in physical pixels, that flattened curves may
+    /// stray from the true ones.
+    pub const PIXEL_TOLERANCE: f32 = 0.2;
+
+    /// Picture space tolerance for where the camera currently is, so curves
+    /// are flattened to a fraction of a physical pixel at any zoom.
+    pub fn for_camera(camera: &Camera, view: ViewInfo, picture: PictureResolution) -> Self {
+        let [scale_x, scale_y] = camera.scale(view, picture);
+        TesselationSettings::with_pixel_scale(scale_x.max(scale_y))
+    }
+    /// For `scale` physical pixels per picture unit.
+    pub fn with_pixel_scale(scale: f32) -> Self {
+        let tolerance = TesselationSettings::PIXEL_TOLERANCE / scale;
+        if !tolerance.is_finite() || tolerance <= 0.0 {
+            return TesselationSettings::default()
+        }
+        TesselationSettings { tolerance }
+    }
+    pub fn tolerance(&self) -> f32 {
+        self.tolerance
+    }
 
     pub const fn default_fill_options() -> lyon::tessellation::FillOptions {
         lyon::tessellation::FillOptions::DEFAULT
@@ -42,7 +71,8 @@ impl TesselationSettings {
     }
 }
 
-const STROKE_WIDTH: lyon::path::AttributeIndex = 0;
+/// The path attribute that scales the line width of variable width strokes.
+pub const STROKE_WIDTH: lyon::path::AttributeIndex = 0;
 
 //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
 // PICTURE
@@ -143,12 +173,121 @@ impl<'a, T> Content<&'a T, &'a [T]> where T: Clone {
 
 pub(crate) type MeshBuffer = VertexBuffers<data::gpu_types::GpuVertex, u32>;
 pub(crate) type GpuPrimitives = Vec<data::gpu_types::GpuPrimitive>;
+pub(crate) type GpuInstances = Vec<GpuInstance>;
+/// A symbol already tessellated into a mesh.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolMesh {
+    pub indices: std::ops::Range<u32>,
+    /// Picture space bounds of the symbol's geometry, before instancing.
+    /// `None` when it draws nothing.
+    pub bounds: Option<lyon::math::Box2D>,
+}
+/// The symbols already tessellated into a mesh, by symbol id.
+pub(crate) type SymbolMeshes = std::collections::HashMap<u64, SymbolMesh>;
+
+/// A contiguous range of the index buffer that is drawn with a single blend
+/// mode, and therefore a single pipeline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrawBatch {
+    pub blend_mode: BlendMode,
+    pub indices: std::ops::Range<u32>,
+    /// Set for a symbol's mesh, which is drawn once per instance in this
+    /// range of the instance buffer.
+    pub instances: Option<std::ops::Range<u32>>,
+}
+
+/// A group of nodes that is rendered into its own offscreen target, and then
+/// composited onto its parent once.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupNode {
+    pub opacity: f32,
+    pub blend_mode: BlendMode,
+    /// Picture space bounds of everything drawn by the group's children,
+    /// expanded to fit the filters.
+    pub bounds: lyon::math::Box2D,
+    pub filters: Vec<Filter>,
+    pub children: Vec<DrawNode>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawNode {
+    Batch(DrawBatch),
+    Group(GroupNode),
+}
+
+impl DrawBatch {
+    /// Extends the last batch when the blend mode is unchanged, otherwise
+    /// starts a new one. Ops that may need a composite pass are never merged,
+    /// since each one must blend against the backdrop left by the previous.
+    pub(crate) fn push(nodes: &mut Vec<DrawNode>, blend_mode: BlendMode, indices: std::ops::Range<u32>) {
+        if indices.is_empty() {
+            return
+        }
+        if let Some(DrawNode::Batch(last)) = nodes.last_mut() {
+            let mergeable = last.blend_mode == blend_mode
+                && last.instances.is_none()
+                && !blend_mode.requires_composite_pass_over(false)
+                && last.indices.end == indices.start;
+            if mergeable {
+                last.indices.end = indices.end;
+                return
+            }
+        }
+        nodes.push(DrawNode::Batch(DrawBatch { blend_mode, indices, instances: None }));
+    }
+}
+
+/// Maps a `prim_id` back to the index of the item that drew it, given the
+/// primitive ranges of each item.
+pub fn op_for_primitive(op_primitives: &[std::ops::Range<u32>], prim_id: u32) -> Option<usize> {
+    let ix = op_primitives.partition_point(|range| range.end <= prim_id);
+    op_primitives
+        .get(ix)
+        .filter(|range| range.contains(&prim_id))
+        .map(|_| ix)
+}
+
+impl DrawNode {
+    /// Shifts every index and instance range, for nodes tessellated into
+    /// separate buffers that are appended after `indices` other indices and
+    /// `instances` other instances.
+    pub(crate) fn offset(&mut self, indices: u32, instances: u32) {
+        match self {
+            DrawNode::Batch(batch) => {
+                batch.indices = batch.indices.start + indices..batch.indices.end + indices;
+                if let Some(range) = batch.instances.as_mut() {
+                    *range = range.start + instances..range.end + instances;
+                }
+            }
+            DrawNode::Group(group) => {
+                for child in group.children.iter_mut() {
+                    child.offset(indices, instances);
+                }
+            }
+        }
+    }
+    /// Visits this node and every descendant, parents before children.
+    pub fn visit(&self, f: &mut impl FnMut(&DrawNode)) {
+        f(self);
+        if let DrawNode::Group(group) = self {
+            for child in group.children.iter() {
+                child.visit(f);
+            }
+        }
+    }
+}
 
 
 #[derive(Debug)]
 pub struct TessellatedContent {
     pub(crate) mesh: MeshBuffer,
     pub(crate) primitives: GpuPrimitives,
+    /// The unresolved color of each primitive.
+    pub(crate) primitive_colors: Vec<ThemeColor>,
+    pub(crate) instances: GpuInstances,
+    pub(crate) nodes: Vec<DrawNode>,
+    /// The primitives of each item, in item order.
+    pub(crate) op_primitives: Vec<std::ops::Range<u32>>,
     pub(crate) picture_resolution: Resolution<f32>,
     pub(crate) needs_update: bool,
 }
@@ -163,94 +302,579 @@ pub type TessellatedPicture = TessellatedContent;
 
 
 impl<T> Content<T, Vec<T>> where T: DrawableObject {
+    pub(crate) fn tessellate_sequential(&self) -> TessellatedContent { self.as_ref().tessellate_sequential() }
+}
+
+impl<T> Content<T, Vec<T>> where T: DrawableObject + Sync {
     pub(crate) fn tessellate(&self) -> TessellatedContent { self.as_ref().tessellate() }
+    pub(crate) fn tessellate_with(&self, settings: TesselationSettings) -> TessellatedContent {
+        self.as_ref().tessellate_with(settings)
+    }
+    pub(crate) fn tessellate_until(
+        &self,
+        settings: Option<TesselationSettings>,
+        cancelled: impl Fn() -> bool + Sync,
+    ) -> Option<TessellatedContent> {
+        self.as_ref().tessellate_until(settings, cancelled)
+    }
 }
 
 impl<'a, T> Content<&'a T, &'a [T]> where T: DrawableObject {
-    pub(crate) fn tessellate(&self) -> TessellatedContent {
+    /// One op after another on the calling thread.
+    pub(crate) fn tessellate_sequential(&self) -> TessellatedContent {
         let mut mesh: VertexBuffers<GpuVertex, u32> = VertexBuffers::new();
         let mut primitives: Vec<data::gpu_types::GpuPrimitive> = Vec::new();
+        let mut primitive_colors: Vec<ThemeColor> = Vec::new();
+        let mut instances: Vec<GpuInstance> = Vec::new();
+        let mut symbols: SymbolMeshes = SymbolMeshes::new();
+        let mut nodes: Vec<DrawNode> = Vec::new();
+        let mut op_primitives: Vec<std::ops::Range<u32>> = Vec::new();
         let mut fill_tessellator: FillTessellator = FillTessellator::new();
         let mut stroke_tessellator: StrokeTessellator = StrokeTessellator::new();
+        let mut op_tessellator = OpTessellator {
+            mesh: &mut mesh,
+            primitives: &mut primitives,
+            primitive_colors: &mut primitive_colors,
+            color_scheme: ColorScheme::default(),
+            instances: &mut instances,
+            symbols: &mut symbols,
+            fill_tessellator: &mut fill_tessellator,
+            stroke_tessellator: &mut stroke_tessellator,
+            tolerance: None,
+        };
         for op in self.items.into_iter() {
-            match op.draw() {
-                DrawOp::Fill(FillOp { path, fill_color, fill_settings }) => {
-                    primitives.push({
-                        data::gpu_types::GpuPrimitive::from_u8_rgba(fill_color)
-                    });
-                    let fill_color_ix = primitives.len() as u32 - 1;
-                    let _: () = fill_tessellator.tessellate_path(
-                            &path,
-                            &fill_settings,
-                            &mut BuffersBuilder::new(
-                                &mut mesh,
-                                data::VertexConstructor {
-                                    prim_id: fill_color_ix,
-                                },
-                            ),
-                        )
-                        .expect("Error during tesselation!");
-                }
-                DrawOp::Stroke(StrokeOp { path, stroke_color, stroke_settings }) => {
-                    primitives.push({
-                        data::gpu_types::GpuPrimitive::from_u8_rgba(stroke_color)
-                    });
-                    let stroke_color_ix = primitives.len() as u32 - 1;
-                    let _: () = stroke_tessellator.tessellate_path(
-                            &path,
-                            &stroke_settings,
-                            &mut BuffersBuilder::new(
-                                &mut mesh,
-                                data::VertexConstructor {
-                                    prim_id: stroke_color_ix,
-                                },
-                            ),
-                        )
-                        .expect("Error during tesselation!");
-                }
-                DrawOp::FillStroke(FillStrokeOp { path, fill_color, stroke_color, fill_settings, stroke_settings }) => {
-                    primitives.push({
-                        data::gpu_types::GpuPrimitive::from_u8_rgba(fill_color)
-                    });
-                    let fill_color_ix = primitives.len() as u32 - 1;
-                    primitives.push({
-                        data::gpu_types::GpuPrimitive::from_u8_rgba(stroke_color)
-                    });
-                    let stroke_color_ix = primitives.len() as u32 - 1;
-                    let _: () = fill_tessellator.tessellate_path(
-                            &path,
-                            &fill_settings,
-                            &mut BuffersBuilder::new(
-                                &mut mesh,
-                                data::VertexConstructor {
-                                    prim_id: fill_color_ix,
-                                },
-                            ),
-                        )
-                        .expect("Error during tesselation!");
-                    let _: () = stroke_tessellator.tessellate_path(
-                            &path,
-                            &stroke_settings,
-                            &mut BuffersBuilder::new(
-                                &mut mesh,
-                                data::VertexConstructor {
-                                    prim_id: stroke_color_ix,
-                                },
-                            ),
-                        )
-                        .expect("Error during tesselation!");
-                }
-            }
+            let first_primitive = op_tessellator.primitives.len() as u32;
+            op_tessellator.append(op.draw(), &mut nodes);
+            op_primitives.push(first_primitive..op_tessellator.primitives.len() as u32);
         }
         TessellatedContent {
             mesh,
             primitives,
+            primitive_colors,
+            instances,
+            nodes,
+            op_primitives,
+            picture_resolution: self.picture_resolution,
+            needs_update: true,
+        }
+    }
+}
+
+
+impl<'a, T> Content<&'a T, &'a [T]> where T: DrawableObject + Sync {
+    /// Spreads the ops over the rayon pool. The output is identical to
+    /// `tessellate_sequential`.
+    pub(crate) fn tessellate(&self) -> TessellatedContent {
+        self.tessellate_until(None, || false).unwrap()
+    }
+    /// With the tolerance of `settings` in place of each op's own, e.g. from
+    /// `TesselationSettings::for_camera`.
+    pub(crate) fn tessellate_with(&self, settings: TesselationSettings) -> TessellatedContent {
+        self.tessellate_until(Some(settings), || false).unwrap()
+    }
+    /// Gives up and returns `None` once `cancelled` returns true, which is
+    /// checked between ops. Without `settings`, each op keeps its own
+    /// tolerance.
+    pub(crate) fn tessellate_until(
+        &self,
+        settings: Option<TesselationSettings>,
+        cancelled: impl Fn() -> bool + Sync,
+    ) -> Option<TessellatedContent> {
+        let mut mesh: VertexBuffers<GpuVertex, u32> = VertexBuffers::new();
+        let mut primitives: Vec<data::gpu_types::GpuPrimitive> = Vec::new();
+        let mut primitive_colors: Vec<ThemeColor> = Vec::new();
+        let mut instances: Vec<GpuInstance> = Vec::new();
+        let mut symbols: SymbolMeshes = SymbolMeshes::new();
+        let mut nodes: Vec<DrawNode> = Vec::new();
+        let mut op_primitives: Vec<std::ops::Range<u32>> = Vec::new();
+        let mut fill_tessellator: FillTessellator = FillTessellator::new();
+        let mut stroke_tessellator: StrokeTessellator = StrokeTessellator::new();
+        let mut op_tessellator = OpTessellator {
+            mesh: &mut mesh,
+            primitives: &mut primitives,
+            primitive_colors: &mut primitive_colors,
+            color_scheme: ColorScheme::default(),
+            instances: &mut instances,
+            symbols: &mut symbols,
+            fill_tessellator: &mut fill_tessellator,
+            stroke_tessellator: &mut stroke_tessellator,
+            tolerance: settings.map(|settings| settings.tolerance()),
+        };
+        let ops = self.items
+            .par_iter()
+            .map(|item| (!cancelled()).then(|| item.draw()))
+            .collect::<Option<Vec<_>>>()?;
+        if !op_tessellator.append_all_until(ops, &mut nodes, &mut op_primitives, cancelled) {
+            return None
+        }
+        Some(TessellatedContent {
+            mesh,
+            primitives,
+            primitive_colors,
+            instances,
+            nodes,
+            op_primitives,
             picture_resolution: self.picture_resolution,
             needs_update: true,
+        })
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// DRAW-OP TESSELLATOR
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// Tessellates a single `DrawOp` into shared buffers. Used by both
+/// `Content::tessellate` and the `SceneTessellator`.
+pub(crate) struct OpTessellator<'a> {
+    pub mesh: &'a mut MeshBuffer,
+    pub primitives: &'a mut GpuPrimitives,
+    /// The unresolved color of each primitive.
+    pub primitive_colors: &'a mut Vec<ThemeColor>,
+    pub color_scheme: ColorScheme,
+    pub instances: &'a mut GpuInstances,
+    pub symbols: &'a mut SymbolMeshes,
+    pub fill_tessellator: &'a mut FillTessellator,
+    pub stroke_tessellator: &'a mut StrokeTessellator,
+    /// Replaces the tolerance of every op when set, for zoom dependent
+    /// levels of detail.
+    pub tolerance: Option<f32>,
+}
+
+impl<'a> OpTessellator<'a> {
+    pub fn append(&mut self, op: DrawOp, nodes: &mut Vec<DrawNode>) {
+        let blend_mode = op.blend_mode();
+        let first_index = self.mesh.indices.len() as u32;
+        match op {
+            DrawOp::Fill(FillOp { path, fill_color, fill_settings, .. }) => {
+                let fill_color_ix = self.push_primitive(fill_color);
+                self.fill(&path, &fill_settings, fill_color_ix);
+            }
+            DrawOp::Stroke(StrokeOp { path, stroke_color, stroke_settings, stroke_dash, .. }) => {
+                let stroke_color_ix = self.push_primitive(stroke_color);
+                self.stroke(&path, &stroke_settings, stroke_dash.as_ref(), stroke_color_ix);
+            }
+            DrawOp::FillStroke(op) => {
+                self.fill_stroke(&op);
+            }
+            DrawOp::Group(group) => {
+                if group.is_passthrough() {
+                    for item in group.items {
+                        self.append(item, nodes);
+                    }
+                    return
+                }
+                let mut children = Vec::new();
+                for item in group.items {
+                    self.append(item, &mut children);
+                }
+                let bounds = children
+                    .iter()
+                    .filter_map(|child| self.node_bounds(child))
+                    .reduce(|a, b| spatial::union(&a, &b));
+                let bounds = match bounds {
+                    Some(bounds) if !bounds.is_empty() => bounds,
+                    _ => return,
+                };
+                nodes.push(DrawNode::Group(GroupNode {
+                    opacity: group.opacity,
+                    blend_mode: group.blend_mode,
+                    bounds: Filter::expand_bounds(&group.filters, bounds),
+                    filters: group.filters,
+                    children,
+                }));
+                return
+            }
+            DrawOp::Instances(op) => {
+                let indices = self.symbol_mesh(&op.symbol).indices;
+                let prim_id = self.push_primitive(data::RGBA::WHITE);
+                let first_instance = self.instances.len() as u32;
+                self.instances.extend(op.instances.iter().map(|instance| instance.to_gpu(prim_id)));
+                let instances = first_instance..self.instances.len() as u32;
+                if !indices.is_empty() && !instances.is_empty() {
+                    nodes.push(DrawNode::Batch(DrawBatch { blend_mode, indices, instances: Some(instances) }));
+                }
+                return
+            }
+        }
+        let last_index = self.mesh.indices.len() as u32;
+        DrawBatch::push(nodes, blend_mode, first_index..last_index);
+    }
+    /// Tessellates a symbol the first time it's used, and returns where it
+    /// is. Symbol geometry is only ever drawn through instanced batches.
+    fn symbol_mesh(&mut self, symbol: &Symbol) -> SymbolMesh {
+        if let Some(symbol) = self.symbols.get(&symbol.id()) {
+            return symbol.clone()
+        }
+        let first_index = self.mesh.indices.len() as u32;
+        self.append_symbol_shape(symbol.shape());
+        let indices = first_index..self.mesh.indices.len() as u32;
+        let symbol_mesh = SymbolMesh { bounds: self.index_bounds(&indices), indices };
+        self.symbols.insert(symbol.id(), symbol_mesh.clone());
+        symbol_mesh
+    }
+    fn append_symbol_shape(&mut self, op: &DrawOp) {
+        match op {
+            DrawOp::Fill(op) => {
+                let fill_color_ix = self.push_primitive(op.fill_color);
+                self.fill(&op.path, &op.fill_settings, fill_color_ix);
+            }
+            DrawOp::Stroke(op) => {
+                let stroke_color_ix = self.push_primitive(op.stroke_color);
+                self.stroke(&op.path, &op.stroke_settings, op.stroke_dash.as_ref(), stroke_color_ix);
+            }
+            DrawOp::FillStroke(op) => {
+                self.fill_stroke(op);
+            }
+            DrawOp::Group(group) => {
+                for item in group.items.iter() {
+                    self.append_symbol_shape(item);
+                }
+            }
+            DrawOp::Instances(_) => {}
+        }
+    }
+    /// Picture space bounds of what a node draws. Groups already carry
+    /// theirs, and symbols know theirs, which may not be in `mesh`.
+    fn node_bounds(&self, node: &DrawNode) -> Option<lyon::math::Box2D> {
+        let batch = match node {
+            DrawNode::Group(group) => return Some(group.bounds),
+            DrawNode::Batch(batch) => batch,
+        };
+        let instances = match batch.instances.as_ref() {
+            Some(instances) => &self.instances[instances.start as usize..instances.end as usize],
+            None => return self.index_bounds(&batch.indices),
+        };
+        let bounds = match self.symbols.values().find(|symbol| symbol.indices == batch.indices) {
+            Some(symbol) => symbol.bounds?,
+            None => self.index_bounds(&batch.indices)?,
+        };
+        instances
+            .iter()
+            .map(|instance| {
+                let [x, y, offset] = instance.transform;
+                lyon::math::Transform::new(x[0], x[1], y[0], y[1], offset[0], offset[1])
+                    .outer_transformed_box(&bounds)
+            })
+            .reduce(|a, b| spatial::union(&a, &b))
+    }
+    fn index_bounds(&self, indices: &std::ops::Range<u32>) -> Option<lyon::math::Box2D> {
+        self.mesh.indices[indices.start as usize..indices.end as usize]
+            .iter()
+            .map(|ix| Point::from(self.mesh.vertices[*ix as usize].position))
+            .map(|point| lyon::math::Box2D::new(point, point))
+            .reduce(|a, b| spatial::union(&a, &b))
+    }
+    fn push_primitive(&mut self, color: impl Into<ThemeColor>) -> u32 {
+        let color = color.into();
+        self.primitives.push({
+            data::gpu_types::GpuPrimitive::from_u8_rgba(color.resolve(self.color_scheme))
+        });
+        self.primitive_colors.push(color);
+        self.primitives.len() as u32 - 1
+    }
+    fn fill(&mut self, path: &lyon::path::Path, fill_settings: &FillOptions, prim_id: u32) {
+        let fill_settings = match self.tolerance {
+            Some(tolerance) => fill_settings.with_tolerance(tolerance),
+            None => *fill_settings,
+        };
+        let _: () = self.fill_tessellator.tessellate_path(
+                path,
+                &fill_settings,
+                &mut BuffersBuilder::new(
+                    self.mesh,
+                    data::VertexConstructor { prim_id },
+                ),
+            )
+            .expect("Error during tesselation!");
+    }
+    /// Dashes are split out of the path here, since lyon's stroke options
+    /// have no notion of them.
+    fn stroke(&mut self, path: &lyon::path::Path, stroke_settings: &StrokeOptions, stroke_dash: Option<&StrokeDash>, prim_id: u32) {
+        let stroke_settings = &match self.tolerance {
+            Some(tolerance) => stroke_settings.with_tolerance(tolerance),
+            None => *stroke_settings,
+        };
+        let (path, stroke_settings) = match stroke_dash {
+            Some(dash) => (
+                std::borrow::Cow::Owned(dash.apply(path, stroke_settings.tolerance)),
+                dash.stroke_options(*stroke_settings),
+            ),
+            None => (std::borrow::Cow::Borrowed(path), *stroke_settings),
+        };
+        let _: () = self.stroke_tessellator.tessellate_path(
+                path.as_ref(),
+                &stroke_settings,
+                &mut BuffersBuilder::new(
+                    self.mesh,
+                    data::VertexConstructor { prim_id },
+                ),
+            )
+            .expect("Error during tesselation!");
+    }
+    /// Inner and outer strokes are filled from their outline, with the
+    /// tolerance the stroke would have been tessellated with.
+    fn fill_stroke(&mut self, op: &FillStrokeOp) {
+        let fill_color_ix = self.push_primitive(op.fill_color);
+        let stroke_color_ix = self.push_primitive(op.stroke_color);
+        self.fill(&op.path, &op.fill_settings, fill_color_ix);
+        match op.stroke_alignment {
+            StrokeAlignment::Center => {
+                self.stroke(&op.path, &op.stroke_settings, op.stroke_dash.as_ref(), stroke_color_ix);
+            }
+            StrokeAlignment::Inside | StrokeAlignment::Outside => {
+                let tolerance = self.tolerance.unwrap_or(op.stroke_settings.tolerance);
+                self.fill(&op.stroke_outline(tolerance), &FillOptions::tolerance(tolerance), stroke_color_ix);
+            }
+        }
+    }
+}
+
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// PARALLEL TESSELLATION
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// An op tessellated on its own, with indices, `prim_id`s, instances and
+/// nodes starting at zero, so it can be done on any thread and appended
+/// later. Colors are kept unresolved.
+#[derive(Debug)]
+pub(crate) struct OpMesh {
+    pub mesh: MeshBuffer,
+    pub primitive_colors: Vec<ThemeColor>,
+    pub instances: GpuInstances,
+    pub nodes: Vec<DrawNode>,
+    /// The symbols this op tessellated into `mesh`, by symbol id.
+    pub symbols: SymbolMeshes,
+    /// The symbols this op draws from the target's buffers instead, as they
+    /// were when it was tessellated. `append_to` points their batches at
+    /// wherever the target has them now.
+    pub shared_symbols: SymbolMeshes,
+}
+
+impl OpMesh {
+    /// `tolerance` is as for `OpTessellator`.
+    pub fn tessellate(
+        op: DrawOp,
+        tolerance: Option<f32>,
+        fill_tessellator: &mut FillTessellator,
+        stroke_tessellator: &mut StrokeTessellator,
+    ) -> Self {
+        OpMesh::tessellate_sharing(op, tolerance, &SymbolMeshes::new(), fill_tessellator, stroke_tessellator)
+    }
+    /// Same as `tessellate`, except symbols in `shared` aren't tessellated
+    /// again. They must be in the target when appending. `shared` should
+    /// have either all of the op's symbols or none, since symbols in `mesh`
+    /// could otherwise be mistaken for shared ones.
+    pub fn tessellate_sharing(
+        op: DrawOp,
+        tolerance: Option<f32>,
+        shared: &SymbolMeshes,
+        fill_tessellator: &mut FillTessellator,
+        stroke_tessellator: &mut StrokeTessellator,
+    ) -> Self {
+        let mut ids = Vec::new();
+        symbol_ids(&op, &mut ids);
+        let shared_symbols = ids
+            .into_iter()
+            .filter_map(|id| shared.get(&id).map(|symbol| (id, symbol.clone())))
+            .collect::<SymbolMeshes>();
+        let mut mesh = VertexBuffers::new();
+        let mut primitive_colors = Vec::new();
+        let mut instances = Vec::new();
+        let mut symbols = shared_symbols.clone();
+        let mut nodes = Vec::new();
+        // The resolved primitives are dropped; `append_to` resolves them
+        // against the target's scheme.
+        let mut op_tessellator = OpTessellator {
+            mesh: &mut mesh,
+            primitives: &mut Vec::new(),
+            primitive_colors: &mut primitive_colors,
+            color_scheme: ColorScheme::default(),
+            instances: &mut instances,
+            symbols: &mut symbols,
+            fill_tessellator,
+            stroke_tessellator,
+            tolerance,
+        };
+        op_tessellator.append(op, &mut nodes);
+        symbols.retain(|id, _| !shared_symbols.contains_key(id));
+        OpMesh { mesh, primitive_colors, instances, nodes, symbols, shared_symbols }
+    }
+    /// Just a symbol's geometry, for laying out symbols that many ops share
+    /// ahead of them. Appending it adds no nodes.
+    pub fn tessellate_symbol(
+        symbol: &Symbol,
+        tolerance: Option<f32>,
+        fill_tessellator: &mut FillTessellator,
+        stroke_tessellator: &mut StrokeTessellator,
+    ) -> Self {
+        let mut mesh = VertexBuffers::new();
+        let mut primitive_colors = Vec::new();
+        let mut symbols = SymbolMeshes::new();
+        OpTessellator {
+            mesh: &mut mesh,
+            primitives: &mut Vec::new(),
+            primitive_colors: &mut primitive_colors,
+            color_scheme: ColorScheme::default(),
+            instances: &mut Vec::new(),
+            symbols: &mut symbols,
+            fill_tessellator,
+            stroke_tessellator,
+            tolerance,
+        }
+        .symbol_mesh(symbol);
+        OpMesh { mesh, primitive_colors, instances: Vec::new(), nodes: Vec::new(), symbols, shared_symbols: SymbolMeshes::new() }
+    }
+    /// Appends to the target's buffers as `OpTessellator::append` would have,
+    /// fixing up indices, `prim_id`s and instance ranges. The first batch is
+    /// merged into the last one in `nodes` when possible.
+    pub fn append_to(&self, target: &mut OpTessellator<'_>, nodes: &mut Vec<DrawNode>) {
+        let first_vertex = target.mesh.vertices.len() as u32;
+        let first_index = target.mesh.indices.len() as u32;
+        let first_primitive = target.primitives.len() as u32;
+        let first_instance = target.instances.len() as u32;
+        target.mesh.vertices.extend(self.mesh.vertices.iter().map(|vertex| GpuVertex {
+            prim_id: vertex.prim_id + first_primitive,
+            ..*vertex
+        }));
+        target.mesh.indices.extend(self.mesh.indices.iter().map(|index| index + first_vertex));
+        let color_scheme = target.color_scheme;
+        target.primitives.extend(self.primitive_colors.iter().map(|color| {
+            data::gpu_types::GpuPrimitive::from_u8_rgba(color.resolve(color_scheme))
+        }));
+        target.primitive_colors.extend_from_slice(&self.primitive_colors);
+        target.instances.extend(self.instances.iter().map(|instance| GpuInstance {
+            prim_id: instance.prim_id + first_primitive,
+            ..*instance
+        }));
+        for (id, symbol) in self.symbols.iter() {
+            let indices = symbol.indices.start + first_index..symbol.indices.end + first_index;
+            target.symbols.insert(*id, SymbolMesh { indices, bounds: symbol.bounds });
+        }
+        for node in self.nodes.iter() {
+            let mut node = node.clone();
+            self.place(&mut node, first_index, first_instance, target.symbols);
+            match node {
+                DrawNode::Batch(batch) if batch.instances.is_none() => {
+                    DrawBatch::push(nodes, batch.blend_mode, batch.indices)
+                }
+                node => nodes.push(node),
+            }
+        }
+    }
+    /// Offsets a node as `DrawNode::offset` does, except for batches of
+    /// shared symbols, which are pointed at the target's copy.
+    fn place(&self, node: &mut DrawNode, first_index: u32, first_instance: u32, symbols: &SymbolMeshes) {
+        let shared = match node {
+            DrawNode::Group(group) => {
+                for child in group.children.iter_mut() {
+                    self.place(child, first_index, first_instance, symbols);
+                }
+                return
+            }
+            DrawNode::Batch(batch) if batch.instances.is_some() => self.shared_symbols
+                .iter()
+                .find(|(_, symbol)| symbol.indices == batch.indices)
+                .map(|(id, _)| symbols[id].indices.clone()),
+            DrawNode::Batch(_) => None,
+        };
+        node.offset(first_index, first_instance);
+        if let (Some(indices), DrawNode::Batch(batch)) = (shared, node) {
+            batch.indices = indices;
+        }
+    }
+}
+
+impl<'a> OpTessellator<'a> {
+    /// Appends every op, tessellating them on the rayon pool with a
+    /// tessellator per thread, and pushes the primitives of each to
+    /// `op_primitives`. The output is identical to appending them one by one.
+    ///
+    /// An op instancing a symbol that an earlier op, or this tessellator,
+    /// already has must reuse that geometry, so those are appended in order
+    /// while merging instead.
+    pub fn append_all(&mut self, ops: Vec<DrawOp>, nodes: &mut Vec<DrawNode>, op_primitives: &mut Vec<std::ops::Range<u32>>) {
+        self.append_all_until(ops, nodes, op_primitives, || false);
+    }
+    /// Stops once `cancelled` returns true, which is checked between ops,
+    /// and returns false. The buffers are then left half written.
+    pub fn append_all_until(
+        &mut self,
+        ops: Vec<DrawOp>,
+        nodes: &mut Vec<DrawNode>,
+        op_primitives: &mut Vec<std::ops::Range<u32>>,
+        cancelled: impl Fn() -> bool + Sync,
+    ) -> bool {
+        let mut seen = self.symbols.keys().copied().collect::<std::collections::HashSet<u64>>();
+        let shares_symbols = ops
+            .iter()
+            .map(|op| {
+                let mut ids = Vec::new();
+                symbol_ids(op, &mut ids);
+                let shared = ids.iter().any(|id| seen.contains(id));
+                seen.extend(ids);
+                shared
+            })
+            .collect::<Vec<_>>();
+        let tolerance = self.tolerance;
+        let meshes = ops
+            .into_par_iter()
+            .zip(shares_symbols)
+            .map_init(
+                || (FillTessellator::new(), StrokeTessellator::new()),
+                |(fill_tessellator, stroke_tessellator), (op, shared)| {
+                    if cancelled() {
+                        return None
+                    }
+                    if shared {
+                        return Some(Either::Right(op))
+                    }
+                    Some(Either::Left(OpMesh::tessellate(op, tolerance, fill_tessellator, stroke_tessellator)))
+                },
+            )
+            .collect::<Option<Vec<_>>>();
+        let meshes = match meshes {
+            Some(meshes) => meshes,
+            None => return false,
+        };
+        for mesh in meshes {
+            if cancelled() {
+                return false
+            }
+            let first_primitive = self.primitives.len() as u32;
+            match mesh {
+                Either::Left(mesh) => mesh.append_to(self, nodes),
+                Either::Right(op) => self.append(op, nodes),
+            }
+            op_primitives.push(first_primitive..self.primitives.len() as u32);
         }
+        true
     }
 }
 
+/// Ids of the symbols an op instances, including inside groups.
+fn symbol_ids(op: &DrawOp, ids: &mut Vec<u64>) {
+    let mut symbols = Vec::new();
+    instanced_symbols(op, &mut symbols);
+    ids.extend(symbols.iter().map(|symbol| symbol.id()));
+}
+
+/// The symbols an op instances, including inside groups, once per
+/// instancing op.
+pub(crate) fn instanced_symbols(op: &DrawOp, symbols: &mut Vec<std::sync::Arc<Symbol>>) {
+    match op {
+        DrawOp::Instances(op) => symbols.push(op.symbol.clone()),
+        DrawOp::Group(group) => {
+            for item in group.items.iter() {
+                instanced_symbols(item, symbols);
+            }
+        }
+        DrawOp::Fill(_) | DrawOp::Stroke(_) | DrawOp::FillStroke(_) => {}
+    }
+}
 
 
 //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
@@ -282,12 +906,15 @@ impl Picture {
             let path = builder.build();
             let fill = FillStrokeOp {
                 path,
-                fill_color: data::RGBA::BLUE.with_alpha(0.7),
-                stroke_color: data::RGBA::BLACK.with_alpha(1.0),
+                fill_color: data::RGBA::BLUE.with_alpha(0.7).into(),
+                stroke_color: data::RGBA::BLACK.with_alpha(1.0).into(),
                 fill_settings: TesselationSettings::default_fill_options(),
                 stroke_settings: TesselationSettings::default_stroke_options()
                     .with_line_width(5.0)
                     .with_line_cap(lyon::path::LineCap::Round),
+                stroke_dash: None,
+                stroke_alignment: StrokeAlignment::default(),
+                blend_mode: BlendMode::default(),
             };
             DrawOp::FillStroke(fill)
         };
@@ -315,12 +942,15 @@ impl Picture {
             let path = builder.build();
             let fill_stroke = FillStrokeOp {
                 path,
-                fill_color: data::RGBA::CYAN.with_alpha(1.0),
+                fill_color: data::RGBA::CYAN.with_alpha(1.0).into(),
                 fill_settings: TesselationSettings::default_fill_options(),
-                stroke_color: data::RGBA::BLACK.with_alpha(1.0),
+                stroke_color: data::RGBA::BLACK.with_alpha(1.0).into(),
                 stroke_settings: TesselationSettings::default_stroke_options()
                     .with_line_width(10.0)
                     .with_line_cap(lyon::path::LineCap::Round),
+                stroke_dash: None,
+                stroke_alignment: StrokeAlignment::default(),
+                blend_mode: BlendMode::default(),
             };
             DrawOp::FillStroke(fill_stroke)
         };
@@ -344,12 +974,15 @@ impl Picture {
             let path = builder.build();
             let fill = FillStrokeOp {
                 path,
-                fill_color: data::RGBA::GREY.with_alpha(0.9),
+                fill_color: data::RGBA::GREY.with_alpha(0.9).into(),
                 fill_settings: TesselationSettings::default_fill_options(),
-                stroke_color: data::RGBA::RED.with_alpha(0.9),
+                stroke_color: data::RGBA::RED.with_alpha(0.9).into(),
                 stroke_settings: TesselationSettings::default_stroke_options()
                     .with_line_width(10.0)
                     .with_line_cap(lyon::path::LineCap::Round),
+                stroke_dash: None,
+                stroke_alignment: StrokeAlignment::default(),
+                blend_mode: BlendMode::default(),
             };
             DrawOp::FillStroke(fill)
         };
@@ -384,8 +1017,10 @@ impl Picture {
             
             let stroke = StrokeOp {
                 path,
-                stroke_color: data::RGBA::RED.with_alpha(0.9),
-                stroke_settings
+                stroke_color: data::RGBA::RED.with_alpha(0.9).into(),
+                stroke_settings,
+                stroke_dash: None,
+                blend_mode: BlendMode::default(),
             };
             DrawOp::Stroke(stroke)
         };
@@ -415,3 +1050,101 @@ impl Picture {
 // }
 
 
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// TESTS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lyon::math::point;
+    use crate::data::RGBA;
+    use crate::data::geometry::Rect;
+    use crate::data::shapes::{Shape, ShapeStyle};
+    use crate::data::symbols::SymbolInstance;
+
+    fn square(min: (f32, f32), size: f32, style: &ShapeStyle) -> DrawOp {
+        Shape::Rect(Rect::from_origin_and_size(min, size, size)).draw(style)
+    }
+
+    fn vertices(mesh: &MeshBuffer) -> Vec<([f32; 2], u32)> {
+        mesh.vertices.iter().map(|vertex| (vertex.position, vertex.prim_id)).collect()
+    }
+
+    fn instances(instances: &GpuInstances) -> Vec<([[f32; 2]; 3], [f32; 4], u32)> {
+        instances.iter().map(|instance| (instance.transform, instance.color, instance.prim_id)).collect()
+    }
+
+    #[test]
+    fn parallel_tessellation_matches_sequential() {
+        let dot = Symbol::new(square((-1.0, -1.0), 2.0, &ShapeStyle::fill(RGBA::BLACK)));
+        let dots = |x: f32| InstancesOp::new(dot.clone(), [SymbolInstance::at(point(x, 10.0)), SymbolInstance::at(point(x, 20.0))]);
+        let screen = ShapeStyle::fill(RGBA::CYAN).with_blend_mode(BlendMode::Screen);
+        let mut picture = Picture { items: Vec::new(), picture_resolution: PictureResolution::new(500.0, 500.0) };
+        // Source-over ops that merge into one batch.
+        picture.append(square((0.0, 0.0), 50.0, &ShapeStyle::fill(RGBA::RED)));
+        picture.append(square((10.0, 10.0), 50.0, &ShapeStyle::fill_stroke(RGBA::GREEN, RGBA::BLUE, 2.0)));
+        picture.append(dots(5.0));
+        // Passthrough groups, nested, which flatten into the parent.
+        picture.append(GroupOp::new([
+            square((100.0, 0.0), 20.0, &ShapeStyle::stroke(RGBA::BLACK, 1.0)),
+            GroupOp::new([square((120.0, 0.0), 20.0, &ShapeStyle::fill(RGBA::PINK))]).into(),
+        ]));
+        // Screen ops that merge with each other but not with the rest.
+        picture.append(square((200.0, 0.0), 30.0, &screen));
+        picture.append(square((210.0, 10.0), 30.0, &screen));
+        // An isolated group that shares the symbol, then the symbol again.
+        picture.append(
+            GroupOp::new([square((300.0, 0.0), 40.0, &ShapeStyle::fill(RGBA::PURPLE)), dots(310.0).into()])
+                .with_opacity(0.5),
+        );
+        picture.append(GroupOp::new([square((400.0, 0.0), 40.0, &ShapeStyle::fill(RGBA::GREY))]).with_blend_mode(BlendMode::Multiply));
+        picture.append(dots(450.0));
+
+        let parallel = picture.tessellate();
+        let sequential = picture.tessellate_sequential();
+        assert_eq!(vertices(&parallel.mesh), vertices(&sequential.mesh));
+        assert_eq!(parallel.mesh.indices, sequential.mesh.indices);
+        assert_eq!(
+            parallel.primitives.iter().map(|prim| prim.color).collect::<Vec<_>>(),
+            sequential.primitives.iter().map(|prim| prim.color).collect::<Vec<_>>(),
+        );
+        assert_eq!(instances(&parallel.instances), instances(&sequential.instances));
+        assert_eq!(parallel.nodes, sequential.nodes);
+        assert_eq!(parallel.op_primitives, sequential.op_primitives);
+
+        // The picture covers what it claims to: merged batches, a flattened
+        // passthrough group, and two isolated groups.
+        let groups = parallel.nodes.iter().filter(|node| matches!(node, DrawNode::Group(_))).count();
+        assert_eq!(groups, 2);
+        assert_eq!(parallel.op_primitives.len(), picture.items.len());
+        let instanced = parallel.nodes.iter().filter(|node| matches!(node, DrawNode::Batch(DrawBatch { instances: Some(_), .. }))).count();
+        assert_eq!(instanced, 2);
+        let screens = parallel.nodes.iter().filter(|node| matches!(node, DrawNode::Batch(batch) if batch.blend_mode == BlendMode::Screen)).count();
+        assert_eq!(screens, 1);
+    }
+
+    #[test]
+    fn camera_settings_follow_the_zoom() {
+        use crate::data::LogicalSize;
+        use crate::data::camera::{Camera, FitMode};
+        let mut picture = Picture { items: Vec::new(), picture_resolution: PictureResolution::new(200.0, 100.0) };
+        let circle = Shape::Circle { center: point(100.0, 50.0).into(), radius: 40.0 };
+        picture.append(circle.draw(&ShapeStyle::fill_stroke(RGBA::PINK, RGBA::BLACK, 2.0)));
+        let view = ViewInfo::new(LogicalSize::new(400.0, 300.0), 2.0);
+        let at_zoom = |zoom: f32| {
+            let camera = Camera::default().with_fit(FitMode::Contain).with_zoom(zoom);
+            let settings = TesselationSettings::for_camera(&camera, view, picture.picture_resolution);
+            // A fifth of a pixel, at 4 pixels per unit before zooming.
+            assert!((settings.tolerance() - 0.05 / zoom).abs() < 1e-6);
+            picture.tessellate_with(settings).mesh.vertices.len()
+        };
+        let (out, default, near) = (at_zoom(1.0), picture.tessellate().mesh.vertices.len(), at_zoom(16.0));
+        assert!(out < default && default < near, "{} {} {}", out, default, near);
+        // Collapsed cameras fall back to the default tolerance.
+        let collapsed = Camera::default().with_zoom(0.0);
+        assert_eq!(TesselationSettings::for_camera(&collapsed, view, picture.picture_resolution), TesselationSettings::default());
+    }
+}
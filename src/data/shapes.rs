@@ -0,0 +1,496 @@
+use lyon::math::{vector, Angle, Box2D};
+use lyon::path::{LineCap, LineJoin, Path, Polygon, Winding};
+use lyon::path::builder::BorderRadii;
+use crate::data::color::ThemeColor;
+use crate::data::dash::StrokeDash;
+use crate::data::draw_cmds::*;
+use crate::data::geometry::{Point, PointVec, Rect};
+use crate::data::picture::TesselationSettings;
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// SHAPES
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// Common shapes, in picture space. Build the outline with `path`, or a
+/// ready-to-append op with `draw`.
+///
+/// Angles are clockwise on screen (y points down), with zero along +x.
+/// Closed outlines wind clockwise on screen, except for the holes of rings.
+#[derive(Debug, Clone)]
+pub enum Shape {
+    Rect(Rect),
+    RoundedRect { rect: Rect, radii: CornerRadii },
+    Circle { center: Point, radius: f32 },
+    Ellipse { center: Point, radius_x: f32, radius_y: f32, rotation: Angle },
+    /// An open circular arc. Filling it closes it with a chord.
+    Arc { center: Point, radius: f32, start_angle: Angle, sweep_angle: Angle },
+    /// A circular sector, closed through the center.
+    Pie { center: Point, radius: f32, start_angle: Angle, sweep_angle: Angle },
+    /// An annular sector. A full turn gives a circle with a round hole.
+    Ring { center: Point, inner_radius: f32, outer_radius: f32, start_angle: Angle, sweep_angle: Angle },
+    /// `sides` corners, with the first one pointing up.
+    RegularPolygon { center: Point, radius: f32, sides: u32, rotation: Angle },
+    /// `points` spikes, alternating between the two radii, with the first
+    /// spike pointing up.
+    Star { center: Point, outer_radius: f32, inner_radius: f32, points: u32, rotation: Angle },
+    Polyline(PointVec),
+    Polygon(PointVec),
+    /// An arrow outline: a shaft of `shaft_width` from `from`, ending in a
+    /// triangular head whose tip is at `to`.
+    Arrow { from: Point, to: Point, shaft_width: f32, head_length: f32, head_width: f32 },
+}
+
+/// Per-corner radii of a rounded rectangle. Radii that don't fit are scaled
+/// down together, as with CSS `border-radius`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CornerRadii {
+    pub top_left: f32,
+    pub top_right: f32,
+    pub bottom_right: f32,
+    pub bottom_left: f32,
+}
+
+impl CornerRadii {
+    pub fn uniform(radius: f32) -> Self {
+        CornerRadii { top_left: radius, top_right: radius, bottom_right: radius, bottom_left: radius }
+    }
+    fn fitted(&self, width: f32, height: f32) -> BorderRadii {
+        let CornerRadii { top_left, top_right, bottom_right, bottom_left } = *self;
+        let [top_left, top_right, bottom_right, bottom_left] = [top_left, top_right, bottom_right, bottom_left].map(|r| r.max(0.0));
+        let fit = |length: f32, sum: f32| if sum > length { length / sum } else { 1.0 };
+        let scale = fit(width, top_left + top_right)
+            .min(fit(width, bottom_left + bottom_right))
+            .min(fit(height, top_left + bottom_left))
+            .min(fit(height, top_right + bottom_right));
+        BorderRadii {
+            top_left: top_left * scale,
+            top_right: top_right * scale,
+            bottom_left: bottom_left * scale,
+            bottom_right: bottom_right * scale,
+        }
+    }
+}
+
+impl Shape {
+    pub fn path(&self) -> Path {
+        let mut builder = Path::builder();
+        match self {
+            Shape::Rect(rect) => {
+                builder.add_rectangle(&(*rect).into(), Winding::Positive);
+            }
+            Shape::RoundedRect { rect, radii } => {
+                let rect: Box2D = (*rect).into();
+                builder.add_rounded_rectangle(&rect, &radii.fitted(rect.width(), rect.height()), Winding::Positive);
+            }
+            Shape::Circle { center, radius } => {
+                builder.add_circle((*center).into(), *radius, Winding::Positive);
+            }
+            Shape::Ellipse { center, radius_x, radius_y, rotation } => {
+                builder.add_ellipse((*center).into(), vector(*radius_x, *radius_y), *rotation, Winding::Positive);
+            }
+            Shape::Arc { center, radius, start_angle, sweep_angle } => {
+                let arc = circular_arc(*center, *radius, *start_angle, *sweep_angle);
+                builder.begin(arc.from());
+                append_arc(&mut builder, &arc);
+                builder.end(false);
+            }
+            Shape::Pie { center, radius, start_angle, sweep_angle } => {
+                if is_full_turn(*sweep_angle) {
+                    builder.add_circle((*center).into(), *radius, Winding::Positive);
+                } else {
+                    let arc = circular_arc(*center, *radius, *start_angle, *sweep_angle);
+                    builder.begin((*center).into());
+                    builder.line_to(arc.from());
+                    append_arc(&mut builder, &arc);
+                    builder.end(true);
+                }
+            }
+            Shape::Ring { center, inner_radius, outer_radius, start_angle, sweep_angle } => {
+                if is_full_turn(*sweep_angle) {
+                    builder.add_circle((*center).into(), *outer_radius, Winding::Positive);
+                    builder.add_circle((*center).into(), *inner_radius, Winding::Negative);
+                } else {
+                    let outer = circular_arc(*center, *outer_radius, *start_angle, *sweep_angle);
+                    let inner = circular_arc(*center, *inner_radius, *start_angle + *sweep_angle, -*sweep_angle);
+                    builder.begin(outer.from());
+                    append_arc(&mut builder, &outer);
+                    builder.line_to(inner.from());
+                    append_arc(&mut builder, &inner);
+                    builder.end(true);
+                }
+            }
+            Shape::RegularPolygon { center, radius, sides, rotation } => {
+                if *sides >= 3 {
+                    let points = (0..*sides)
+                        .map(|ix| polar(*center, *radius, *rotation + Angle::two_pi() * (ix as f32 / *sides as f32)))
+                        .collect::<Vec<_>>();
+                    builder.add_polygon(Polygon { points: &points, closed: true });
+                }
+            }
+            Shape::Star { center, outer_radius, inner_radius, points, rotation } => {
+                if *points >= 2 {
+                    let corners = 2 * *points;
+                    let points = (0..corners)
+                        .map(|ix| {
+                            let radius = if ix % 2 == 0 { *outer_radius } else { *inner_radius };
+                            polar(*center, radius, *rotation + Angle::two_pi() * (ix as f32 / corners as f32))
+                        })
+                        .collect::<Vec<_>>();
+                    builder.add_polygon(Polygon { points: &points, closed: true });
+                }
+            }
+            Shape::Polyline(points) | Shape::Polygon(points) => {
+                let closed = matches!(self, Shape::Polygon(_));
+                let points = points.points.iter().map(|point| (*point).into()).collect::<Vec<_>>();
+                if !points.is_empty() {
+                    builder.add_polygon(Polygon { points: &points, closed });
+                }
+            }
+            Shape::Arrow { from, to, shaft_width, head_length, head_width } => {
+                let (from, to): (lyon::math::Point, lyon::math::Point) = ((*from).into(), (*to).into());
+                let length = (to - from).length();
+                if length > 0.0 {
+                    let direction = (to - from) / length;
+                    // To the left of the shaft on screen, so the outline winds
+                    // clockwise.
+                    let normal = vector(direction.y, -direction.x);
+                    let base = to - direction * head_length.clamp(0.0, length);
+                    let shaft = normal * (*shaft_width * 0.5);
+                    let head = normal * (*head_width * 0.5);
+                    let points = [
+                        from + shaft,
+                        base + shaft,
+                        base + head,
+                        to,
+                        base - head,
+                        base - shaft,
+                        from - shaft,
+                    ];
+                    builder.add_polygon(Polygon { points: &points, closed: true });
+                }
+            }
+        }
+        builder.build()
+    }
+    pub fn draw(&self, style: &ShapeStyle) -> DrawOp {
+        style.draw(self.path())
+    }
+}
+
+fn circular_arc(center: Point, radius: f32, start_angle: Angle, sweep_angle: Angle) -> lyon::geom::Arc<f32> {
+    lyon::geom::Arc {
+        center: center.into(),
+        radii: vector(radius, radius),
+        start_angle,
+        sweep_angle,
+        x_rotation: Angle::zero(),
+    }
+}
+
+fn append_arc(builder: &mut lyon::path::path::Builder, arc: &lyon::geom::Arc<f32>) {
+    arc.for_each_cubic_bezier(&mut |segment| {
+        builder.cubic_bezier_to(segment.ctrl1, segment.ctrl2, segment.to);
+    });
+}
+
+fn is_full_turn(sweep_angle: Angle) -> bool {
+    sweep_angle.radians.abs() >= Angle::two_pi().radians - 1e-4
+}
+
+/// The point at `angle` from straight up, clockwise.
+fn polar(center: Point, radius: f32, angle: Angle) -> lyon::math::Point {
+    let angle = angle - Angle::frac_pi_2();
+    lyon::math::point(center.x + radius * angle.radians.cos(), center.y + radius * angle.radians.sin())
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// SHAPE STYLE
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// How a shape is painted. Either color may be left out; with neither, the
+/// shape draws nothing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapeStyle {
+    pub fill_color: Option<ThemeColor>,
+    pub stroke_color: Option<ThemeColor>,
+    pub stroke_width: f32,
+    pub line_cap: LineCap,
+    pub line_join: LineJoin,
+    pub stroke_dash: Option<StrokeDash>,
+    /// Only applies when the shape is both filled and stroked.
+    pub stroke_alignment: StrokeAlignment,
+    pub blend_mode: BlendMode,
+}
+
+/// Neither filled nor stroked, so it paints nothing until a color is set.
+impl Default for ShapeStyle {
+    fn default() -> Self {
+        ShapeStyle {
+            fill_color: None,
+            stroke_color: None,
+            stroke_width: 1.0,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Miter,
+            stroke_dash: None,
+            stroke_alignment: StrokeAlignment::default(),
+            blend_mode: BlendMode::default(),
+        }
+    }
+}
+
+impl ShapeStyle {
+    pub fn fill(color: impl Into<ThemeColor>) -> Self {
+        ShapeStyle { fill_color: Some(color.into()), ..Default::default() }
+    }
+    pub fn stroke(color: impl Into<ThemeColor>, width: f32) -> Self {
+        ShapeStyle { stroke_color: Some(color.into()), stroke_width: width, ..Default::default() }
+    }
+    pub fn fill_stroke(fill_color: impl Into<ThemeColor>, stroke_color: impl Into<ThemeColor>, stroke_width: f32) -> Self {
+        ShapeStyle { fill_color: Some(fill_color.into()), ..ShapeStyle::stroke(stroke_color, stroke_width) }
+    }
+    pub fn with_line_cap(mut self, line_cap: LineCap) -> Self {
+        self.line_cap = line_cap;
+        self
+    }
+    pub fn with_line_join(mut self, line_join: LineJoin) -> Self {
+        self.line_join = line_join;
+        self
+    }
+    pub fn with_dash(mut self, stroke_dash: StrokeDash) -> Self {
+        self.stroke_dash = Some(stroke_dash);
+        self
+    }
+    pub fn with_stroke_alignment(mut self, stroke_alignment: StrokeAlignment) -> Self {
+        self.stroke_alignment = stroke_alignment;
+        self
+    }
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+    pub fn stroke_options(&self) -> lyon::tessellation::StrokeOptions {
+        TesselationSettings::default_stroke_options()
+            .with_line_width(self.stroke_width)
+            .with_line_cap(self.line_cap)
+            .with_line_join(self.line_join)
+    }
+    /// Paints any path with this style. Without either color, that's an
+    /// empty `GroupOp`.
+    pub fn draw(&self, path: Path) -> DrawOp {
+        let fill_settings = TesselationSettings::default_fill_options();
+        let stroke_settings = self.stroke_options();
+        let stroke_dash = self.stroke_dash.clone();
+        let blend_mode = self.blend_mode;
+        match (self.fill_color, self.stroke_color) {
+            (Some(fill_color), Some(stroke_color)) => FillStrokeOp {
+                path,
+                fill_color,
+                stroke_color,
+                fill_settings,
+                stroke_settings,
+                stroke_dash,
+                stroke_alignment: self.stroke_alignment,
+                blend_mode,
+            }.into(),
+            (Some(fill_color), None) => FillOp { path, fill_color, fill_settings, blend_mode }.into(),
+            (None, Some(stroke_color)) => StrokeOp { path, stroke_color, stroke_settings, stroke_dash, blend_mode }.into(),
+            (None, None) => GroupOp::new([]).into(),
+        }
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// TESTS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lyon::algorithms::aabb::bounding_box;
+    use lyon::algorithms::area::approximate_signed_area;
+    use lyon::path::PathEvent;
+    use crate::data::RGBA;
+
+    fn degrees(degrees: f32) -> Angle {
+        Angle::degrees(degrees)
+    }
+
+    fn assert_box_eq(actual: Box2D, expected: Box2D) {
+        let close = (actual.min - expected.min).length() < 1e-3 && (actual.max - expected.max).length() < 1e-3;
+        assert!(close, "{:?} != {:?}", actual, expected);
+    }
+
+    /// Whether each subpath is closed, in order.
+    fn subpaths(path: &Path) -> Vec<bool> {
+        path.iter()
+            .filter_map(|event| match event {
+                PathEvent::End { close, .. } => Some(close),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn area(path: &Path) -> f32 {
+        approximate_signed_area(0.001, path.iter())
+    }
+
+    fn polyline() -> PointVec {
+        [(0.0, 0.0), (10.0, 5.0), (20.0, 0.0)].into_iter().collect()
+    }
+
+    #[test]
+    fn each_shape_has_its_bounds_and_subpaths() {
+        let bounds = |min: (f32, f32), max: (f32, f32)| Box2D::new(min.into(), max.into());
+        let rect = Rect::from_origin_and_size((10.0, 20.0), 30.0, 40.0);
+        let center = Point::new(0.0, 0.0);
+        let cases = [
+            (Shape::Rect(rect), bounds((10.0, 20.0), (40.0, 60.0)), vec![true]),
+            (Shape::RoundedRect { rect, radii: CornerRadii::uniform(5.0) }, bounds((10.0, 20.0), (40.0, 60.0)), vec![true]),
+            (Shape::Circle { center: Point::new(50.0, 50.0), radius: 10.0 }, bounds((40.0, 40.0), (60.0, 60.0)), vec![true]),
+            (
+                Shape::Ellipse { center, radius_x: 20.0, radius_y: 10.0, rotation: degrees(90.0) },
+                bounds((-10.0, -20.0), (10.0, 20.0)),
+                vec![true],
+            ),
+            // From +x, clockwise on screen to +y.
+            (
+                Shape::Arc { center, radius: 10.0, start_angle: degrees(0.0), sweep_angle: degrees(90.0) },
+                bounds((0.0, 0.0), (10.0, 10.0)),
+                vec![false],
+            ),
+            (
+                Shape::Pie { center, radius: 10.0, start_angle: degrees(180.0), sweep_angle: degrees(90.0) },
+                bounds((-10.0, -10.0), (0.0, 0.0)),
+                vec![true],
+            ),
+            (
+                Shape::Ring { center, inner_radius: 5.0, outer_radius: 10.0, start_angle: degrees(0.0), sweep_angle: degrees(90.0) },
+                bounds((0.0, 0.0), (10.0, 10.0)),
+                vec![true],
+            ),
+            (
+                Shape::Ring { center, inner_radius: 5.0, outer_radius: 10.0, start_angle: degrees(0.0), sweep_angle: degrees(360.0) },
+                bounds((-10.0, -10.0), (10.0, 10.0)),
+                vec![true, true],
+            ),
+            // Corners up, right, down and left.
+            (
+                Shape::RegularPolygon { center, radius: 10.0, sides: 4, rotation: degrees(0.0) },
+                bounds((-10.0, -10.0), (10.0, 10.0)),
+                vec![true],
+            ),
+            // A triangle pointing up.
+            (
+                Shape::RegularPolygon { center, radius: 10.0, sides: 3, rotation: degrees(0.0) },
+                bounds((-10.0 * 0.75f32.sqrt(), -10.0), (10.0 * 0.75f32.sqrt(), 5.0)),
+                vec![true],
+            ),
+            (
+                Shape::Star { center, outer_radius: 10.0, inner_radius: 2.0, points: 4, rotation: degrees(0.0) },
+                bounds((-10.0, -10.0), (10.0, 10.0)),
+                vec![true],
+            ),
+            (Shape::Polyline(polyline()), bounds((0.0, 0.0), (20.0, 5.0)), vec![false]),
+            (Shape::Polygon(polyline()), bounds((0.0, 0.0), (20.0, 5.0)), vec![true]),
+            (
+                Shape::Arrow { from: center, to: Point::new(100.0, 0.0), shaft_width: 10.0, head_length: 20.0, head_width: 30.0 },
+                bounds((0.0, -15.0), (100.0, 15.0)),
+                vec![true],
+            ),
+        ];
+        for (shape, expected, expected_subpaths) in cases {
+            let path = shape.path();
+            assert_box_eq(bounding_box(path.iter()), expected);
+            assert_eq!(subpaths(&path), expected_subpaths, "{:?}", shape);
+        }
+    }
+
+    #[test]
+    fn closed_outlines_wind_clockwise_on_screen() {
+        let center = Point::new(0.0, 0.0);
+        let pi = std::f32::consts::PI;
+        let cases = [
+            (Shape::Rect(Rect::from_origin_and_size((0.0, 0.0), 30.0, 40.0)), 1200.0),
+            (Shape::Circle { center, radius: 10.0 }, pi * 100.0),
+            (Shape::Pie { center, radius: 10.0, start_angle: degrees(45.0), sweep_angle: degrees(90.0) }, pi * 25.0),
+            // The hole winds the other way, so it's subtracted.
+            (Shape::Ring { center, inner_radius: 5.0, outer_radius: 10.0, start_angle: degrees(0.0), sweep_angle: degrees(360.0) }, pi * 75.0),
+            (Shape::Ring { center, inner_radius: 5.0, outer_radius: 10.0, start_angle: degrees(0.0), sweep_angle: degrees(180.0) }, pi * 37.5),
+            (Shape::RegularPolygon { center, radius: 10.0, sides: 4, rotation: degrees(0.0) }, 200.0),
+            (Shape::Arrow { from: center, to: Point::new(100.0, 0.0), shaft_width: 10.0, head_length: 20.0, head_width: 30.0 }, 1100.0),
+        ];
+        for (shape, expected) in cases {
+            let area = area(&shape.path());
+            // Arcs are approximated by cubics.
+            assert!((area - expected).abs() < expected * 1e-2, "{:?}: {} != {}", shape, area, expected);
+        }
+        // A negative sweep runs counter-clockwise, and so does the outline.
+        let backwards = Shape::Pie { center, radius: 10.0, start_angle: degrees(135.0), sweep_angle: degrees(-90.0) };
+        assert!(area(&backwards.path()) < 0.0);
+    }
+
+    #[test]
+    fn corner_radii_that_dont_fit_shrink_together() {
+        // Twice the half height, so everything halves.
+        let radii = CornerRadii::uniform(10.0).fitted(20.0, 10.0);
+        assert_eq!([radii.top_left, radii.top_right, radii.bottom_right, radii.bottom_left], [5.0; 4]);
+        // One oversized corner scales the others too, keeping proportions.
+        let radii = CornerRadii { top_left: 30.0, top_right: 10.0, bottom_right: 4.0, bottom_left: 0.0 }.fitted(20.0, 100.0);
+        assert_eq!([radii.top_left, radii.top_right, radii.bottom_right, radii.bottom_left], [15.0, 5.0, 2.0, 0.0]);
+        // Radii that fit are kept, and negative ones are dropped.
+        let radii = CornerRadii { top_left: 2.0, top_right: -3.0, bottom_right: 4.0, bottom_left: 5.0 }.fitted(20.0, 10.0);
+        assert_eq!([radii.top_left, radii.top_right, radii.bottom_right, radii.bottom_left], [2.0, 0.0, 4.0, 5.0]);
+        // Fully rounded, the outline stays inside the rect.
+        let rect = Rect::from_origin_and_size((0.0, 0.0), 20.0, 10.0);
+        let path = Shape::RoundedRect { rect, radii: CornerRadii::uniform(100.0) }.path();
+        assert_box_eq(bounding_box(path.iter()), rect.into());
+    }
+
+    #[test]
+    fn degenerate_shapes_stay_finite_or_empty() {
+        let center = Point::new(5.0, 5.0);
+        let empty = [
+            Shape::RegularPolygon { center, radius: 10.0, sides: 2, rotation: degrees(0.0) },
+            Shape::Star { center, outer_radius: 10.0, inner_radius: 5.0, points: 0, rotation: degrees(0.0) },
+            Shape::Star { center, outer_radius: 10.0, inner_radius: 5.0, points: 1, rotation: degrees(0.0) },
+            Shape::Polygon(PointVec::new()),
+            Shape::Polyline(PointVec::new()),
+            Shape::Arrow { from: center, to: center, shaft_width: 2.0, head_length: 4.0, head_width: 6.0 },
+        ];
+        for shape in empty {
+            assert_eq!(shape.path().iter().count(), 0, "{:?}", shape);
+        }
+        let zero_sized = [
+            Shape::Rect(Rect::from_origin_and_size(center, 0.0, 0.0)),
+            Shape::RoundedRect { rect: Rect::from_origin_and_size(center, 0.0, 0.0), radii: CornerRadii::uniform(3.0) },
+            Shape::RoundedRect { rect: Rect::from_origin_and_size(center, 10.0, 0.0), radii: CornerRadii::uniform(3.0) },
+            Shape::Circle { center, radius: 0.0 },
+            Shape::Star { center, outer_radius: 0.0, inner_radius: 0.0, points: 5, rotation: degrees(0.0) },
+        ];
+        let mut tessellator = lyon::tessellation::FillTessellator::new();
+        for shape in zero_sized {
+            let path = shape.path();
+            assert!(path.iter().all(|event| event.from().is_finite() && event.to().is_finite()), "{:?}", shape);
+            assert_eq!(area(&path), 0.0, "{:?}", shape);
+            let mut mesh: lyon::tessellation::VertexBuffers<lyon::math::Point, u32> = lyon::tessellation::VertexBuffers::new();
+            tessellator
+                .tessellate_path(&path, &lyon::tessellation::FillOptions::default(), &mut lyon::tessellation::BuffersBuilder::new(&mut mesh, |vertex: lyon::tessellation::FillVertex| vertex.position()))
+                .unwrap();
+            assert!(mesh.indices.is_empty(), "{:?}", shape);
+        }
+    }
+
+    #[test]
+    fn styles_pick_the_op_they_paint_with() {
+        let path = Shape::Rect(Rect::from_origin_and_size((0.0, 0.0), 10.0, 10.0)).path();
+        assert!(matches!(ShapeStyle::fill(RGBA::RED).draw(path.clone()), DrawOp::Fill(_)));
+        assert!(matches!(ShapeStyle::stroke(RGBA::RED, 2.0).draw(path.clone()), DrawOp::Stroke(_)));
+        assert!(matches!(ShapeStyle::fill_stroke(RGBA::RED, RGBA::BLUE, 2.0).draw(path.clone()), DrawOp::FillStroke(_)));
+        match ShapeStyle::default().draw(path) {
+            DrawOp::Group(group) => assert!(group.items.is_empty()),
+            op => panic!("expected an empty group, got {:?}", op),
+        }
+    }
+}
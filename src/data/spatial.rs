@@ -0,0 +1,228 @@
+use lyon::math::{Box2D, Point};
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// R-TREE
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// A static R-tree over axis aligned bounds, bulk loaded with
+/// Sort-Tile-Recursive packing. Rebuild it when the items change.
+#[derive(Debug, Clone)]
+pub struct RTree<T> {
+    items: Vec<(Box2D, T)>,
+    /// `levels[0]` points into `items`, every other level points into the
+    /// level below it. The last level is the root.
+    levels: Vec<Vec<RTreeNode>>,
+}
+
+#[derive(Debug, Clone)]
+struct RTreeNode {
+    bounds: Box2D,
+    children: std::ops::Range<usize>,
+}
+
+impl<T> RTree<T> {
+    pub const NODE_CAPACITY: usize = 16;
+
+    pub fn new(items: impl IntoIterator<Item = (Box2D, T)>) -> Self {
+        let mut items = items.into_iter().collect::<Vec<_>>();
+        let mut levels = Vec::new();
+        if items.is_empty() {
+            return RTree { items, levels }
+        }
+        let mut level = pack(&mut items, |(bounds, _)| *bounds);
+        while level.len() > 1 {
+            let mut parents = pack(&mut level, |node| node.bounds);
+            std::mem::swap(&mut level, &mut parents);
+            levels.push(parents);
+        }
+        levels.push(level);
+        RTree { items, levels }
+    }
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &(Box2D, T)> {
+        self.items.iter()
+    }
+    /// Calls `f` with every item whose bounds overlap `region`, including
+    /// bounds that only touch it.
+    pub fn query(&self, region: &Box2D, mut f: impl FnMut(&Box2D, &T)) {
+        let root = match self.levels.last() {
+            Some(root) => root,
+            None => return,
+        };
+        let mut stack = (0..root.len()).map(|ix| (self.levels.len() - 1, ix)).collect::<Vec<_>>();
+        while let Some((depth, ix)) = stack.pop() {
+            let node = &self.levels[depth][ix];
+            if !overlaps(&node.bounds, region) {
+                continue
+            }
+            if depth == 0 {
+                for (bounds, item) in &self.items[node.children.clone()] {
+                    if overlaps(bounds, region) {
+                        f(bounds, item);
+                    }
+                }
+            } else {
+                stack.extend(node.children.clone().map(|child| (depth - 1, child)));
+            }
+        }
+    }
+    pub fn query_point(&self, point: Point, f: impl FnMut(&Box2D, &T)) {
+        self.query(&Box2D::new(point, point), f)
+    }
+}
+
+/// Inclusive overlap test, so degenerate boxes (points, lines) still hit.
+pub fn overlaps(a: &Box2D, b: &Box2D) -> bool {
+    a.min.x <= b.max.x && b.min.x <= a.max.x && a.min.y <= b.max.y && b.min.y <= a.max.y
+}
+
+/// Whether `inner` lies within `outer`, edges included.
+pub fn contains(outer: &Box2D, inner: &Box2D) -> bool {
+    outer.min.x <= inner.min.x && outer.min.y <= inner.min.y && inner.max.x <= outer.max.x && inner.max.y <= outer.max.y
+}
+
+/// Unlike `Box2D::union`, this keeps degenerate boxes.
+pub fn union(a: &Box2D, b: &Box2D) -> Box2D {
+    Box2D::new(a.min.min(b.min), a.max.max(b.max))
+}
+
+/// Sorts `entries` into tiles of `NODE_CAPACITY` and returns one node per
+/// tile.
+fn pack<E>(entries: &mut [E], bounds: impl Fn(&E) -> Box2D) -> Vec<RTreeNode> {
+    let capacity = RTree::<()>::NODE_CAPACITY;
+    let center = |entry: &E| bounds(entry).center();
+    let node_count = entries.len().div_ceil(capacity);
+    let slice_count = (node_count as f32).sqrt().ceil() as usize;
+    let slice_len = slice_count * capacity;
+    entries.sort_by(|a, b| center(a).x.total_cmp(&center(b).x));
+    for slice in entries.chunks_mut(slice_len) {
+        slice.sort_by(|a, b| center(a).y.total_cmp(&center(b).y));
+    }
+    let mut nodes = Vec::with_capacity(node_count);
+    for (chunk_ix, chunk) in entries.chunks(capacity).enumerate() {
+        let start = chunk_ix * capacity;
+        let bounds = chunk
+            .iter()
+            .map(&bounds)
+            .reduce(|a, b| union(&a, &b))
+            .unwrap();
+        nodes.push(RTreeNode { bounds, children: start..start + chunk.len() });
+    }
+    nodes
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// TESTS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lyon::math::point;
+
+    /// A deterministic scatter of boxes, some of them points and lines.
+    fn scattered_boxes(count: usize) -> Vec<Box2D> {
+        let mut seed = 0x2545_f491_u32;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            (seed % 1000) as f32 / 10.0
+        };
+        (0..count)
+            .map(|ix| {
+                let min = point(next(), next());
+                let size = match ix % 4 {
+                    0 => (0.0, 0.0),
+                    1 => (next() / 10.0, 0.0),
+                    _ => (next() / 10.0, next() / 10.0),
+                };
+                Box2D::new(min, point(min.x + size.0, min.y + size.1))
+            })
+            .collect()
+    }
+
+    fn query_sorted(tree: &RTree<usize>, region: &Box2D) -> Vec<usize> {
+        let mut hits = Vec::new();
+        tree.query(region, |_, ix| hits.push(*ix));
+        hits.sort_unstable();
+        hits
+    }
+
+    fn brute_force(boxes: &[Box2D], region: &Box2D) -> Vec<usize> {
+        (0..boxes.len()).filter(|ix| overlaps(&boxes[*ix], region)).collect()
+    }
+
+    #[test]
+    fn empty_tree() {
+        let tree = RTree::<usize>::new([]);
+        assert!(tree.is_empty());
+        assert!(query_sorted(&tree, &Box2D::new(point(-1e9, -1e9), point(1e9, 1e9))).is_empty());
+    }
+
+    #[test]
+    fn packs_full_nodes_that_cover_their_children() {
+        let boxes = scattered_boxes(1000);
+        let tree = RTree::new(boxes.iter().copied().enumerate().map(|(ix, bounds)| (bounds, ix)));
+        assert_eq!(tree.len(), 1000);
+        let capacity = RTree::<()>::NODE_CAPACITY;
+        assert_eq!(tree.levels[0].len(), 1000usize.div_ceil(capacity));
+        assert_eq!(tree.levels.last().unwrap().len(), 1);
+        for (depth, level) in tree.levels.iter().enumerate() {
+            for node in level {
+                assert!(node.children.len() <= capacity);
+                let children = node.children.clone().map(|child| match depth {
+                    0 => tree.items[child].0,
+                    _ => tree.levels[depth - 1][child].bounds,
+                });
+                for child in children {
+                    assert!(contains(&node.bounds, &child));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn queries_match_brute_force() {
+        let boxes = scattered_boxes(700);
+        let tree = RTree::new(boxes.iter().copied().enumerate().map(|(ix, bounds)| (bounds, ix)));
+        let regions = [
+            Box2D::new(point(10.0, 10.0), point(30.0, 25.0)),
+            Box2D::new(point(-5.0, -5.0), point(200.0, 200.0)),
+            Box2D::new(point(150.0, 150.0), point(160.0, 160.0)),
+            // Degenerate regions: a point and a line.
+            Box2D::new(boxes[0].min, boxes[0].min),
+            Box2D::new(point(0.0, 50.0), point(100.0, 50.0)),
+        ];
+        for region in regions {
+            assert_eq!(query_sorted(&tree, &region), brute_force(&boxes, &region));
+        }
+        assert!(query_sorted(&tree, &regions[3]).contains(&0));
+    }
+
+    #[test]
+    fn touching_counts_as_overlapping() {
+        let tree = RTree::new([
+            (Box2D::new(point(0.0, 0.0), point(1.0, 1.0)), 0),
+            (Box2D::new(point(2.0, 0.0), point(2.0, 0.0)), 1),
+        ]);
+        assert_eq!(query_sorted(&tree, &Box2D::new(point(1.0, 1.0), point(2.0, 2.0))), vec![0]);
+        let mut hits = Vec::new();
+        tree.query_point(point(2.0, 0.0), |_, ix| hits.push(*ix));
+        assert_eq!(hits, vec![1]);
+        assert!(query_sorted(&tree, &Box2D::new(point(1.1, 0.5), point(1.9, 0.5))).is_empty());
+    }
+
+    #[test]
+    fn union_keeps_degenerate_boxes() {
+        let a = Box2D::new(point(0.0, 0.0), point(0.0, 0.0));
+        let b = Box2D::new(point(3.0, 1.0), point(3.0, 2.0));
+        assert_eq!(union(&a, &b), Box2D::new(point(0.0, 0.0), point(3.0, 2.0)));
+    }
+}
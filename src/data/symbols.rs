@@ -0,0 +1,81 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use lyon::math::{Angle, Point, Transform};
+use crate::data::RGBA;
+use crate::data::draw_cmds::DrawOp;
+use crate::data::gpu_types::GpuInstance;
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// SYMBOLS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// A shape that is tessellated once and drawn many times by an
+/// `InstancesOp`, e.g. a map marker. Its paths are in the symbol's own
+/// coordinates, usually centered on the origin.
+///
+/// Group effects (opacity, filters, blend modes) inside the shape are
+/// ignored, and nested `Instances` ops are skipped: a symbol is a single
+/// mesh, drawn with the blend mode of the op that instances it.
+#[derive(Debug)]
+pub struct Symbol {
+    id: u64,
+    shape: DrawOp,
+}
+
+static NEXT_SYMBOL_ID: AtomicU64 = AtomicU64::new(0);
+
+impl Symbol {
+    /// Symbols are shared between ops and keyed by identity, so they're
+    /// immutable and handed out behind an `Arc`.
+    pub fn new(shape: impl Into<DrawOp>) -> Arc<Symbol> {
+        let id = NEXT_SYMBOL_ID.fetch_add(1, Ordering::Relaxed);
+        Arc::new(Symbol { id, shape: shape.into() })
+    }
+    /// Unique for the lifetime of the process.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+    pub fn shape(&self) -> &DrawOp {
+        &self.shape
+    }
+}
+
+/// One placement of a symbol. `color` multiplies the symbol's own colors,
+/// so a white symbol takes on the instance color as is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SymbolInstance {
+    pub transform: Transform,
+    pub color: RGBA<u8>,
+}
+
+impl SymbolInstance {
+    pub fn at(position: Point) -> Self {
+        SymbolInstance {
+            transform: Transform::translation(position.x, position.y),
+            color: RGBA::WHITE,
+        }
+    }
+    /// Scales about the symbol's origin, before the existing transform.
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.transform = Transform::scale(scale, scale).then(&self.transform);
+        self
+    }
+    /// Rotates about the symbol's origin, before the existing transform.
+    pub fn with_rotation(mut self, angle: Angle) -> Self {
+        self.transform = Transform::rotation(angle).then(&self.transform);
+        self
+    }
+    pub fn with_color(mut self, color: RGBA<u8>) -> Self {
+        self.color = color;
+        self
+    }
+    /// `prim_id` identifies the op for picking.
+    pub(crate) fn to_gpu(self, prim_id: u32) -> GpuInstance {
+        let t = &self.transform;
+        GpuInstance {
+            transform: [[t.m11, t.m12], [t.m21, t.m22], [t.m31, t.m32]],
+            color: self.color.to_premultiplied(),
+            prim_id,
+        }
+    }
+}
@@ -1,9 +1,19 @@
-use crate::data::draw_cmds::{DrawOp, FillOp, FillStrokeOp, StrokeOp};
-use crate::{ViewResolution, PictureResolution};
-use crate::data::{gpu_types, RGBA};
+use crate::data::draw_cmds::{BlendMode, DrawOp, FillOp, FillStrokeOp, StrokeOp};
+use crate::data::picture::{self, DrawBatch, DrawNode, OpMesh, OpTessellator, SymbolMesh, SymbolMeshes};
+use crate::{ViewInfo, ViewResolution, PictureResolution};
+use crate::data::gpu_types;
+use crate::data::color::{Palette, ThemeColor};
 use crate::data::collections::CowCollection;
+use crate::data::drawable::PathOptions;
 use crate::data::geometry::{Point, PointVec, PointVecRef};
+use crate::data::camera::Camera;
+use crate::data::lod::LodLevel;
+use crate::data::symbols::Symbol;
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use rayon::prelude::*;
 use lyon::tessellation::geometry_builder::VertexBuffers;
 
 //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
@@ -37,6 +47,17 @@ impl DrawableObject for DrawOp {
 pub struct SceneTessellator {
     pub mesh: VertexBuffers<gpu_types::GpuVertex, u32>,
     pub primitives: Vec<gpu_types::GpuPrimitive>,
+    /// The unresolved color of each primitive, kept to re-resolve them when
+    /// the color scheme changes.
+    pub primitive_colors: Vec<ThemeColor>,
+    /// Resolves the color tokens of everything appended.
+    pub color_scheme: ColorScheme,
+    pub instances: Vec<gpu_types::GpuInstance>,
+    /// Symbols already in `mesh`, shared by every op that instances them.
+    pub symbols: SymbolMeshes,
+    pub nodes: Vec<DrawNode>,
+    /// The primitives of each appended op, in append order.
+    pub op_primitives: Vec<std::ops::Range<u32>>,
     pub fill_tessellator: lyon::tessellation::FillTessellator,
     pub stroke_tessellator: lyon::tessellation::StrokeTessellator,
     pub picture_resolution: PictureResolution,
@@ -51,90 +72,760 @@ impl SceneTessellator {
     pub fn new(picture_resolution: PictureResolution) -> Self {
         let mesh: VertexBuffers<gpu_types::GpuVertex, u32> = VertexBuffers::new();
         let primitives: Vec<gpu_types::GpuPrimitive> = Vec::new();
+        let primitive_colors: Vec<ThemeColor> = Vec::new();
+        let instances: Vec<gpu_types::GpuInstance> = Vec::new();
+        let symbols: SymbolMeshes = SymbolMeshes::new();
+        let nodes: Vec<DrawNode> = Vec::new();
         let fill_tessellator: lyon::tessellation::FillTessellator = lyon::tessellation::FillTessellator::new();
         let stroke_tessellator: lyon::tessellation::StrokeTessellator = lyon::tessellation::StrokeTessellator::new();
-        SceneTessellator { mesh, primitives, fill_tessellator, stroke_tessellator, picture_resolution }
+        let op_primitives: Vec<std::ops::Range<u32>> = Vec::new();
+        SceneTessellator {
+            mesh,
+            primitives,
+            primitive_colors,
+            color_scheme: ColorScheme::default(),
+            instances,
+            symbols,
+            nodes,
+            op_primitives,
+            fill_tessellator,
+            stroke_tessellator,
+            picture_resolution,
+        }
     }
     pub fn append_draw_op(&mut self, object: impl Into<DrawOp>) {
-        use lyon::tessellation::geometry_builder::*;
-        match object.into() {
-            DrawOp::Fill(FillOp { path, fill_color, fill_settings }) => {
-                self.primitives.push({
-                    gpu_types::GpuPrimitive::from_u8_rgba(fill_color)
-                });
-                let fill_color_ix = self.primitives.len() as u32 - 1;
-                let _: () = self.fill_tessellator.tessellate_path(
-                        &path,
-                        &fill_settings,
-                        &mut BuffersBuilder::new(
-                            &mut self.mesh,
-                            crate::data::VertexConstructor {
-                                prim_id: fill_color_ix,
-                            },
-                        ),
-                    )
-                    .expect("Error during tesselation!");
+        let first_primitive = self.primitives.len() as u32;
+        let mut nodes = std::mem::take(&mut self.nodes);
+        self.op_tessellator().append(object.into(), &mut nodes);
+        self.nodes = nodes;
+        self.op_primitives.push(first_primitive..self.primitives.len() as u32);
+    }
+    /// Same as appending each op in turn, but tessellated on the rayon pool.
+    pub fn append_draw_ops(&mut self, objects: impl IntoIterator<Item = impl Into<DrawOp>>) {
+        let ops = objects.into_iter().map(Into::into).collect::<Vec<DrawOp>>();
+        let mut nodes = std::mem::take(&mut self.nodes);
+        let mut op_primitives = std::mem::take(&mut self.op_primitives);
+        self.op_tessellator().append_all(ops, &mut nodes, &mut op_primitives);
+        self.nodes = nodes;
+        self.op_primitives = op_primitives;
+    }
+    fn op_tessellator(&mut self) -> OpTessellator<'_> {
+        OpTessellator {
+            mesh: &mut self.mesh,
+            primitives: &mut self.primitives,
+            primitive_colors: &mut self.primitive_colors,
+            color_scheme: self.color_scheme,
+            instances: &mut self.instances,
+            symbols: &mut self.symbols,
+            fill_tessellator: &mut self.fill_tessellator,
+            stroke_tessellator: &mut self.stroke_tessellator,
+            tolerance: None,
+        }
+    }
+    /// Re-resolves every primitive with a color token, leaving the geometry
+    /// alone. Returns the primitives to upload, or `None` when nothing
+    /// changed.
+    pub fn set_color_scheme(&mut self, color_scheme: ColorScheme) -> Option<SceneChanges> {
+        if color_scheme == self.color_scheme {
+            return None
+        }
+        self.color_scheme = color_scheme;
+        let first = self.primitive_colors.iter().position(ThemeColor::is_token)?;
+        for (primitive, color) in self.primitives[first..].iter_mut().zip(self.primitive_colors[first..].iter()) {
+            *primitive = gpu_types::GpuPrimitive::from_u8_rgba(color.resolve(color_scheme));
+        }
+        let (vertices, indices, instances) = (self.mesh.vertices.len() as u32, self.mesh.indices.len() as u32, self.instances.len() as u32);
+        Some(SceneChanges {
+            vertices: vertices..vertices,
+            indices: indices..indices,
+            primitives: first as u32..self.primitives.len() as u32,
+            instances: instances..instances,
+        })
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// KEYED SCENE
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// A stable handle to an op in a `Scene`. IDs are never reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObjectId(pub u64);
+
+/// Where an object's output lives in the `SceneTessellator` buffers after
+/// the last `Scene::sync`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectRange {
+    pub vertices: std::ops::Range<u32>,
+    pub indices: std::ops::Range<u32>,
+    /// The `prim_id`s written by the object.
+    pub primitives: std::ops::Range<u32>,
+    pub instances: std::ops::Range<u32>,
+    /// Top-level nodes added by the object. May be empty when its first
+    /// batch was merged into the previous object's.
+    pub nodes: std::ops::Range<usize>,
+}
+
+/// The tail of each buffer that `Scene::sync` rewrote. Everything before
+/// these ranges is unchanged, so only the tails need uploading.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SceneChanges {
+    pub vertices: std::ops::Range<u32>,
+    pub indices: std::ops::Range<u32>,
+    pub primitives: std::ops::Range<u32>,
+    pub instances: std::ops::Range<u32>,
+}
+
+/// Draw ops keyed by stable IDs, in draw order (bottom first).
+///
+/// Each object is tessellated on its own and cached, so `sync` only
+/// re-tessellates objects that were inserted or updated. Output is laid out
+/// in draw order, so an edit at position `n` rewrites the buffers from
+/// object `n` onward and leaves everything below it in place.
+///
+/// Op indices reported by hit testing and GPU picking are draw order
+/// positions; map them back with `Scene::id_at`.
+///
+/// With a level of detail set, ops are tessellated at the level's tolerance
+/// instead of their own, and a few levels are cached per object. Levels
+/// requested with `request_lod` are tessellated on the rayon pool while the
+/// current one keeps being drawn, and switched in by the `sync` after they
+/// finish. Both levels are within a fraction of a pixel of the true curves,
+/// so the switch doesn't show.
+///
+/// Symbols are shared by the whole scene: each is tessellated once per
+/// level, at the head of the buffers, and instanced from there by every
+/// object. Adding a new symbol, or removing the last object that instances
+/// one, changes the head and so rewrites everything.
+#[derive(Debug)]
+pub struct Scene {
+    pub picture_resolution: PictureResolution,
+    objects: HashMap<ObjectId, SceneObject>,
+    order: Vec<ObjectId>,
+    layout: HashMap<ObjectId, ObjectRange>,
+    /// Lowest draw order position whose output may differ from the last sync.
+    first_dirty: Option<usize>,
+    next_id: u64,
+    /// `None` tessellates every op at its own tolerance.
+    lod: Option<LodLevel>,
+    lod_job: Option<LodJob>,
+    /// Every symbol the objects instance, in head order.
+    symbols: Vec<SceneSymbol>,
+}
+
+#[derive(Debug)]
+struct SceneObject {
+    op: DrawOp,
+    /// Bumped on every update, so background results for an older op are
+    /// dropped.
+    revision: u64,
+    /// Tessellations of the current op, by level. Symbols are drawn from
+    /// the head, wherever it has them.
+    meshes: LevelCache,
+}
+
+#[derive(Debug)]
+struct SceneSymbol {
+    symbol: Arc<Symbol>,
+    /// Instancing ops among the objects. The symbol is evicted at zero.
+    users: usize,
+    meshes: LevelCache,
+}
+
+/// Meshes by level. Colors are kept unresolved, so the cache survives
+/// scheme changes.
+#[derive(Debug, Default)]
+struct LevelCache(Vec<(Option<LodLevel>, OpMesh)>);
+
+impl LevelCache {
+    const MAX_CACHED_LEVELS: usize = 3;
+
+    fn get(&self, level: Option<LodLevel>) -> Option<&OpMesh> {
+        self.0.iter().find(|(x, _)| *x == level).map(|(_, mesh)| mesh)
+    }
+    /// Keeps the levels nearest to `level`.
+    fn insert(&mut self, level: Option<LodLevel>, mesh: OpMesh) {
+        self.0.retain(|(x, _)| *x != level);
+        self.0.push((level, mesh));
+        let distance = |x: &Option<LodLevel>| match (x, level) {
+            (Some(x), Some(level)) => (x.0 - level.0).unsigned_abs(),
+            (x, level) if *x == level => 0,
+            _ => u32::MAX,
+        };
+        while self.0.len() > LevelCache::MAX_CACHED_LEVELS {
+            let farthest = self.0
+                .iter()
+                .enumerate()
+                .max_by_key(|(_, (x, _))| distance(x))
+                .map(|(ix, _)| ix)
+                .unwrap();
+            self.0.remove(farthest);
+        }
+    }
+}
+
+/// Tessellations of every object missing a level, running on the rayon
+/// pool. Dropping the job cancels it.
+#[derive(Debug)]
+struct LodJob {
+    level: LodLevel,
+    cancelled: Arc<AtomicBool>,
+    results: mpsc::Receiver<LodResults>,
+}
+
+#[derive(Debug)]
+struct LodResults {
+    /// Symbols that weren't cached for the level, by id.
+    symbols: Vec<(u64, OpMesh)>,
+    /// Objects, by id and revision.
+    objects: Vec<(ObjectId, u64, OpMesh)>,
+}
+
+impl Drop for LodJob {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Scene {
+    pub fn new(picture_resolution: PictureResolution) -> Self {
+        Scene {
+            picture_resolution,
+            objects: HashMap::new(),
+            order: Vec::new(),
+            layout: HashMap::new(),
+            first_dirty: None,
+            next_id: 0,
+            lod: None,
+            lod_job: None,
+            symbols: Vec::new(),
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+    /// IDs in draw order.
+    pub fn ids(&self) -> &[ObjectId] {
+        &self.order
+    }
+    pub fn id_at(&self, position: usize) -> Option<ObjectId> {
+        self.order.get(position).copied()
+    }
+    pub fn position(&self, id: ObjectId) -> Option<usize> {
+        self.order.iter().position(|x| *x == id)
+    }
+    pub fn get(&self, id: ObjectId) -> Option<&DrawOp> {
+        self.objects.get(&id).map(|object| &object.op)
+    }
+    /// Ops in draw order, e.g. for `HitTestIndex::new`.
+    pub fn ops(&self) -> impl Iterator<Item = &DrawOp> {
+        self.order.iter().map(|id| &self.objects[id].op)
+    }
+    /// The object's output as of the last `sync`.
+    pub fn range(&self, id: ObjectId) -> Option<&ObjectRange> {
+        self.layout.get(&id)
+    }
+    /// Adds an op on top of everything else.
+    pub fn insert(&mut self, op: impl Into<DrawOp>) -> ObjectId {
+        self.insert_at(self.order.len(), op)
+    }
+    /// Adds an op at a draw order position, clamped to the end.
+    pub fn insert_at(&mut self, position: usize, op: impl Into<DrawOp>) -> ObjectId {
+        let id = ObjectId(self.next_id);
+        self.next_id += 1;
+        let position = position.min(self.order.len());
+        let op = op.into();
+        self.add_symbols(&op);
+        self.objects.insert(id, SceneObject { op, revision: 0, meshes: LevelCache::default() });
+        self.order.insert(position, id);
+        self.mark_dirty(position);
+        id
+    }
+    /// Replaces an op, returning the previous one.
+    pub fn update(&mut self, id: ObjectId, op: impl Into<DrawOp>) -> Option<DrawOp> {
+        let position = self.position(id)?;
+        let op = op.into();
+        self.add_symbols(&op);
+        let object = self.objects.get_mut(&id)?;
+        object.meshes = LevelCache::default();
+        object.revision += 1;
+        let previous = std::mem::replace(&mut object.op, op);
+        self.remove_symbols(&previous);
+        self.mark_dirty(position);
+        Some(previous)
+    }
+    pub fn remove(&mut self, id: ObjectId) -> Option<DrawOp> {
+        let position = self.position(id)?;
+        self.order.remove(position);
+        self.layout.remove(&id);
+        self.mark_dirty(position);
+        let object = self.objects.remove(&id)?;
+        self.remove_symbols(&object.op);
+        Some(object.op)
+    }
+    /// Moves an object to a draw order position, clamped to the end.
+    pub fn move_to(&mut self, id: ObjectId, position: usize) -> bool {
+        let current = match self.position(id) {
+            Some(current) => current,
+            None => return false,
+        };
+        let position = position.min(self.order.len() - 1);
+        if position != current {
+            self.order.remove(current);
+            self.order.insert(position, id);
+            self.mark_dirty(position.min(current));
+        }
+        true
+    }
+    pub fn bring_to_front(&mut self, id: ObjectId) -> bool {
+        self.move_to(id, usize::MAX)
+    }
+    pub fn send_to_back(&mut self, id: ObjectId) -> bool {
+        self.move_to(id, 0)
+    }
+    fn mark_dirty(&mut self, position: usize) {
+        self.first_dirty = Some(self.first_dirty.map_or(position, |first| first.min(position)));
+    }
+    /// Counts the symbols an op instances, adding new ones to the head.
+    fn add_symbols(&mut self, op: &DrawOp) {
+        let mut symbols = Vec::new();
+        picture::instanced_symbols(op, &mut symbols);
+        for symbol in symbols {
+            match self.symbols.iter_mut().find(|x| x.symbol.id() == symbol.id()) {
+                Some(x) => x.users += 1,
+                None => {
+                    self.symbols.push(SceneSymbol { symbol, users: 1, meshes: LevelCache::default() });
+                    self.mark_dirty(0);
+                }
+            }
+        }
+    }
+    /// Uncounts the symbols an op instances, evicting the ones no object
+    /// uses anymore.
+    fn remove_symbols(&mut self, op: &DrawOp) {
+        let mut symbols = Vec::new();
+        picture::instanced_symbols(op, &mut symbols);
+        for symbol in symbols {
+            let ix = match self.symbols.iter().position(|x| x.symbol.id() == symbol.id()) {
+                Some(ix) => ix,
+                None => continue,
+            };
+            self.symbols[ix].users -= 1;
+            if self.symbols[ix].users == 0 {
+                self.symbols.remove(ix);
+                self.mark_dirty(0);
+            }
+        }
+    }
+}
+
+impl Scene {
+    /// The level drawn as of the next `sync`.
+    pub fn lod(&self) -> Option<LodLevel> {
+        self.lod
+    }
+    /// The level being tessellated in the background, if any.
+    pub fn pending_lod(&self) -> Option<LodLevel> {
+        self.lod_job.as_ref().map(|job| job.level)
+    }
+    /// Switches level right away, cancelling any pending one. The next
+    /// `sync` tessellates whatever isn't cached for it.
+    pub fn set_lod(&mut self, level: Option<LodLevel>) {
+        self.lod_job = None;
+        if level != self.lod {
+            self.lod = level;
+            self.mark_dirty(0);
+        }
+    }
+    /// Tessellates every object missing `level` in the background, and
+    /// switches to it in the first `sync` after that's done. Cancels a
+    /// pending request for another level.
+    pub fn request_lod(&mut self, level: LodLevel) {
+        if self.lod == Some(level) {
+            self.lod_job = None;
+            return
+        }
+        if self.pending_lod() == Some(level) {
+            return
+        }
+        let jobs = self.order
+            .iter()
+            .map(|id| (*id, &self.objects[id]))
+            .filter(|(_, object)| object.meshes.get(Some(level)).is_none())
+            .map(|(id, object)| (id, object.revision, object.op.clone()))
+            .collect::<Vec<_>>();
+        // Each symbol with the mesh it has for the level, if any.
+        let symbols = self.symbols
+            .iter()
+            .map(|x| (x.symbol.clone(), x.meshes.get(Some(level)).map(|mesh| mesh.symbols[&x.symbol.id()].clone())))
+            .collect::<Vec<_>>();
+        if jobs.is_empty() && symbols.iter().all(|(_, mesh)| mesh.is_some()) {
+            self.set_lod(Some(level));
+            return
+        }
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (sender, results) = mpsc::channel();
+        let job_cancelled = cancelled.clone();
+        rayon::spawn(move || {
+            let tolerance = Some(level.tolerance());
+            let mut fill_tessellator = lyon::tessellation::FillTessellator::new();
+            let mut stroke_tessellator = lyon::tessellation::StrokeTessellator::new();
+            let mut results = LodResults { symbols: Vec::new(), objects: Vec::with_capacity(jobs.len()) };
+            let mut head = Vec::with_capacity(symbols.len());
+            for (symbol, mesh) in symbols {
+                let mesh = match mesh {
+                    Some(mesh) => mesh,
+                    None => {
+                        let mesh = OpMesh::tessellate_symbol(&symbol, tolerance, &mut fill_tessellator, &mut stroke_tessellator);
+                        let symbol_mesh = mesh.symbols[&symbol.id()].clone();
+                        results.symbols.push((symbol.id(), mesh));
+                        symbol_mesh
+                    }
+                };
+                head.push((symbol.id(), mesh));
+            }
+            let head = head_symbols(head);
+            for (id, revision, op) in jobs {
+                if job_cancelled.load(Ordering::Relaxed) {
+                    return
+                }
+                let mesh = OpMesh::tessellate_sharing(op, tolerance, &head, &mut fill_tessellator, &mut stroke_tessellator);
+                results.objects.push((id, revision, mesh));
+            }
+            let _ = sender.send(results);
+        });
+        self.lod_job = Some(LodJob { level, cancelled, results });
+    }
+    /// Requests the level the camera's current zoom needs, see
+    /// `LodLevel::target`. Cheap enough to call every frame.
+    pub fn follow_camera(&mut self, camera: &Camera, view: ViewInfo) {
+        let [scale_x, scale_y] = camera.scale(view, self.picture_resolution);
+        let current = self.pending_lod().or(self.lod);
+        self.request_lod(LodLevel::target(current, scale_x.max(scale_y)));
+    }
+    /// Caches the results of a finished background job and switches to its
+    /// level.
+    fn poll_lod(&mut self) {
+        let job = match self.lod_job.as_ref() {
+            Some(job) => job,
+            None => return,
+        };
+        let results = match job.results.try_recv() {
+            Ok(results) => results,
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                self.lod_job = None;
+                return
             }
-            DrawOp::Stroke(StrokeOp { path, stroke_color, stroke_settings }) => {
-                self.primitives.push({
-                    gpu_types::GpuPrimitive::from_u8_rgba(stroke_color)
-                });
-                let stroke_color_ix = self.primitives.len() as u32 - 1;
-                let _: () = self.stroke_tessellator.tessellate_path(
-                        &path,
-                        &stroke_settings,
-                        &mut BuffersBuilder::new(
-                            &mut self.mesh,
-                            crate::data::VertexConstructor {
-                                prim_id: stroke_color_ix,
-                            },
-                        ),
-                    )
-                    .expect("Error during tesselation!");
+        };
+        let level = job.level;
+        for (id, mesh) in results.symbols {
+            if let Some(x) = self.symbols.iter_mut().find(|x| x.symbol.id() == id) {
+                x.meshes.insert(Some(level), mesh);
             }
-            DrawOp::FillStroke(FillStrokeOp { path, fill_color, stroke_color, fill_settings, stroke_settings }) => {
-                self.primitives.push({
-                    gpu_types::GpuPrimitive::from_u8_rgba(fill_color)
-                });
-                let fill_color_ix = self.primitives.len() as u32 - 1;
-                self.primitives.push({
-                    gpu_types::GpuPrimitive::from_u8_rgba(stroke_color)
-                });
-                let stroke_color_ix = self.primitives.len() as u32 - 1;
-                let _: () = self.fill_tessellator.tessellate_path(
-                        &path,
-                        &fill_settings,
-                        &mut BuffersBuilder::new(
-                            &mut self.mesh,
-                            crate::data::VertexConstructor {
-                                prim_id: fill_color_ix,
-                            },
-                        ),
-                    )
-                    .expect("Error during tesselation!");
-                let _: () = self.stroke_tessellator.tessellate_path(
-                        &path,
-                        &stroke_settings,
-                        &mut BuffersBuilder::new(
-                            &mut self.mesh,
-                            crate::data::VertexConstructor {
-                                prim_id: stroke_color_ix,
-                            },
-                        ),
-                    )
-                    .expect("Error during tesselation!");
+        }
+        for (id, revision, mesh) in results.objects {
+            match self.objects.get_mut(&id) {
+                Some(object) if object.revision == revision => object.meshes.insert(Some(level), mesh),
+                _ => {}
+            }
+        }
+        self.set_lod(Some(level));
+    }
+}
+
+/// Where symbols land when their meshes are laid out in order from the
+/// start of the buffers, given where each is within its own mesh. Objects
+/// only need the ranges to tell symbols apart, so any layout will do.
+fn head_symbols(meshes: impl IntoIterator<Item = (u64, SymbolMesh)>) -> SymbolMeshes {
+    let mut first_index = 0;
+    let mut head = SymbolMeshes::new();
+    for (id, symbol) in meshes {
+        let indices = symbol.indices.start + first_index..symbol.indices.end + first_index;
+        first_index = indices.end;
+        head.insert(id, SymbolMesh { indices, bounds: symbol.bounds });
+    }
+    head
+}
+
+impl Scene {
+    /// Brings `tessellator` up to date with the scene, and returns what
+    /// changed, or `None` when nothing did. The scene owns the tessellator's
+    /// contents: don't append to it by other means in between.
+    pub fn sync(&mut self, tessellator: &mut SceneTessellator) -> Option<SceneChanges> {
+        self.poll_lod();
+        let first = self.first_dirty.take()?.min(self.order.len());
+        let cut = match first.checked_sub(1).map(|ix| &self.layout[&self.order[ix]]) {
+            Some(range) => (range.vertices.end, range.indices.end, range.primitives.end, range.instances.end, range.nodes.end),
+            None => (0, 0, 0, 0, 0),
+        };
+        let (first_vertex, first_index, first_primitive, first_instance, first_node) = cut;
+        tessellator.picture_resolution = self.picture_resolution;
+        tessellator.mesh.vertices.truncate(first_vertex as usize);
+        tessellator.mesh.indices.truncate(first_index as usize);
+        tessellator.primitives.truncate(first_primitive as usize);
+        tessellator.primitive_colors.truncate(first_primitive as usize);
+        tessellator.instances.truncate(first_instance as usize);
+        tessellator.op_primitives.truncate(first);
+        tessellator.nodes.truncate(first_node);
+        // The next object's first batch may have been merged into this one.
+        if let Some(DrawNode::Batch(batch)) = tessellator.nodes.last_mut() {
+            if batch.instances.is_none() {
+                batch.indices.end = batch.indices.end.min(first_index);
             }
         }
+        let tolerance = self.lod.map(|level| level.tolerance());
+        if first == 0 {
+            self.append_symbols(tessellator);
+        }
+        // Objects are tessellated on their own, so the missing ones can be
+        // done in parallel before appending everything in order.
+        let missing = self.order[first..]
+            .iter()
+            .map(|id| (*id, &self.objects[id]))
+            .filter(|(_, object)| object.meshes.get(self.lod).is_none())
+            .map(|(id, object)| (id, object.op.clone()))
+            .collect::<Vec<_>>();
+        let head = &tessellator.symbols;
+        let meshes = missing
+            .into_par_iter()
+            .map_init(
+                || (lyon::tessellation::FillTessellator::new(), lyon::tessellation::StrokeTessellator::new()),
+                |(fill_tessellator, stroke_tessellator), (id, op)| {
+                    (id, OpMesh::tessellate_sharing(op, tolerance, head, fill_tessellator, stroke_tessellator))
+                },
+            )
+            .collect::<Vec<_>>();
+        for (id, mesh) in meshes {
+            self.objects.get_mut(&id).unwrap().meshes.insert(self.lod, mesh);
+        }
+        for id in self.order[first..].iter() {
+            let range = tessellator.append_object(self.objects[id].meshes.get(self.lod).unwrap());
+            self.layout.insert(*id, range);
+        }
+        Some(SceneChanges {
+            vertices: first_vertex..tessellator.mesh.vertices.len() as u32,
+            indices: first_index..tessellator.mesh.indices.len() as u32,
+            primitives: first_primitive..tessellator.primitives.len() as u32,
+            instances: first_instance..tessellator.instances.len() as u32,
+        })
+    }
+}
+
+impl Scene {
+    /// Lays out every symbol at the head of the emptied buffers,
+    /// tessellating the ones not cached for the current level.
+    fn append_symbols(&mut self, tessellator: &mut SceneTessellator) {
+        let tolerance = self.lod.map(|level| level.tolerance());
+        let lod = self.lod;
+        self.symbols
+            .par_iter_mut()
+            .filter(|x| x.meshes.get(lod).is_none())
+            .for_each_init(
+                || (lyon::tessellation::FillTessellator::new(), lyon::tessellation::StrokeTessellator::new()),
+                |(fill_tessellator, stroke_tessellator), x| {
+                    let mesh = OpMesh::tessellate_symbol(&x.symbol, tolerance, fill_tessellator, stroke_tessellator);
+                    x.meshes.insert(lod, mesh);
+                },
+            );
+        tessellator.symbols.clear();
+        for x in self.symbols.iter() {
+            x.meshes.get(lod).unwrap().append_to(&mut tessellator.op_tessellator(), &mut Vec::new());
+        }
+    }
+}
+
+impl SceneTessellator {
+    fn append_object(&mut self, mesh: &OpMesh) -> ObjectRange {
+        let first_vertex = self.mesh.vertices.len() as u32;
+        let first_index = self.mesh.indices.len() as u32;
+        let first_primitive = self.primitives.len() as u32;
+        let first_instance = self.instances.len() as u32;
+        let first_node = self.nodes.len();
+        let mut nodes = std::mem::take(&mut self.nodes);
+        mesh.append_to(&mut self.op_tessellator(), &mut nodes);
+        self.nodes = nodes;
+        let primitives = first_primitive..self.primitives.len() as u32;
+        self.op_primitives.push(primitives.clone());
+        ObjectRange {
+            vertices: first_vertex..self.mesh.vertices.len() as u32,
+            indices: first_index..self.mesh.indices.len() as u32,
+            primitives,
+            instances: first_instance..self.instances.len() as u32,
+            nodes: first_node..self.nodes.len(),
+        }
     }
 }
 
 
 #[derive(Debug, Clone)]
 pub struct DynamicStroke {
-    color: RGBA<u8>,
+    color: ThemeColor,
     path: PointVec,
 }
 
+impl DynamicStroke {
+    pub fn new(color: impl Into<ThemeColor>, path: PointVec) -> Self {
+        DynamicStroke { color: color.into(), path }
+    }
+}
+
+/// Raw input strokes are smoothed and fitted with the default `PathOptions`.
+impl DrawableObject for DynamicStroke {
+    fn draw(&self) -> DrawOp {
+        let options = PathOptions::default().with_color(self.color);
+        crate::data::ink::ink_stroke(&self.path, &options).into()
+    }
+}
+
+
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// LIVE STROKES
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+/// A stroke that grows while it's being drawn, tessellated straight into a
+/// `SceneTessellator` at a constant cost per sample.
+///
+/// The polyline is cut at the midpoint of each segment. Every piece from one
+/// midpoint, through a sample, to the next midpoint is tessellated once with
+/// its join and butt ends, so neighbouring pieces meet edge to edge. Only the
+/// tail, from the last midpoint to the newest sample and its end cap, is
+/// redone on each `push`.
+///
+/// The stroke must stay the last thing appended to the tessellator until it
+/// is finished.
+#[derive(Debug)]
+pub struct LiveStroke {
+    prim_id: u32,
+    stroke_settings: lyon::tessellation::StrokeOptions,
+    /// Samples closer than this to the previous one are dropped.
+    pub min_distance: f32,
+    points: Vec<lyon::math::Point>,
+    /// Where the tail starts in the tessellator's buffers.
+    tail_vertex: u32,
+    tail_index: u32,
+    /// Where the stroke's indices start, and whether its batch was pushed
+    /// yet.
+    first_index: u32,
+    has_batch: bool,
+}
+
+impl LiveStroke {
+    pub fn begin(tessellator: &mut SceneTessellator, color: impl Into<ThemeColor>, stroke_settings: lyon::tessellation::StrokeOptions) -> Self {
+        let color = color.into();
+        let prim_id = tessellator.primitives.len() as u32;
+        tessellator.primitives.push(gpu_types::GpuPrimitive::from_u8_rgba(color.resolve(tessellator.color_scheme)));
+        tessellator.primitive_colors.push(color);
+        tessellator.op_primitives.push(prim_id..prim_id + 1);
+        let tail_vertex = tessellator.mesh.vertices.len() as u32;
+        let tail_index = tessellator.mesh.indices.len() as u32;
+        LiveStroke {
+            prim_id,
+            stroke_settings,
+            min_distance: 0.5,
+            points: Vec::new(),
+            tail_vertex,
+            tail_index,
+            first_index: tail_index,
+            has_batch: false,
+        }
+    }
+    /// Appends a sample, and returns the parts of the buffers that changed,
+    /// for `CanvasLayer::write_scene_changes`. `None` when the sample was
+    /// dropped.
+    pub fn push(&mut self, tessellator: &mut SceneTessellator, point: impl Into<lyon::math::Point>) -> Option<SceneChanges> {
+        let point = point.into();
+        let too_close = self.points
+            .last()
+            .is_some_and(|last| (point - *last).length() <= self.min_distance.max(0.0));
+        if too_close || !point.x.is_finite() || !point.y.is_finite() {
+            return None
+        }
+        self.points.push(point);
+        tessellator.mesh.vertices.truncate(self.tail_vertex as usize);
+        tessellator.mesh.indices.truncate(self.tail_index as usize);
+        let changed_vertex = self.tail_vertex;
+        let changed_index = self.tail_index;
+        let count = self.points.len();
+        let midpoint = |ix: usize| self.points[ix].lerp(self.points[ix + 1], 0.5);
+        let butt = lyon::path::LineCap::Butt;
+        if count >= 2 {
+            // Commit the piece that ends at the newest midpoint.
+            let (piece, start_cap) = if count == 2 {
+                (vec![self.points[0], midpoint(0)], self.stroke_settings.start_cap)
+            } else {
+                (vec![midpoint(count - 3), self.points[count - 2], midpoint(count - 2)], butt)
+            };
+            let settings = self.stroke_settings.with_start_cap(start_cap).with_end_cap(butt);
+            self.tessellate(tessellator, &piece, &settings);
+            self.tail_vertex = tessellator.mesh.vertices.len() as u32;
+            self.tail_index = tessellator.mesh.indices.len() as u32;
+        }
+        let (tail, settings) = if count == 1 {
+            // A lone sample draws nothing, a zero-length segment draws a dot.
+            (vec![point, point], self.stroke_settings)
+        } else {
+            (vec![midpoint(count - 2), point], self.stroke_settings.with_start_cap(butt))
+        };
+        self.tessellate(tessellator, &tail, &settings);
+        let last_index = tessellator.mesh.indices.len() as u32;
+        if self.has_batch {
+            if let Some(DrawNode::Batch(batch)) = tessellator.nodes.last_mut() {
+                batch.indices.end = last_index;
+            }
+        } else if last_index > self.first_index {
+            DrawBatch::push(&mut tessellator.nodes, BlendMode::default(), self.first_index..last_index);
+            self.has_batch = true;
+        }
+        Some(SceneChanges {
+            vertices: changed_vertex..tessellator.mesh.vertices.len() as u32,
+            indices: changed_index..last_index,
+            primitives: self.prim_id..self.prim_id + 1,
+            instances: tessellator.instances.len() as u32..tessellator.instances.len() as u32,
+        })
+    }
+    pub fn points(&self) -> &[lyon::math::Point] {
+        &self.points
+    }
+    /// The finished polyline as an op, e.g. to replace the live geometry
+    /// with a smoothed stroke. The tessellator keeps what was drawn.
+    pub fn finish(self, color: impl Into<ThemeColor>) -> StrokeOp {
+        let mut builder = lyon::path::Path::builder();
+        if let Some(first) = self.points.first() {
+            builder.begin(*first);
+            for point in self.points[1..].iter() {
+                builder.line_to(*point);
+            }
+            builder.end(false);
+        }
+        StrokeOp {
+            path: builder.build(),
+            stroke_color: color.into(),
+            stroke_settings: self.stroke_settings,
+            stroke_dash: None,
+            blend_mode: BlendMode::default(),
+        }
+    }
+    fn tessellate(&self, tessellator: &mut SceneTessellator, points: &[lyon::math::Point], settings: &lyon::tessellation::StrokeOptions) {
+        let mut builder = lyon::path::Path::builder();
+        builder.add_polygon(lyon::path::Polygon { points, closed: false });
+        let _: () = tessellator.stroke_tessellator
+            .tessellate_path(
+                &builder.build(),
+                settings,
+                &mut lyon::tessellation::BuffersBuilder::new(
+                    &mut tessellator.mesh,
+                    crate::data::VertexConstructor { prim_id: self.prim_id },
+                ),
+            )
+            .expect("Error during tesselation!");
+    }
+}
+
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum DrawMode {
@@ -144,12 +835,22 @@ pub enum DrawMode {
 }
 
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub enum ColorScheme {
     Dark,
+    #[default]
     Light,
 }
 
+impl ColorScheme {
+    pub fn palette(self) -> &'static Palette {
+        match self {
+            ColorScheme::Dark => &Palette::DARK,
+            ColorScheme::Light => &Palette::LIGHT,
+        }
+    }
+}
+
 // pub struct Scene {
     
 // }
@@ -189,4 +890,416 @@ pub enum ColorScheme {
 //―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
 
 
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+// TESTS
+//―――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――――
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lyon::math::point;
+    use lyon::path::LineCap;
+    use lyon::tessellation::StrokeOptions;
+    use crate::data::RGBA;
+    use crate::data::draw_cmds::GroupOp;
+    use crate::data::geometry::Rect;
+    use crate::data::shapes::{Shape, ShapeStyle};
+    use crate::data::draw_cmds::InstancesOp;
+    use crate::data::symbols::SymbolInstance;
+
+    fn square(min: (f32, f32), size: f32, style: &ShapeStyle) -> DrawOp {
+        Shape::Rect(Rect::from_origin_and_size(min, size, size)).draw(style)
+    }
+
+    /// Everything a sync writes, in comparable form.
+    #[derive(Debug, Clone, Default, PartialEq)]
+    struct Contents {
+        vertices: Vec<([f32; 2], u32)>,
+        indices: Vec<u32>,
+        primitives: Vec<[f32; 4]>,
+        instances: Vec<([[f32; 2]; 3], [f32; 4], u32)>,
+        nodes: Vec<DrawNode>,
+        op_primitives: Vec<std::ops::Range<u32>>,
+    }
+
+    impl Contents {
+        fn of(tessellator: &SceneTessellator) -> Self {
+            Contents {
+                vertices: vertices(&tessellator.mesh),
+                indices: tessellator.mesh.indices.clone(),
+                primitives: tessellator.primitives.iter().map(|prim| prim.color).collect(),
+                instances: tessellator.instances.iter().map(|instance| (instance.transform, instance.color, instance.prim_id)).collect(),
+                nodes: tessellator.nodes.clone(),
+                op_primitives: tessellator.op_primitives.clone(),
+            }
+        }
+        /// Tessellates the scene's ops from scratch, in one go.
+        fn expected(scene: &Scene) -> Self {
+            let mut tessellator = SceneTessellator::new(scene.picture_resolution);
+            tessellator.append_draw_ops(scene.ops().cloned());
+            Contents::of(&tessellator)
+        }
+        /// Uploads only the tails `changes` reports, as a renderer would.
+        fn upload(&mut self, tessellator: &SceneTessellator, changes: &SceneChanges) {
+            fn tail<T: Clone>(uploaded: &mut Vec<T>, current: Vec<T>, changed: &std::ops::Range<u32>) {
+                assert_eq!(changed.end as usize, current.len());
+                assert!(changed.start as usize <= uploaded.len());
+                uploaded.truncate(changed.start as usize);
+                uploaded.extend_from_slice(&current[changed.start as usize..]);
+            }
+            let current = Contents::of(tessellator);
+            tail(&mut self.vertices, current.vertices, &changes.vertices);
+            tail(&mut self.indices, current.indices, &changes.indices);
+            tail(&mut self.primitives, current.primitives, &changes.primitives);
+            tail(&mut self.instances, current.instances, &changes.instances);
+            self.nodes = current.nodes;
+            self.op_primitives = current.op_primitives;
+        }
+    }
+
+    /// Syncs, uploads the changes, and checks both against a tessellation
+    /// from scratch. Returns the changes.
+    fn sync_and_check(scene: &mut Scene, tessellator: &mut SceneTessellator, uploaded: &mut Contents) -> SceneChanges {
+        let changes = scene.sync(tessellator).expect("nothing changed");
+        uploaded.upload(tessellator, &changes);
+        let expected = Contents::expected(scene);
+        assert_eq!(Contents::of(tessellator), expected);
+        assert_eq!(*uploaded, expected);
+        for (position, id) in scene.ids().iter().enumerate() {
+            assert_eq!(scene.range(*id).unwrap().primitives, expected.op_primitives[position]);
+        }
+        changes
+    }
+
+    /// A triangle as drawn: blend mode, corners after instancing, primitive
+    /// color, instance color, and the op that picking reports.
+    type DrawnTriangle = (BlendMode, [[f32; 2]; 3], [f32; 4], [f32; 4], Option<usize>);
+
+    /// Everything the nodes draw, in order. Independent of where things are
+    /// in the buffers, so scenes with symbols at the head can be compared
+    /// against a plain tessellation.
+    fn drawn(tessellator: &SceneTessellator) -> Vec<DrawnTriangle> {
+        fn visit(tessellator: &SceneTessellator, node: &DrawNode, drawn: &mut Vec<DrawnTriangle>) {
+            let batch = match node {
+                DrawNode::Group(group) => {
+                    drawn.push((group.blend_mode, [[0.0; 2]; 3], [group.opacity; 4], [0.0; 4], None));
+                    for child in group.children.iter() {
+                        visit(tessellator, child, drawn);
+                    }
+                    return
+                }
+                DrawNode::Batch(batch) => batch,
+            };
+            let indices = &tessellator.mesh.indices[batch.indices.start as usize..batch.indices.end as usize];
+            let instances = match batch.instances.as_ref() {
+                Some(range) => tessellator.instances[range.start as usize..range.end as usize]
+                    .iter()
+                    .map(|instance| (instance.transform, instance.color, instance.prim_id))
+                    .collect::<Vec<_>>(),
+                None => vec![([[1.0, 0.0], [0.0, 1.0], [0.0, 0.0]], [1.0; 4], u32::MAX)],
+            };
+            for (transform, color, instance_prim) in instances {
+                for triangle in indices.chunks(3) {
+                    let vertex = |ix: usize| tessellator.mesh.vertices[triangle[ix] as usize];
+                    let [x, y, offset] = transform;
+                    let corner = |ix: usize| {
+                        let [px, py] = vertex(ix).position;
+                        [x[0] * px + y[0] * py + offset[0], x[1] * px + y[1] * py + offset[1]]
+                    };
+                    let prim_id = vertex(0).prim_id;
+                    let picked = if instance_prim == u32::MAX { prim_id } else { instance_prim };
+                    drawn.push((
+                        batch.blend_mode,
+                        [corner(0), corner(1), corner(2)],
+                        tessellator.primitives[prim_id as usize].color,
+                        color,
+                        picture::op_for_primitive(&tessellator.op_primitives, picked),
+                    ));
+                }
+            }
+        }
+        let mut drawn = Vec::new();
+        for node in tessellator.nodes.iter() {
+            visit(tessellator, node, &mut drawn);
+        }
+        drawn
+    }
+
+    /// Syncs, and checks that the scene draws what a tessellation from
+    /// scratch does, with each symbol in its buffers once.
+    fn sync_and_check_drawn(scene: &mut Scene, tessellator: &mut SceneTessellator) {
+        scene.sync(tessellator);
+        let mut expected = SceneTessellator::new(scene.picture_resolution);
+        expected.append_draw_ops(scene.ops().cloned());
+        assert_eq!(drawn(tessellator), drawn(&expected));
+        assert_eq!(tessellator.mesh.indices.len(), expected.mesh.indices.len());
+        assert_eq!(tessellator.symbols.len(), expected.symbols.len());
+    }
+
+    fn vertices(mesh: &VertexBuffers<gpu_types::GpuVertex, u32>) -> Vec<([f32; 2], u32)> {
+        mesh.vertices.iter().map(|vertex| (vertex.position, vertex.prim_id)).collect()
+    }
+
+    /// What `LiveStroke` promises to match: the pieces between midpoints,
+    /// each tessellated on its own, then the tail with the end cap.
+    fn tessellate_pieces(points: &[lyon::math::Point], settings: &StrokeOptions, prim_id: u32) -> VertexBuffers<gpu_types::GpuVertex, u32> {
+        let mut mesh = VertexBuffers::new();
+        let mut tessellator = lyon::tessellation::StrokeTessellator::new();
+        let mut tessellate = |piece: Vec<lyon::math::Point>, settings: StrokeOptions| {
+            let mut builder = lyon::path::Path::builder();
+            builder.add_polygon(lyon::path::Polygon { points: &piece, closed: false });
+            tessellator
+                .tessellate_path(
+                    &builder.build(),
+                    &settings,
+                    &mut lyon::tessellation::BuffersBuilder::new(&mut mesh, crate::data::VertexConstructor { prim_id }),
+                )
+                .unwrap();
+        };
+        let midpoint = |ix: usize| points[ix].lerp(points[ix + 1], 0.5);
+        let last = points.len() - 1;
+        tessellate(vec![points[0], midpoint(0)], settings.with_end_cap(LineCap::Butt));
+        for (ix, point) in points.iter().enumerate().take(last).skip(1) {
+            tessellate(vec![midpoint(ix - 1), *point, midpoint(ix)], settings.with_start_cap(LineCap::Butt).with_end_cap(LineCap::Butt));
+        }
+        tessellate(vec![midpoint(last - 1), points[last]], settings.with_start_cap(LineCap::Butt));
+        mesh
+    }
+
+    #[test]
+    fn live_stroke_matches_pieces_tessellated_once() {
+        let mut tessellator = SceneTessellator::new(PictureResolution::new(1000.0, 1000.0));
+        // Something drawn before the stroke, which it must leave alone.
+        let mut builder = lyon::path::Path::builder();
+        builder.add_circle(point(50.0, 50.0), 20.0, lyon::path::Winding::Positive);
+        tessellator.append_draw_op(FillOp {
+            path: builder.build(),
+            fill_color: RGBA::RED.into(),
+            fill_settings: lyon::tessellation::FillOptions::default(),
+            blend_mode: BlendMode::default(),
+        });
+        let before = vertices(&tessellator.mesh);
+        let first_vertex = tessellator.mesh.vertices.len();
+        let first_index = tessellator.mesh.indices.len();
+        let settings = StrokeOptions::default()
+            .with_line_width(6.0)
+            .with_line_cap(LineCap::Round)
+            .with_line_join(lyon::path::LineJoin::Round);
+        let mut stroke = LiveStroke::begin(&mut tessellator, RGBA::BLACK, settings);
+        let points = (0..40)
+            .map(|ix| {
+                let t = ix as f32 * 0.3;
+                point(100.0 + t * 20.0, 100.0 + (t * 1.7).sin() * 40.0)
+            })
+            .collect::<Vec<_>>();
+        let mut committed = (first_vertex, first_index);
+        for (ix, point) in points.iter().enumerate() {
+            let snapshot = (vertices(&tessellator.mesh), tessellator.mesh.indices.clone());
+            let changes = stroke.push(&mut tessellator, *point).unwrap();
+            // Only the tail of the last push is rewritten.
+            assert_eq!(changes.vertices.start as usize, committed.0);
+            assert_eq!(changes.indices.start as usize, committed.1);
+            assert_eq!(vertices(&tessellator.mesh)[..committed.0], snapshot.0[..committed.0]);
+            assert_eq!(tessellator.mesh.indices[..committed.1], snapshot.1[..committed.1]);
+            committed = (stroke.tail_vertex as usize, stroke.tail_index as usize);
+            if ix >= 1 {
+                let expected = tessellate_pieces(&points[..=ix], &settings, stroke.prim_id);
+                assert_eq!(vertices(&tessellator.mesh)[first_vertex..], vertices(&expected)[..]);
+                let indices = tessellator.mesh.indices[first_index..]
+                    .iter()
+                    .map(|index| index - first_vertex as u32)
+                    .collect::<Vec<_>>();
+                assert_eq!(indices, expected.indices);
+            }
+        }
+        assert_eq!(vertices(&tessellator.mesh)[..first_vertex], before[..]);
+        // The stroke's batch may have merged with the fill's.
+        match tessellator.nodes.last() {
+            Some(DrawNode::Batch(batch)) => {
+                assert!(batch.indices.start <= first_index as u32);
+                assert_eq!(batch.indices.end, tessellator.mesh.indices.len() as u32);
+            }
+            _ => panic!("the stroke has no batch"),
+        }
+    }
+
+    #[test]
+    fn live_stroke_drops_close_and_non_finite_samples() {
+        let mut tessellator = SceneTessellator::new(PictureResolution::new(1000.0, 1000.0));
+        let mut stroke = LiveStroke::begin(&mut tessellator, RGBA::BLACK, StrokeOptions::default());
+        assert!(stroke.push(&mut tessellator, point(0.0, 0.0)).is_some());
+        assert!(stroke.push(&mut tessellator, point(0.2, 0.0)).is_none());
+        assert!(stroke.push(&mut tessellator, point(f32::NAN, 0.0)).is_none());
+        assert!(stroke.push(&mut tessellator, point(2.0, 0.0)).is_some());
+        assert_eq!(stroke.points(), &[point(0.0, 0.0), point(2.0, 0.0)]);
+    }
+
+    #[test]
+    fn scene_edits_match_a_full_tessellation() {
+        let mut scene = Scene::new(PictureResolution::new(500.0, 500.0));
+        let mut tessellator = SceneTessellator::new(scene.picture_resolution);
+        let mut uploaded = Contents::default();
+        let screen = ShapeStyle::fill(RGBA::CYAN).with_blend_mode(BlendMode::Screen);
+        let ids = [
+            scene.insert(square((0.0, 0.0), 50.0, &ShapeStyle::fill(RGBA::RED))),
+            scene.insert(square((10.0, 10.0), 50.0, &ShapeStyle::fill_stroke(RGBA::GREEN, RGBA::BLUE, 2.0))),
+            scene.insert(square((20.0, 20.0), 50.0, &screen)),
+            scene.insert(square((30.0, 30.0), 50.0, &ShapeStyle::stroke(RGBA::BLACK, 1.0))),
+        ];
+        let changes = sync_and_check(&mut scene, &mut tessellator, &mut uploaded);
+        assert_eq!(changes.vertices.start, 0);
+        assert!(scene.sync(&mut tessellator).is_none());
+
+        // Each edit rewrites from the object below it onward.
+        let below = |scene: &Scene, position: usize| scene.range(scene.id_at(position - 1).unwrap()).unwrap().clone();
+        let inserted = scene.insert_at(1, square((5.0, 5.0), 20.0, &ShapeStyle::fill(RGBA::PINK)));
+        let expected_start = below(&scene, 1);
+        let changes = sync_and_check(&mut scene, &mut tessellator, &mut uploaded);
+        assert_eq!(changes.vertices.start, expected_start.vertices.end);
+        assert_eq!(changes.indices.start, expected_start.indices.end);
+        assert_eq!(scene.ids(), &[ids[0], inserted, ids[1], ids[2], ids[3]]);
+
+        // A bigger op than before, then one that no longer merges with the
+        // op below.
+        assert!(scene.update(ids[2], GroupOp::new([square((20.0, 20.0), 80.0, &screen), square((40.0, 40.0), 10.0, &screen)])).is_some());
+        let expected_start = below(&scene, 3);
+        let changes = sync_and_check(&mut scene, &mut tessellator, &mut uploaded);
+        assert_eq!(changes.vertices.start, expected_start.vertices.end);
+        assert!(scene.update(inserted, square((5.0, 5.0), 20.0, &screen)).is_some());
+        sync_and_check(&mut scene, &mut tessellator, &mut uploaded);
+
+        // Removing the top object only truncates.
+        let expected_end = below(&scene, 4);
+        assert!(scene.remove(ids[3]).is_some());
+        let changes = sync_and_check(&mut scene, &mut tessellator, &mut uploaded);
+        assert_eq!(changes.vertices, expected_end.vertices.end..expected_end.vertices.end);
+        assert_eq!(changes.indices, expected_end.indices.end..expected_end.indices.end);
+        assert!(scene.remove(ids[3]).is_none());
+
+        // Reordering, including moves that clamp.
+        assert!(scene.move_to(ids[0], 2));
+        sync_and_check(&mut scene, &mut tessellator, &mut uploaded);
+        assert!(scene.bring_to_front(inserted));
+        sync_and_check(&mut scene, &mut tessellator, &mut uploaded);
+        assert!(scene.send_to_back(ids[2]));
+        let changes = sync_and_check(&mut scene, &mut tessellator, &mut uploaded);
+        assert_eq!(changes.vertices.start, 0);
+        assert_eq!(scene.ids(), &[ids[2], ids[1], ids[0], inserted]);
+        assert!(!scene.move_to(ids[3], 0));
+
+        // Several edits are synced at once, from the lowest one.
+        scene.remove(ids[1]);
+        scene.insert(square((60.0, 60.0), 30.0, &ShapeStyle::fill(RGBA::PURPLE)));
+        let expected_start = below(&scene, 1);
+        let changes = sync_and_check(&mut scene, &mut tessellator, &mut uploaded);
+        assert_eq!(changes.vertices.start, expected_start.vertices.end);
+
+        // Emptied out.
+        for id in scene.ids().to_vec() {
+            scene.remove(id);
+        }
+        let changes = sync_and_check(&mut scene, &mut tessellator, &mut uploaded);
+        assert_eq!(changes.vertices, 0..0);
+        assert!(tessellator.nodes.is_empty());
+    }
+
+    #[test]
+    fn truncating_sync_cuts_batches_merged_across_objects() {
+        let mut scene = Scene::new(PictureResolution::new(500.0, 500.0));
+        let mut tessellator = SceneTessellator::new(scene.picture_resolution);
+        let mut uploaded = Contents::default();
+        let bottom = scene.insert(square((0.0, 0.0), 50.0, &ShapeStyle::fill(RGBA::RED)));
+        let top = scene.insert(square((10.0, 10.0), 50.0, &ShapeStyle::fill(RGBA::GREEN)));
+        sync_and_check(&mut scene, &mut tessellator, &mut uploaded);
+        // Both objects draw from one batch, which the top one doesn't own.
+        assert_eq!(tessellator.nodes.len(), 1);
+        assert!(scene.range(top).unwrap().nodes.is_empty());
+
+        assert!(scene.update(top, square((10.0, 10.0), 20.0, &ShapeStyle::fill(RGBA::GREEN))).is_some());
+        sync_and_check(&mut scene, &mut tessellator, &mut uploaded);
+        scene.remove(top);
+        sync_and_check(&mut scene, &mut tessellator, &mut uploaded);
+        match tessellator.nodes.as_slice() {
+            [DrawNode::Batch(batch)] => assert_eq!(batch.indices, scene.range(bottom).unwrap().indices),
+            nodes => panic!("expected one batch, got {:?}", nodes),
+        }
+    }
+
+    #[test]
+    fn symbols_are_tessellated_once_per_scene() {
+        let dot = Symbol::new(square((-1.0, -1.0), 2.0, &ShapeStyle::fill(RGBA::BLACK)));
+        let ring = Symbol::new(square((-2.0, -2.0), 4.0, &ShapeStyle::stroke(RGBA::RED, 0.5)));
+        let dots = |symbol: &Arc<Symbol>, x: f32| InstancesOp::new(symbol.clone(), [SymbolInstance::at(point(x, 10.0)), SymbolInstance::at(point(x, 20.0))]);
+        let mut scene = Scene::new(PictureResolution::new(500.0, 500.0));
+        let mut tessellator = SceneTessellator::new(scene.picture_resolution);
+        let plain = scene.insert(square((0.0, 0.0), 50.0, &ShapeStyle::fill(RGBA::GREEN)));
+        let first = scene.insert(dots(&dot, 5.0));
+        let grouped = scene.insert(GroupOp::new([square((60.0, 0.0), 20.0, &ShapeStyle::fill(RGBA::BLUE)), dots(&dot, 65.0).into()]).with_opacity(0.5));
+        let last = scene.insert(dots(&dot, 100.0));
+        sync_and_check_drawn(&mut scene, &mut tessellator);
+        // The symbol heads the buffers, ahead of every object.
+        assert_eq!(tessellator.symbols[&dot.id()].indices.start, 0);
+        assert!(scene.range(plain).unwrap().indices.start > 0);
+
+        // Edits above the head leave it alone.
+        let head = tessellator.symbols.clone();
+        assert!(scene.update(last, dots(&dot, 200.0)).is_some());
+        let changes = scene.sync(&mut tessellator).unwrap();
+        assert_eq!(changes.indices.start, scene.range(grouped).unwrap().indices.end);
+        assert_eq!(tessellator.symbols, head);
+        scene.remove(first);
+        sync_and_check_drawn(&mut scene, &mut tessellator);
+        assert_eq!(tessellator.symbols, head);
+
+        // A new symbol rewrites everything, then is evicted with its last
+        // user.
+        let ringed = scene.insert_at(1, GroupOp::new([dots(&ring, 30.0).into(), dots(&ring, 40.0).into()]));
+        let changes = scene.sync(&mut tessellator).unwrap();
+        assert_eq!(changes.indices.start, 0);
+        assert_eq!(tessellator.symbols.len(), 2);
+        sync_and_check_drawn(&mut scene, &mut tessellator);
+        scene.remove(ringed);
+        sync_and_check_drawn(&mut scene, &mut tessellator);
+        assert!(!tessellator.symbols.contains_key(&ring.id()));
+
+        // Swapping one symbol for another in an update.
+        assert!(scene.update(last, dots(&ring, 100.0)).is_some());
+        sync_and_check_drawn(&mut scene, &mut tessellator);
+        assert!(scene.update(grouped, dots(&ring, 60.0)).is_some());
+        sync_and_check_drawn(&mut scene, &mut tessellator);
+        assert!(!tessellator.symbols.contains_key(&dot.id()));
+        scene.remove(last);
+        scene.remove(grouped);
+        sync_and_check_drawn(&mut scene, &mut tessellator);
+        assert!(tessellator.symbols.is_empty());
+    }
 
+    #[test]
+    fn background_levels_share_symbols() {
+        let dot = Symbol::new(Shape::Circle { center: point(0.0, 0.0).into(), radius: 3.0 }.draw(&ShapeStyle::fill(RGBA::BLACK)));
+        let dots = |x: f32| InstancesOp::new(dot.clone(), [SymbolInstance::at(point(x, 10.0))]);
+        let ops = [dots(5.0).into(), square((0.0, 0.0), 50.0, &ShapeStyle::fill(RGBA::GREEN)), dots(50.0).into()];
+        let mut scene = Scene::new(PictureResolution::new(500.0, 500.0));
+        let mut tessellator = SceneTessellator::new(scene.picture_resolution);
+        for op in ops.iter() {
+            scene.insert(op.clone());
+        }
+        scene.sync(&mut tessellator);
+        let level = LodLevel(3);
+        scene.request_lod(level);
+        while scene.lod() != Some(level) {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            scene.sync(&mut tessellator);
+        }
+        let mut expected_scene = Scene::new(scene.picture_resolution);
+        let mut expected = SceneTessellator::new(scene.picture_resolution);
+        for op in ops.iter() {
+            expected_scene.insert(op.clone());
+        }
+        expected_scene.set_lod(Some(level));
+        expected_scene.sync(&mut expected);
+        assert_eq!(drawn(&tessellator), drawn(&expected));
+        assert_eq!(tessellator.mesh.indices, expected.mesh.indices);
+        assert_eq!(tessellator.symbols, expected.symbols);
+    }
+}
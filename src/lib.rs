@@ -15,6 +15,6 @@ use wgpu::util::DeviceExt;
 use std::f64::NAN;
 
 pub use data::View;
-pub use data::{ViewResolution, PictureResolution};
+pub use data::{LogicalSize, ViewInfo, ViewResolution, PictureResolution};
 pub use data::{Picture, TesselationSettings, TessellatedPicture};
 
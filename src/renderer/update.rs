@@ -22,7 +22,7 @@ use std::f64::NAN;
 
 impl RendererContext {
     pub fn update(&mut self, latest_view: View) {
-        let _ = self.gpu_handle.update(latest_view.view_resolution, self.msaa_samples, &mut self.msaa_texture);
+        let _ = self.gpu_handle.update(latest_view.view_info, self.msaa_samples, &mut self.msaa_texture);
         let (mut state_hint, mut renderer_state) = self.state.take()
             .map(|x| (x.needs_update(&latest_view), x))
             .unwrap_or_else(|| {